@@ -0,0 +1,746 @@
+use crate::events::{read_trace, root_value};
+use crate::json_deserialize::{BaseInfo, DiffInfo, Edge, Node, Root, ShapeHint, Style, StyleChange, StyleTarget};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::collections::HashMap;
+use std::error::Error;
+use std::time::Instant;
+use trace::diff::{LabelChange, WeightChange, split_legacy};
+
+//"PLTR", the version, the string table and then the model. ids and counts are varints,
+//labels, weights and notes are indexes into the string table. every listener is one block
+//with its byte length in front, so a reader can skip listeners it does not need.
+//version 1 wrote a changed label or weight as one "old===new" string and versions 1 and 2
+//had no labels in a base, both are still read
+const MAGIC: &[u8; 4] = b"PLTR";
+const VERSION: u8 = 3;
+
+//which style fields follow
+const FILL: u8 = 1;
+const STROKE: u8 = 2;
+const SHAPE: u8 = 4;
+const SIZE: u8 = 8;
+const THICKNESS: u8 = 16;
+
+//which optional diff fields follow
+const SEQ: u8 = 1;
+const TIMESTAMP: u8 = 2;
+
+const NODE_TARGET: u8 = 0;
+const EDGE_TARGET: u8 = 1;
+
+pub fn is_binary(start: &[u8]) -> bool {
+  start.starts_with(MAGIC)
+}
+
+pub fn read_binary(path: &str) -> Result<Root, Box<dyn Error>> {
+  let bytes = std::fs::read(path)?;
+  Ok(decode(&bytes)?)
+}
+
+pub fn write_binary(root: &Root, path: &str) -> Result<(), Box<dyn Error>> {
+  std::fs::write(path, encode(root))?;
+  Ok(())
+}
+
+fn put_varint(bytes: &mut Vec<u8>, mut value: u64) {
+  loop {
+    let low = (value & 0x7f) as u8;
+    value >>= 7;
+    if value == 0 {
+      bytes.push(low);
+      return;
+    }
+    bytes.push(low | 0x80);
+  }
+}
+
+#[derive(Default)]
+struct Encoder {
+  bytes: Vec<u8>,
+  strings: Vec<String>,
+  string_ids: HashMap<String, u64>,
+  version: u8, //older ones are only written to check they are still read
+}
+
+impl Encoder {
+  fn varint(&mut self, value: u64) {
+    put_varint(&mut self.bytes, value);
+  }
+
+  fn string(&mut self, text: &str) {
+    let id = match self.string_ids.get(text) {
+      Some(id) => *id,
+      None => {
+        let id = self.strings.len() as u64;
+        self.strings.push(text.to_string());
+        self.string_ids.insert(text.to_string(), id);
+        id
+      }
+    };
+    self.varint(id);
+  }
+
+  fn f32(&mut self, value: f32) {
+    self.bytes.extend_from_slice(&value.to_le_bytes());
+  }
+
+  fn style(&mut self, style: &Style) {
+    let flag = |set: bool, bit: u8| if set { bit } else { 0 };
+    self.bytes.push(
+      flag(style.fill.is_some(), FILL)
+        | flag(style.stroke.is_some(), STROKE)
+        | flag(style.shape.is_some(), SHAPE)
+        | flag(style.size.is_some(), SIZE)
+        | flag(style.thickness.is_some(), THICKNESS),
+    );
+    for (r, g, b) in [style.fill, style.stroke].into_iter().flatten() {
+      self.f32(r);
+      self.f32(g);
+      self.f32(b);
+    }
+    if let Some(shape) = style.shape {
+      self.bytes.push(match shape {
+        ShapeHint::Circle => 0,
+        ShapeHint::Square => 1,
+        ShapeHint::Rectangle => 2,
+      });
+    }
+    if let Some((width, height)) = style.size {
+      self.f32(width);
+      self.f32(height);
+    }
+    if let Some(thickness) = style.thickness {
+      self.f32(thickness);
+    }
+  }
+
+  fn edges(&mut self, edges: &[Edge]) {
+    self.varint(edges.len() as u64);
+    for edge in edges {
+      self.varint(edge.from_id as u64);
+      self.varint(edge.to_id as u64);
+      self.string(&edge.weight);
+    }
+  }
+
+  fn nodes(&mut self, nodes: &[Node]) {
+    self.varint(nodes.len() as u64);
+    for node in nodes {
+      self.varint(node.id as u64);
+      self.string(&node.label);
+    }
+  }
+
+  fn weight_changes(&mut self, changes: &[WeightChange]) {
+    if self.version == 1 {
+      let edges: Vec<Edge> = changes.iter()
+        .map(|change| Edge { from_id: change.from_id, to_id: change.to_id, weight: format!("{}==={}", change.old, change.new) })
+        .collect();
+      return self.edges(&edges);
+    }
+    self.varint(changes.len() as u64);
+    for change in changes {
+      self.varint(change.from_id as u64);
+      self.varint(change.to_id as u64);
+      self.string(&change.old);
+      self.string(&change.new);
+    }
+  }
+
+  fn label_changes(&mut self, changes: &[LabelChange]) {
+    if self.version == 1 {
+      let nodes: Vec<Node> = changes.iter()
+        .map(|change| Node { id: change.id, label: format!("{}==={}", change.old, change.new) })
+        .collect();
+      return self.nodes(&nodes);
+    }
+    self.varint(changes.len() as u64);
+    for change in changes {
+      self.varint(change.id as u64);
+      self.string(&change.old);
+      self.string(&change.new);
+    }
+  }
+
+  fn base(&mut self, base: &BaseInfo) {
+    self.varint(base.root_id as u64);
+    self.string(&base.note);
+    let mut from_ids: Vec<&u32> = base.edges.keys().collect();
+    from_ids.sort();
+    self.varint(from_ids.len() as u64);
+    for from_id in from_ids {
+      let mut connections: Vec<(&u32, &String)> = base.edges[from_id].iter().collect();
+      connections.sort();
+      self.varint(*from_id as u64);
+      self.varint(connections.len() as u64);
+      for (to_id, weight) in connections {
+        self.varint(*to_id as u64);
+        self.string(weight);
+      }
+    }
+    if self.version < 3 {
+      return;
+    }
+    let mut labels: Vec<(&u32, &String)> = base.labels.iter().collect();
+    labels.sort();
+    self.varint(labels.len() as u64);
+    for (id, label) in labels {
+      self.varint(*id as u64);
+      self.string(label);
+    }
+  }
+
+  fn diff(&mut self, diff: &DiffInfo) {
+    self.edges(&diff.added_edges);
+    self.edges(&diff.removed_edges);
+    self.nodes(&diff.added_nodes);
+    self.nodes(&diff.removed_nodes);
+    self.weight_changes(&diff.weights_changed);
+    self.label_changes(&diff.labels_changed);
+    self.varint(diff.styles_changed.len() as u64);
+    for change in &diff.styles_changed {
+      match change.target {
+        StyleTarget::Node(id) => {
+          self.bytes.push(NODE_TARGET);
+          self.varint(id as u64);
+        }
+        StyleTarget::Edge(from_id, to_id) => {
+          self.bytes.push(EDGE_TARGET);
+          self.varint(from_id as u64);
+          self.varint(to_id as u64);
+        }
+      }
+      self.style(&change.style);
+    }
+    self.string(&diff.note);
+    self.varint(diff.root_id as u64);
+    let flag = |set: bool, bit: u8| if set { bit } else { 0 };
+    self.bytes.push(flag(diff.seq.is_some(), SEQ) | flag(diff.timestamp.is_some(), TIMESTAMP));
+    if let Some(seq) = diff.seq {
+      self.varint(seq);
+    }
+    if let Some(timestamp) = diff.timestamp {
+      self.bytes.extend_from_slice(&timestamp.to_le_bytes());
+    }
+  }
+}
+
+//the same root always encodes to the same bytes
+pub fn encode(root: &Root) -> Vec<u8> {
+  encode_version(root, VERSION)
+}
+
+fn encode_version(root: &Root, version: u8) -> Vec<u8> {
+  let mut encoder = Encoder { version, ..Encoder::default() };
+  encoder.varint(root.nodes.len() as u64);
+  for (id, label) in root.nodes.iter().enumerate() {
+    encoder.string(label);
+    encoder.style(&root.node_styles.get(id).copied().unwrap_or_default());
+  }
+  let mut edge_styles: Vec<(&(u32, u32), &Style)> = root.edge_styles.iter().collect();
+  edge_styles.sort_by_key(|(ends, _)| **ends);
+  encoder.varint(edge_styles.len() as u64);
+  for (&(from_id, to_id), style) in edge_styles {
+    encoder.varint(from_id as u64);
+    encoder.varint(to_id as u64);
+    encoder.style(style);
+  }
+
+  encoder.varint(root.total_listeners as u64);
+  for listener_id in 0..root.total_listeners as usize {
+    let outer = std::mem::take(&mut encoder.bytes);
+    encoder.base(&root.bases[listener_id]);
+    encoder.varint(root.diffs[listener_id].len() as u64);
+    for diff in &root.diffs[listener_id] {
+      encoder.diff(diff);
+    }
+    let block = std::mem::replace(&mut encoder.bytes, outer);
+    encoder.varint(block.len() as u64);
+    encoder.bytes.extend_from_slice(&block);
+  }
+
+  let mut bytes = Vec::with_capacity(encoder.bytes.len() + 64);
+  bytes.extend_from_slice(MAGIC);
+  bytes.push(version);
+  put_varint(&mut bytes, encoder.strings.len() as u64);
+  for text in &encoder.strings {
+    put_varint(&mut bytes, text.len() as u64);
+    bytes.extend_from_slice(text.as_bytes());
+  }
+  bytes.extend_from_slice(&encoder.bytes);
+  bytes
+}
+
+struct Decoder<'a> {
+  bytes: &'a [u8],
+  at: usize,
+  strings: &'a [String],
+  version: u8,
+}
+
+impl<'a> Decoder<'a> {
+  fn take(&mut self, count: usize) -> Result<&'a [u8], String> {
+    if count > self.bytes.len() - self.at {
+      return Err(String::from("the trace ends early"));
+    }
+    let taken = &self.bytes[self.at..self.at + count];
+    self.at += count;
+    Ok(taken)
+  }
+
+  fn u8(&mut self) -> Result<u8, String> {
+    Ok(self.take(1)?[0])
+  }
+
+  fn varint(&mut self) -> Result<u64, String> {
+    let mut value = 0u64;
+    for shift in (0..64).step_by(7) {
+      let byte = self.u8()?;
+      value |= ((byte & 0x7f) as u64) << shift;
+      if byte & 0x80 == 0 {
+        return Ok(value);
+      }
+    }
+    Err(format!("varint at byte {} is too long", self.at))
+  }
+
+  fn id(&mut self) -> Result<u32, String> {
+    let value = self.varint()?;
+    u32::try_from(value).map_err(|_| format!("id {} does not fit", value))
+  }
+
+  //every entry takes at least a byte, a count past the end is broken and is not allocated for
+  fn count(&mut self) -> Result<usize, String> {
+    let count = self.varint()?;
+    if count > (self.bytes.len() - self.at) as u64 {
+      return Err(format!("count {} at byte {} runs past the end", count, self.at));
+    }
+    Ok(count as usize)
+  }
+
+  fn f32(&mut self) -> Result<f32, String> {
+    Ok(f32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+  }
+
+  fn f64(&mut self) -> Result<f64, String> {
+    Ok(f64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+  }
+
+  fn string(&mut self) -> Result<String, String> {
+    let index = self.varint()?;
+    self.strings.get(index as usize).cloned().ok_or(format!("string {} is not in the table", index))
+  }
+
+  fn string_table(&mut self) -> Result<Vec<String>, String> {
+    let count = self.count()?;
+    let mut strings = Vec::with_capacity(count);
+    for _ in 0..count {
+      let length = self.count()?;
+      let text = std::str::from_utf8(self.take(length)?).map_err(|e| e.to_string())?;
+      strings.push(text.to_string());
+    }
+    Ok(strings)
+  }
+
+  fn color(&mut self) -> Result<(f32, f32, f32), String> {
+    Ok((self.f32()?, self.f32()?, self.f32()?))
+  }
+
+  fn style(&mut self) -> Result<Style, String> {
+    let flags = self.u8()?;
+    if flags & !(FILL | STROKE | SHAPE | SIZE | THICKNESS) != 0 {
+      return Err(format!("unknown style flags {:#x}", flags));
+    }
+    let mut style = Style::default();
+    if flags & FILL != 0 {
+      style.fill = Some(self.color()?);
+    }
+    if flags & STROKE != 0 {
+      style.stroke = Some(self.color()?);
+    }
+    if flags & SHAPE != 0 {
+      style.shape = Some(match self.u8()? {
+        0 => ShapeHint::Circle,
+        1 => ShapeHint::Square,
+        2 => ShapeHint::Rectangle,
+        other => return Err(format!("unknown shape {}", other)),
+      });
+    }
+    if flags & SIZE != 0 {
+      style.size = Some((self.f32()?, self.f32()?));
+    }
+    if flags & THICKNESS != 0 {
+      style.thickness = Some(self.f32()?);
+    }
+    Ok(style)
+  }
+
+  fn edges(&mut self) -> Result<Vec<Edge>, String> {
+    let count = self.count()?;
+    let mut edges = Vec::with_capacity(count);
+    for _ in 0..count {
+      edges.push(Edge { from_id: self.id()?, to_id: self.id()?, weight: self.string()? });
+    }
+    Ok(edges)
+  }
+
+  fn nodes(&mut self) -> Result<Vec<Node>, String> {
+    let count = self.count()?;
+    let mut nodes = Vec::with_capacity(count);
+    for _ in 0..count {
+      nodes.push(Node { id: self.id()?, label: self.string()? });
+    }
+    Ok(nodes)
+  }
+
+  fn weight_changes(&mut self) -> Result<Vec<WeightChange>, String> {
+    if self.version == 1 {
+      return Ok(self.edges()?.into_iter().map(|edge| {
+        let (old, new) = split_legacy(&edge.weight);
+        WeightChange { from_id: edge.from_id, to_id: edge.to_id, old: old.to_string(), new: new.to_string() }
+      }).collect());
+    }
+    let count = self.count()?;
+    let mut changes = Vec::with_capacity(count);
+    for _ in 0..count {
+      changes.push(WeightChange { from_id: self.id()?, to_id: self.id()?, old: self.string()?, new: self.string()? });
+    }
+    Ok(changes)
+  }
+
+  fn label_changes(&mut self) -> Result<Vec<LabelChange>, String> {
+    if self.version == 1 {
+      return Ok(self.nodes()?.into_iter().map(|node| {
+        let (old, new) = split_legacy(&node.label);
+        LabelChange { id: node.id, old: old.to_string(), new: new.to_string() }
+      }).collect());
+    }
+    let count = self.count()?;
+    let mut changes = Vec::with_capacity(count);
+    for _ in 0..count {
+      changes.push(LabelChange { id: self.id()?, old: self.string()?, new: self.string()? });
+    }
+    Ok(changes)
+  }
+
+  fn base(&mut self) -> Result<BaseInfo, String> {
+    let mut base = BaseInfo { root_id: self.id()?, note: self.string()?, ..BaseInfo::default() };
+    for _ in 0..self.count()? {
+      let from_id = self.id()?;
+      let mut connections = HashMap::new();
+      for _ in 0..self.count()? {
+        connections.insert(self.id()?, self.string()?);
+      }
+      base.edges.insert(from_id, connections);
+    }
+    if self.version >= 3 {
+      for _ in 0..self.count()? {
+        base.labels.insert(self.id()?, self.string()?);
+      }
+    }
+    Ok(base)
+  }
+
+  fn diff(&mut self) -> Result<DiffInfo, String> {
+    let mut diff = DiffInfo {
+      added_edges: self.edges()?,
+      removed_edges: self.edges()?,
+      added_nodes: self.nodes()?,
+      removed_nodes: self.nodes()?,
+      weights_changed: self.weight_changes()?,
+      labels_changed: self.label_changes()?,
+      ..DiffInfo::default()
+    };
+    for _ in 0..self.count()? {
+      let target = match self.u8()? {
+        NODE_TARGET => StyleTarget::Node(self.id()?),
+        EDGE_TARGET => StyleTarget::Edge(self.id()?, self.id()?),
+        other => return Err(format!("unknown style target {}", other)),
+      };
+      diff.styles_changed.push(StyleChange { target, style: self.style()? });
+    }
+    diff.note = self.string()?;
+    diff.root_id = self.id()?;
+    let flags = self.u8()?;
+    if flags & !(SEQ | TIMESTAMP) != 0 {
+      return Err(format!("unknown diff flags {:#x}", flags));
+    }
+    if flags & SEQ != 0 {
+      diff.seq = Some(self.varint()?);
+    }
+    if flags & TIMESTAMP != 0 {
+      diff.timestamp = Some(self.f64()?);
+    }
+    Ok(diff)
+  }
+}
+
+pub fn decode(bytes: &[u8]) -> Result<Root, String> {
+  let mut header = Decoder { bytes, at: 0, strings: &[], version: VERSION };
+  if header.take(MAGIC.len()).ok() != Some(MAGIC.as_slice()) {
+    return Err(String::from("not a binary trace"));
+  }
+  let version = header.u8()?;
+  if version == 0 || version > VERSION {
+    return Err(format!("binary trace version {} is not 1 to {}", version, VERSION));
+  }
+  let strings = header.string_table()?;
+  let mut decoder = Decoder { bytes, at: header.at, strings: &strings, version };
+
+  let mut root = Root::new(0);
+  let node_count = decoder.count()?;
+  for _ in 0..node_count {
+    root.nodes.push(decoder.string()?);
+    root.node_styles.push(decoder.style()?);
+  }
+  for _ in 0..decoder.count()? {
+    let ends = (decoder.id()?, decoder.id()?);
+    root.edge_styles.insert(ends, decoder.style()?);
+  }
+
+  let total_listeners = decoder.varint()?;
+  if total_listeners > u8::MAX as u64 {
+    return Err(format!("{} listeners are more than a trace can have", total_listeners));
+  }
+  for listener_id in 0..total_listeners as u8 {
+    let length = decoder.count()?;
+    let mut block = Decoder { bytes: decoder.take(length)?, at: 0, strings: &strings, version };
+    let in_block = |message: String| format!("listener {}: {}", listener_id, message);
+    root.ensure_listener(listener_id);
+    root.bases[listener_id as usize] = block.base().map_err(in_block)?;
+    for _ in 0..block.count().map_err(in_block)? {
+      let diff = block.diff().map_err(in_block)?;
+      root.push_diff(listener_id as usize, diff);
+    }
+    if block.at != length {
+      return Err(in_block(format!("{} bytes left over", length - block.at)));
+    }
+  }
+  if decoder.at != bytes.len() {
+    return Err(format!("{} bytes after the last listener", bytes.len() - decoder.at));
+  }
+  root.check()?;
+  Ok(root)
+}
+
+const RANDOM_SEED: u64 = 0x7ace;
+const LABELS: [&str; 9] = ["", "0", "17", "-4", "left", "right", "ağaç", "a much longer label for one node", "a===b"];
+
+fn random_style(rng: &mut StdRng) -> Style {
+  let color = |rng: &mut StdRng| rng.random_bool(0.5).then(|| (rng.random(), rng.random(), rng.random()));
+  Style {
+    fill: color(rng),
+    stroke: color(rng),
+    shape: match rng.random_range(0..4) {
+      0 => Some(ShapeHint::Circle),
+      1 => Some(ShapeHint::Square),
+      2 => Some(ShapeHint::Rectangle),
+      _ => None,
+    },
+    size: rng.random_bool(0.3).then(|| (rng.random_range(1.0..80.0), rng.random_range(1.0..80.0))),
+    thickness: rng.random_bool(0.3).then(|| rng.random_range(0.5..8.0)),
+  }
+}
+
+fn random_label(rng: &mut StdRng) -> String {
+  match rng.random_range(0..3) {
+    0 => rng.random_range(0..100000u32).to_string(),
+    _ => LABELS[rng.random_range(0..LABELS.len())].to_string(),
+  }
+}
+
+//up to most - 1 of them
+fn random_edges(rng: &mut StdRng, nodes: u32, most: usize) -> Vec<Edge> {
+  (0..rng.random_range(0..most))
+    .map(|_| Edge { from_id: rng.random_range(0..nodes), to_id: rng.random_range(0..nodes), weight: random_label(rng) })
+    .collect()
+}
+
+fn random_nodes(rng: &mut StdRng, nodes: u32, most: usize) -> Vec<Node> {
+  (0..rng.random_range(0..most)).map(|_| Node { id: rng.random_range(0..nodes), label: random_label(rng) }).collect()
+}
+
+fn random_weight_changes(rng: &mut StdRng, nodes: u32, most: usize) -> Vec<WeightChange> {
+  (0..rng.random_range(0..most))
+    .map(|_| WeightChange { from_id: rng.random_range(0..nodes), to_id: rng.random_range(0..nodes), old: random_label(rng), new: random_label(rng) })
+    .collect()
+}
+
+fn random_label_changes(rng: &mut StdRng, nodes: u32, most: usize) -> Vec<LabelChange> {
+  (0..rng.random_range(0..most))
+    .map(|_| LabelChange { id: rng.random_range(0..nodes), old: random_label(rng), new: random_label(rng) })
+    .collect()
+}
+
+//any combination the model allows, not only ones a real program would produce
+fn random_root(rng: &mut StdRng, diffs_per_listener: usize) -> Root {
+  let mut root = Root::new(rng.random_range(1..5));
+  let nodes = rng.random_range(1..40u32);
+  for id in 0..nodes {
+    let style = if rng.random_bool(0.3) { random_style(rng) } else { Style::default() };
+    root.register_node(id, random_label(rng), style);
+  }
+  for _ in 0..rng.random_range(0..5) {
+    let ends = (rng.random_range(0..nodes), rng.random_range(0..nodes));
+    root.edge_styles.insert(ends, random_style(rng));
+  }
+  //a trace is ordered by seq, by timestamps or by neither, the other one may still be on some diffs.
+  //both only go up, a listener's diffs never step back in either
+  let (every_seq, every_timestamp) = match rng.random_range(0..3) {
+    0 => (true, false),
+    1 => (false, true),
+    _ => (false, false),
+  };
+  let unordered = !every_seq && !every_timestamp;
+  let (mut seq, mut timestamp) = (0, 0.0);
+  for listener_id in 0..root.total_listeners as usize {
+    let base = &mut root.bases[listener_id];
+    base.root_id = rng.random_range(0..nodes);
+    base.note = random_label(rng);
+    for edge in random_edges(rng, nodes, 20) {
+      base.edges.entry(edge.from_id).or_default().insert(edge.to_id, edge.weight);
+    }
+    for _ in 0..rng.random_range(0..3) {
+      base.labels.insert(rng.random_range(0..nodes), random_label(rng));
+    }
+    for _ in 0..diffs_per_listener {
+      let mut diff = DiffInfo {
+        added_edges: random_edges(rng, nodes, 4),
+        removed_edges: random_edges(rng, nodes, 3),
+        added_nodes: random_nodes(rng, nodes, 3),
+        removed_nodes: random_nodes(rng, nodes, 2),
+        weights_changed: random_weight_changes(rng, nodes, 3),
+        labels_changed: random_label_changes(rng, nodes, 3),
+        note: random_label(rng),
+        root_id: rng.random_range(0..nodes),
+        ..DiffInfo::default()
+      };
+      for _ in 0..rng.random_range(0..3) {
+        let target = if rng.random_bool(0.5) {
+          StyleTarget::Node(rng.random_range(0..nodes))
+        } else {
+          StyleTarget::Edge(rng.random_range(0..nodes), rng.random_range(0..nodes))
+        };
+        diff.styles_changed.push(StyleChange { target, style: random_style(rng) });
+      }
+      seq += rng.random_range(1..1000u64);
+      timestamp += rng.random_range(0.0..1e6);
+      diff.seq = (every_seq || (!unordered && rng.random_bool(0.5))).then_some(seq);
+      diff.timestamp = (every_timestamp || (!unordered && rng.random_bool(0.5))).then_some(timestamp);
+      root.push_diff(listener_id, diff);
+    }
+  }
+  root
+}
+
+//--binary-bench [diffs], reads a trace the size of a long hash table recording from json and from binary
+pub fn run_bench_command(args: &[String]) -> i32 {
+  let diffs = match args.first().map(|arg| arg.parse::<usize>()) {
+    None => 20000,
+    Some(Ok(diffs)) => diffs,
+    Some(Err(_)) => {
+      println!("usage: --binary-bench [diffs]");
+      return 2;
+    }
+  };
+  let mut rng = StdRng::seed_from_u64(RANDOM_SEED);
+  let large = random_root(&mut rng, diffs);
+  let directory = std::env::temp_dir();
+  let json_path = directory.join("parse_listen_binary_bench.json");
+  let binary_path = directory.join("parse_listen_binary_bench.pltr");
+  let (json_path, binary_path) = (json_path.to_string_lossy(), binary_path.to_string_lossy());
+  let written = std::fs::write(json_path.as_ref(), root_value(&large).to_string())
+    .map_err(|e| e.to_string())
+    .and_then(|_| write_binary(&large, &binary_path).map_err(|e| e.to_string()));
+  if let Err(e) = written {
+    println!("Could not write the large trace: {}", e);
+    return 2;
+  }
+  let started = Instant::now();
+  let from_json = read_trace(&json_path);
+  let json_time = started.elapsed();
+  let started = Instant::now();
+  let from_binary = read_trace(&binary_path);
+  let binary_time = started.elapsed();
+  let size = |path: &str| std::fs::metadata(path).map(|metadata| metadata.len()).unwrap_or(0);
+  let sizes = (size(&json_path), size(&binary_path));
+  let _ = std::fs::remove_file(json_path.as_ref());
+  let _ = std::fs::remove_file(binary_path.as_ref());
+  if let Err(e) = from_json.and(from_binary) {
+    println!("The large trace does not read back: {}", e);
+    return 1;
+  }
+  println!("{} diffs per listener  json / binary", diffs);
+  println!("  size  {} / {} bytes", sizes.0, sizes.1);
+  println!("  read  {:?} / {:?}", json_time, binary_time);
+  0
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn random_roots(count: usize) -> Vec<Root> {
+    let mut rng = StdRng::seed_from_u64(RANDOM_SEED);
+    (0..count)
+      .map(|_| {
+        let diffs = rng.random_range(0..12);
+        random_root(&mut rng, diffs)
+      })
+      .collect()
+  }
+
+  #[test]
+  fn random_traces_read_back_the_same() {
+    for (case, root) in random_roots(300).iter().enumerate() {
+      let bytes = encode(root);
+      let decoded = decode(&bytes).unwrap_or_else(|message| panic!("case {} does not read back: {}", case, message));
+      assert_eq!(root_value(&decoded), root_value(root), "case {} reads back different", case);
+      assert_eq!(encode(&decoded), bytes, "case {} encodes differently the second time", case);
+    }
+  }
+
+  //a cut off file always fails, it never panics or reads as a shorter trace
+  #[test]
+  fn cut_off_traces_do_not_read() {
+    for (case, root) in random_roots(20).iter().enumerate() {
+      let bytes = encode(root);
+      for length in 0..bytes.len() {
+        assert!(decode(&bytes[..length]).is_err(), "case {} cut to {} bytes still reads", case, length);
+      }
+    }
+  }
+
+  #[test]
+  fn unknown_versions_are_refused() {
+    for root in random_roots(20) {
+      let mut bytes = encode(&root);
+      for version in [0, VERSION + 1, u8::MAX] {
+        bytes[MAGIC.len()] = version;
+        assert_eq!(decode(&bytes).err(), Some(format!("binary trace version {} is not 1 to {}", version, VERSION)));
+      }
+    }
+  }
+
+  //version 2 had no base labels. version 1 also cut a change at its first ===, so one whose old
+  //text has a === in it does not come back the same
+  #[test]
+  fn older_versions_still_read() {
+    for (case, mut root) in random_roots(300).into_iter().enumerate() {
+      for base in root.bases.iter_mut() {
+        base.labels.clear();
+      }
+      for version in [2, 1] {
+        if version == 1 {
+          for diff in root.diffs.iter_mut().flatten() {
+            diff.labels_changed.retain(|change| !change.old.contains("==="));
+            diff.weights_changed.retain(|change| !change.old.contains("==="));
+          }
+        }
+        let decoded = decode(&encode_version(&root, version));
+        assert_eq!(decoded.map(|decoded| root_value(&decoded)), Ok(root_value(&root)), "case {} as version {}", case, version);
+      }
+    }
+  }
+}
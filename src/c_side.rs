@@ -127,6 +127,7 @@ unsafe extern "C" {
   pub fn rotate_text(text_id: c_uint, angle: c_float);
   pub fn remove_text(text_id: c_uint);
   pub fn scale_text(text_id: c_uint, pixel_height: c_int);
+  pub fn color_text(r: c_float, g: c_float, b: c_float); // every text shares one color
   pub fn get_text(text_id: c_uint) -> *mut Text;
   pub fn cleanup_text(); 
   pub fn override_sprite(sprite_id: c_uint, x: c_float, y: c_float, width: c_float, height: c_float, thick: c_float, r: c_float, g: c_float, b: c_float);
@@ -0,0 +1,766 @@
+use crate::json_deserialize::{BaseInfo, DiffInfo, Edge, Node, Root, ShapeHint, Style};
+use std::collections::{HashMap, HashSet, VecDeque, btree_map};
+use std::error::Error;
+use trace::diff::{Diff, Snapshot, diff};
+
+//graphviz files as a single listener trace. every graph is a step, a file may hold several
+//and several files follow each other. nodes keep their id across steps by name, a label
+//attribute is the node's label and an edge's label its weight. the viewer draws every edge
+//with an arrow, an edge of an undirected graph points the way it was written
+pub fn is_dot_path(path: &str) -> bool {
+  let path = path.to_ascii_lowercase();
+  path.ends_with(".dot") || path.ends_with(".gv")
+}
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct DotNode {
+  pub name: String,
+  pub label: Option<String>,
+}
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct DotGraph {
+  pub name: Option<String>,
+  pub directed: bool,
+  pub label: Option<String>,
+  pub nodes: Vec<DotNode>, //in the order they first appear
+  pub edges: Vec<(usize, usize, Option<String>)>, //indexes into nodes and the label
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+  Id(String),
+  Keyword(&'static str), //graph, digraph, subgraph, node, edge and strict in any case
+  EdgeOp(bool),          //-> or --
+  Punct(char),
+}
+
+const KEYWORDS: [&str; 6] = ["graph", "digraph", "subgraph", "node", "edge", "strict"];
+
+fn tokenize(text: &str) -> Result<Vec<(Token, usize)>, String> {
+  let chars: Vec<char> = text.chars().collect();
+  let mut tokens = Vec::new();
+  let mut at = 0;
+  let mut line = 1;
+  let mut line_start = true; //only whitespace since the line began
+  while at < chars.len() {
+    let c = chars[at];
+    if c == '\n' {
+      line += 1;
+      line_start = true;
+      at += 1;
+      continue;
+    }
+    if c.is_whitespace() {
+      at += 1;
+      continue;
+    }
+    //a # at the start of a line is left over from the c preprocessor
+    if (c == '#' && line_start) || (c == '/' && chars.get(at + 1) == Some(&'/')) {
+      while at < chars.len() && chars[at] != '\n' {
+        at += 1;
+      }
+      continue;
+    }
+    line_start = false;
+    if c == '/' && chars.get(at + 1) == Some(&'*') {
+      let start_line = line;
+      at += 2;
+      while at < chars.len() && !(chars[at] == '*' && chars.get(at + 1) == Some(&'/')) {
+        line += (chars[at] == '\n') as usize;
+        at += 1;
+      }
+      if at >= chars.len() {
+        return Err(format!("line {}: the comment is never closed", start_line));
+      }
+      at += 2;
+      continue;
+    }
+    let start_line = line;
+    if c == '"' {
+      //"a" + "b" is one id, \" is a quote and a backslash before a line break joins the lines.
+      //other escapes stay for the label to handle
+      let mut id = String::new();
+      loop {
+        at += 1;
+        loop {
+          match chars.get(at) {
+            None => return Err(format!("line {}: the string is never closed", start_line)),
+            Some('"') => break,
+            Some('\\') if chars.get(at + 1) == Some(&'"') => {
+              id.push('"');
+              at += 1;
+            }
+            Some('\\') if chars.get(at + 1) == Some(&'\n') => {
+              line += 1;
+              at += 1;
+            }
+            Some(other) => {
+              line += (*other == '\n') as usize;
+              id.push(*other);
+            }
+          }
+          at += 1;
+        }
+        at += 1;
+        let mut next = at;
+        while chars.get(next).is_some_and(|c| c.is_whitespace()) {
+          next += 1;
+        }
+        if chars.get(next) != Some(&'+') {
+          break;
+        }
+        next += 1;
+        while chars.get(next).is_some_and(|c| c.is_whitespace()) {
+          next += 1;
+        }
+        if chars.get(next) != Some(&'"') {
+          return Err(format!("line {}: + has to join two strings", line));
+        }
+        line += chars[at..next].iter().filter(|c| **c == '\n').count();
+        at = next;
+      }
+      tokens.push((Token::Id(id), start_line));
+      continue;
+    }
+    if c == '<' {
+      //an html label, the markup is dropped
+      let mut depth = 0;
+      let mut id = String::new();
+      let mut in_tag = false;
+      loop {
+        match chars.get(at) {
+          None => return Err(format!("line {}: the html string is never closed", start_line)),
+          Some('<') => {
+            depth += 1;
+            in_tag = depth > 1;
+          }
+          Some('>') => {
+            depth -= 1;
+            in_tag = false;
+            if depth == 0 {
+              break;
+            }
+          }
+          Some(other) => {
+            line += (*other == '\n') as usize;
+            if !in_tag {
+              id.push(*other);
+            }
+          }
+        }
+        at += 1;
+      }
+      at += 1;
+      tokens.push((Token::Id(id.split_whitespace().collect::<Vec<_>>().join(" ")), start_line));
+      continue;
+    }
+    if c == '-' && matches!(chars.get(at + 1), Some('>') | Some('-')) {
+      tokens.push((Token::EdgeOp(chars[at + 1] == '>'), line));
+      at += 2;
+      continue;
+    }
+    if c.is_ascii_digit() || ((c == '-' || c == '.') && chars.get(at + 1).is_some_and(|c| c.is_ascii_digit() || *c == '.')) {
+      let start = at;
+      at += 1;
+      while chars.get(at).is_some_and(|c| c.is_ascii_digit() || *c == '.') {
+        at += 1;
+      }
+      tokens.push((Token::Id(chars[start..at].iter().collect()), line));
+      continue;
+    }
+    if c.is_alphabetic() || c == '_' || !c.is_ascii() {
+      let start = at;
+      while chars.get(at).is_some_and(|c| c.is_alphanumeric() || *c == '_' || !c.is_ascii()) {
+        at += 1;
+      }
+      let word: String = chars[start..at].iter().collect();
+      let token = match KEYWORDS.iter().find(|keyword| keyword.eq_ignore_ascii_case(&word)) {
+        Some(keyword) => Token::Keyword(keyword),
+        None => Token::Id(word),
+      };
+      tokens.push((token, line));
+      continue;
+    }
+    if "{}[]=;,:".contains(c) {
+      tokens.push((Token::Punct(c), line));
+      at += 1;
+      continue;
+    }
+    return Err(format!("line {}: unexpected {:?}", line, c));
+  }
+  Ok(tokens)
+}
+
+//the attributes a node or edge statement starts from, a subgraph changes them for itself only
+#[derive(Clone, Default)]
+struct Defaults {
+  node_label: Option<String>,
+  edge_label: Option<String>,
+}
+
+struct Parser {
+  tokens: Vec<(Token, usize)>,
+  at: usize,
+  graph: DotGraph,
+  ids: HashMap<String, usize>,
+  depth: usize, //subgraphs around the statement, their labels are not the graph's
+}
+
+impl Parser {
+  fn peek(&self) -> Option<&Token> {
+    self.tokens.get(self.at).map(|(token, _)| token)
+  }
+
+  fn error(&self, message: &str) -> String {
+    match self.tokens.get(self.at) {
+      Some((token, line)) => format!("line {}: {} at {:?}", line, message, token),
+      None => format!("{} at the end", message),
+    }
+  }
+
+  fn eat(&mut self, token: &Token) -> bool {
+    if self.peek() == Some(token) {
+      self.at += 1;
+      return true;
+    }
+    false
+  }
+
+  fn expect(&mut self, token: &Token, message: &str) -> Result<(), String> {
+    if self.eat(token) { Ok(()) } else { Err(self.error(message)) }
+  }
+
+  fn id(&mut self) -> Option<String> {
+    if let Some(Token::Id(id)) = self.peek() {
+      let id = id.clone();
+      self.at += 1;
+      return Some(id);
+    }
+    None
+  }
+
+  //[a=b, c=d][e=f], only label is kept
+  fn attributes(&mut self) -> Result<Option<String>, String> {
+    let mut label = None;
+    while self.eat(&Token::Punct('[')) {
+      while !self.eat(&Token::Punct(']')) {
+        let key = self.id().ok_or_else(|| self.error("expected an attribute name"))?;
+        self.expect(&Token::Punct('='), "expected =")?;
+        let value = self.id().ok_or_else(|| self.error("expected an attribute value"))?;
+        if key == "label" {
+          label = Some(value);
+        }
+        let _ = self.eat(&Token::Punct(',')) || self.eat(&Token::Punct(';'));
+      }
+    }
+    Ok(label)
+  }
+
+  fn node(&mut self, name: String, defaults: &Defaults) -> usize {
+    if let Some(index) = self.ids.get(&name) {
+      return *index;
+    }
+    let index = self.graph.nodes.len();
+    self.ids.insert(name.clone(), index);
+    self.graph.nodes.push(DotNode { name, label: defaults.node_label.clone() });
+    index
+  }
+
+  //a node with its port dropped, or a subgraph standing for every node in it
+  fn endpoint(&mut self, defaults: &Defaults) -> Result<Option<Vec<usize>>, String> {
+    if matches!(self.peek(), Some(Token::Keyword("subgraph")) | Some(Token::Punct('{'))) {
+      return self.subgraph(defaults).map(Some);
+    }
+    let Some(name) = self.id() else {
+      return Ok(None);
+    };
+    let index = self.node(name, defaults);
+    while self.eat(&Token::Punct(':')) {
+      self.id().ok_or_else(|| self.error("expected a port"))?;
+    }
+    Ok(Some(vec![index]))
+  }
+
+  fn subgraph(&mut self, defaults: &Defaults) -> Result<Vec<usize>, String> {
+    if self.eat(&Token::Keyword("subgraph")) {
+      self.id();
+    }
+    self.expect(&Token::Punct('{'), "expected {")?;
+    self.depth += 1;
+    let mentioned = self.statements(defaults.clone());
+    self.depth -= 1;
+    mentioned
+  }
+
+  //up to the closing }, the nodes that appeared in them
+  fn statements(&mut self, mut defaults: Defaults) -> Result<Vec<usize>, String> {
+    let mut mentioned = Vec::new();
+    while !self.eat(&Token::Punct('}')) {
+      if self.peek().is_none() {
+        return Err(self.error("expected }"));
+      }
+      if self.eat(&Token::Punct(';')) {
+        continue;
+      }
+      let attribute_target = match self.peek() {
+        Some(Token::Keyword(keyword @ ("graph" | "node" | "edge"))) => Some(*keyword),
+        _ => None,
+      };
+      if let Some(target) = attribute_target {
+        self.at += 1;
+        let label = self.attributes()?;
+        match target {
+          "node" => defaults.node_label = label.or(defaults.node_label),
+          "edge" => defaults.edge_label = label.or(defaults.edge_label),
+          _ if self.depth == 0 && self.graph.label.is_none() => self.graph.label = label,
+          _ => {}
+        }
+        continue;
+      }
+      //a graph attribute, label = "..."
+      if matches!(self.tokens.get(self.at + 1), Some((Token::Punct('='), _))) {
+        let key = self.id().ok_or_else(|| self.error("expected an attribute name"))?;
+        self.at += 1;
+        let value = self.id().ok_or_else(|| self.error("expected an attribute value"))?;
+        if key == "label" && self.depth == 0 && self.graph.label.is_none() {
+          self.graph.label = Some(value);
+        }
+        continue;
+      }
+
+      let is_subgraph = matches!(self.peek(), Some(Token::Keyword("subgraph")) | Some(Token::Punct('{')));
+      let mut ends = vec![self.endpoint(&defaults)?.ok_or_else(|| self.error("expected a statement"))?];
+      while let Some(Token::EdgeOp(directed)) = self.peek() {
+        if *directed != self.graph.directed {
+          return Err(self.error(if self.graph.directed { "-- in a digraph" } else { "-> in a graph" }));
+        }
+        self.at += 1;
+        ends.push(self.endpoint(&defaults)?.ok_or_else(|| self.error("expected a node or subgraph"))?);
+      }
+      let label = self.attributes()?;
+      if ends.len() == 1 {
+        if !is_subgraph && label.is_some() {
+          self.graph.nodes[ends[0][0]].label = label;
+        }
+      } else {
+        let label = label.or(defaults.edge_label.clone());
+        for pair in ends.windows(2) {
+          for from in &pair[0] {
+            for to in &pair[1] {
+              self.graph.edges.push((*from, *to, label.clone()));
+            }
+          }
+        }
+      }
+      mentioned.extend(ends.into_iter().flatten());
+    }
+    Ok(mentioned)
+  }
+}
+
+pub fn parse_dot(text: &str) -> Result<Vec<DotGraph>, String> {
+  let tokens = tokenize(text)?;
+  let mut parser = Parser { tokens, at: 0, graph: DotGraph::default(), ids: HashMap::new(), depth: 0 };
+  let mut graphs = Vec::new();
+  while parser.peek().is_some() {
+    parser.eat(&Token::Keyword("strict"));
+    parser.graph = DotGraph::default();
+    parser.ids.clear();
+    parser.graph.directed = match parser.peek() {
+      Some(Token::Keyword("digraph")) => true,
+      Some(Token::Keyword("graph")) => false,
+      _ => return Err(parser.error("expected graph or digraph")),
+    };
+    parser.at += 1;
+    parser.graph.name = parser.id();
+    parser.expect(&Token::Punct('{'), "expected {")?;
+    parser.statements(Defaults::default())?;
+    graphs.push(std::mem::take(&mut parser.graph));
+  }
+  if graphs.is_empty() {
+    return Err(String::from("no graph in the file"));
+  }
+  Ok(graphs)
+}
+
+//\N is the node's name and \G the graph's, line breaks become spaces
+fn shown_label(label: &str, name: &str, graph_name: &str) -> String {
+  label.replace("\\N", name)
+    .replace("\\G", graph_name)
+    .replace('\n', " ")
+    .replace("\\n", " ")
+    .replace("\\l", " ")
+    .replace("\\r", " ")
+    .trim()
+    .to_string()
+}
+
+//the viewer lays a step out from its root and only shows what that reaches, so the graph
+//itself is node 0, pointing at every node nothing before it in the file reaches
+const GRAPH_ID: u32 = 0;
+
+struct Steps {
+  root: Root,
+  ids: HashMap<String, u32>,
+  previous: Option<Snapshot>,
+}
+
+impl Steps {
+  fn add(&mut self, graph: &DotGraph, note: String) {
+    let graph_name = graph.name.clone().unwrap_or_else(|| String::from(if graph.directed { "digraph" } else { "graph" }));
+    let mut snapshot = Snapshot { root: Some(GRAPH_ID), ..Snapshot::default() };
+    snapshot.labels.insert(GRAPH_ID, graph_name.clone());
+    if self.root.nodes.is_empty() {
+      self.root.register_node(GRAPH_ID, graph_name.clone(), Style { shape: Some(ShapeHint::Rectangle), ..Style::default() });
+    }
+    let ids: Vec<u32> = graph.nodes.iter()
+      .map(|node| {
+        //a node without a label shows its name
+        let label = shown_label(node.label.as_deref().unwrap_or("\\N"), &node.name, &graph_name);
+        let next_id = self.ids.len() as u32 + 1;
+        let id = *self.ids.entry(node.name.clone()).or_insert(next_id);
+        if id == next_id {
+          self.root.register_node(id, label.clone(), Style::default());
+        }
+        snapshot.labels.insert(id, label);
+        id
+      })
+      .collect();
+    //two edges between the same nodes are one, the first one's label stays
+    let mut nexts: HashMap<u32, Vec<u32>> = HashMap::new();
+    for (from, to, label) in &graph.edges {
+      let ends = (ids[*from], ids[*to]);
+      if let btree_map::Entry::Vacant(entry) = snapshot.edges.entry(ends) {
+        entry.insert(label.as_deref().map(|label| shown_label(label, "", &graph_name)).unwrap_or_default());
+        nexts.entry(ends.0).or_default().push(ends.1);
+      }
+    }
+    let mut reached = HashSet::new();
+    for id in &ids {
+      if !reached.insert(*id) {
+        continue;
+      }
+      snapshot.edges.insert((GRAPH_ID, *id), String::new());
+      let mut queue = VecDeque::from([*id]);
+      while let Some(from) = queue.pop_front() {
+        for to in nexts.get(&from).into_iter().flatten() {
+          if reached.insert(*to) {
+            queue.push_back(*to);
+          }
+        }
+      }
+    }
+
+    match self.previous.as_ref() {
+      None => {
+        let mut base = BaseInfo { root_id: GRAPH_ID, note, ..BaseInfo::default() };
+        for id in snapshot.labels.keys() {
+          base.edges.insert(*id, HashMap::new());
+        }
+        for ((from_id, to_id), weight) in &snapshot.edges {
+          base.edges.get_mut(from_id).unwrap().insert(*to_id, weight.clone());
+        }
+        self.root.bases[0] = base;
+      }
+      Some(previous) => {
+        let diff_info = diff_info(diff(previous, &snapshot), note);
+        self.root.push_diff(0, diff_info);
+      }
+    }
+    self.previous = Some(snapshot);
+  }
+}
+
+fn diff_info(diff: Diff, note: String) -> DiffInfo {
+  let nodes = |nodes: Vec<(u32, String)>| nodes.into_iter().map(|(id, label)| Node { id, label }).collect();
+  let edges = |edges: Vec<((u32, u32), String)>| {
+    edges.into_iter().map(|((from_id, to_id), weight)| Edge { from_id, to_id, weight }).collect()
+  };
+  DiffInfo {
+    added_nodes: nodes(diff.added_nodes),
+    removed_nodes: nodes(diff.removed_nodes),
+    added_edges: edges(diff.added_edges),
+    removed_edges: edges(diff.removed_edges),
+    labels_changed: diff.labels_changed,
+    weights_changed: diff.weights_changed,
+    note,
+    root_id: GRAPH_ID,
+    ..DiffInfo::default()
+  }
+}
+
+//every graph of every file in order is a step, noted with the graph's label or where it came from
+pub fn read_dot(paths: &[&str]) -> Result<Root, Box<dyn Error>> {
+  let mut steps = Steps { root: Root::new(1), ids: HashMap::new(), previous: None };
+  for path in paths {
+    let graphs = parse_dot(&std::fs::read_to_string(path)?).map_err(|e| format!("{}: {}", path, e))?;
+    let count = graphs.len();
+    for (index, graph) in graphs.iter().enumerate() {
+      let note = match (&graph.label, count) {
+        (Some(label), _) => shown_label(label, "", graph.name.as_deref().unwrap_or("")),
+        (None, 1) => path.to_string(),
+        (None, _) => format!("{} graph {}", path, index + 1),
+      };
+      steps.add(graph, note);
+    }
+  }
+  Ok(steps.root)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use rand::rngs::StdRng;
+  use rand::{Rng, SeedableRng};
+  use std::collections::{BTreeMap, BTreeSet};
+
+  const CHECK_SEED: u64 = 0xd07;
+  const NAMES: [&str; 6] = ["a", "_b2", "42", "-1.5", "node three", "say \"hi\""];
+
+  //a name as any of the ways dot can write it
+  fn write_id(rng: &mut StdRng, id: &str) -> String {
+    let plain = id.chars().all(|c| c.is_alphanumeric() || c == '_') && !id.starts_with(|c: char| c.is_ascii_digit())
+      && !KEYWORDS.iter().any(|keyword| keyword.eq_ignore_ascii_case(id));
+    let number = id.parse::<f64>().is_ok() && !id.contains(['e', 'E', '+']) && !id.ends_with('.');
+    let quoted = id.replace('"', "\\\"");
+    match rng.random_range(0..4) {
+      0 if plain || number => id.to_string(),
+      1 if quoted.len() > 1 && !quoted.ends_with('\\') => {
+        let split = (1..quoted.len()).find(|at| quoted.is_char_boundary(*at) && !quoted[..*at].ends_with('\\')).unwrap_or(1);
+        format!("\"{}\" + \"{}\"", &quoted[..split], &quoted[split..])
+      }
+      _ => format!("\"{}\"", quoted),
+    }
+  }
+
+  fn separator(rng: &mut StdRng) -> &'static str {
+    ["\n", ";\n", " ; ", "\n// a comment\n", " /* another\n one */ ", "\n\n"][rng.random_range(0..6)]
+  }
+
+  //a graph, the text that writes it and what a step of it should show
+  struct Case {
+    text: String,
+    labels: BTreeSet<String>,
+    edges: BTreeSet<(String, String, String)>,
+  }
+
+  fn random_case(rng: &mut StdRng, directed: bool) -> Case {
+    let count = rng.random_range(0..12);
+    let names: Vec<String> = (0..count).map(|index| format!("{}{}", NAMES[rng.random_range(0..NAMES.len())], index)).collect();
+    let labels: Vec<Option<String>> = names.iter()
+      .map(|name| rng.random_bool(0.5).then(|| format!("label of {}", name)))
+      .collect();
+    let shown = |index: usize| labels[index].clone().unwrap_or_else(|| names[index].clone());
+    let op = if directed { " -> " } else { " -- " };
+
+    let mut text = String::new();
+    if rng.random_bool(0.2) {
+      text.push_str("# 1 \"preprocessed\"\n");
+    }
+    if rng.random_bool(0.3) {
+      text.push_str("strict ");
+    }
+    text.push_str(if directed { "digraph" } else { "graph" });
+    if rng.random_bool(0.5) {
+      text.push_str(" G");
+    }
+    text.push_str(" {");
+    text.push_str(separator(rng));
+    text.push_str("graph [rankdir=LR]");
+    text.push_str(separator(rng));
+    //a name that never shows up is no node, only the first edge between two nodes is kept
+    let mut appears = vec![false; count];
+    let mut edges: BTreeMap<(usize, usize), String> = BTreeMap::new();
+    for index in 0..count {
+      if labels[index].is_some() || rng.random_bool(0.5) {
+        text.push_str(&write_id(rng, &names[index]));
+        if rng.random_bool(0.3) {
+          text.push_str(":port");
+        }
+        if let Some(label) = &labels[index] {
+          text.push_str(&format!(" [shape=box, label={}]", write_id(rng, label)));
+        }
+        text.push_str(separator(rng));
+        appears[index] = true;
+      }
+      //a chain, sometimes through a subgraph
+      if count > 1 && rng.random_bool(0.6) {
+        let label = rng.random_bool(0.5).then(|| rng.random_range(0..100).to_string());
+        let mut groups: Vec<Vec<usize>> = Vec::new();
+        let mut parts = Vec::new();
+        for _ in 0..rng.random_range(2..4) {
+          let group: Vec<usize> = (0..if rng.random_bool(0.2) { 2 } else { 1 }).map(|_| rng.random_range(0..count)).collect();
+          let written: Vec<String> = group.iter().map(|node| write_id(rng, &names[*node])).collect();
+          parts.push(if group.len() > 1 { format!("{{{}}}", written.join(" ")) } else { written.join(" ") });
+          groups.push(group);
+        }
+        text.push_str(&parts.join(op));
+        if let Some(label) = &label {
+          text.push_str(&format!(" [label=\"{}\", color=red]", label));
+        }
+        text.push_str(separator(rng));
+        for pair in groups.windows(2) {
+          for from in &pair[0] {
+            for to in &pair[1] {
+              edges.entry((*from, *to)).or_insert_with(|| label.clone().unwrap_or_default());
+            }
+          }
+        }
+        for node in groups.iter().flatten() {
+          appears[*node] = true;
+        }
+      }
+    }
+    text.push('}');
+
+    Case {
+      text,
+      labels: (0..count).filter(|index| appears[*index]).map(shown).collect(),
+      edges: edges.iter().map(|((from, to), label)| (shown(*from), label.clone(), shown(*to))).collect(),
+    }
+  }
+
+  //what a step shows, edges as (from label, weight, to label)
+  #[derive(Debug, PartialEq)]
+  struct Shown {
+    labels: BTreeSet<String>,
+    edges: BTreeSet<(String, String, String)>,
+    reaches_every_node: bool,
+  }
+
+  fn shown(labels: &[&str], edges: &[(&str, &str, &str)]) -> Shown {
+    Shown {
+      labels: labels.iter().map(|label| label.to_string()).collect(),
+      edges: edges.iter().map(|(from, weight, to)| (from.to_string(), weight.to_string(), to.to_string())).collect(),
+      reaches_every_node: true,
+    }
+  }
+
+  //replays the diffs like the viewer does, leaving out the graph's own node
+  fn shown_steps(root: &Root) -> Vec<Shown> {
+    let mut labels: HashMap<u32, String> = HashMap::new();
+    let mut edges: HashMap<(u32, u32), String> = HashMap::new();
+    for (from_id, connections) in &root.bases[0].edges {
+      labels.insert(*from_id, root.nodes[*from_id as usize].clone());
+      for (to_id, weight) in connections {
+        edges.insert((*from_id, *to_id), weight.clone());
+      }
+    }
+    let mut steps = Vec::new();
+    for step in 0..=root.diffs[0].len() {
+      if step > 0 {
+        let diff = &root.diffs[0][step - 1];
+        for edge in &diff.removed_edges {
+          edges.remove(&(edge.from_id, edge.to_id));
+        }
+        for node in &diff.removed_nodes {
+          labels.remove(&node.id);
+        }
+        for node in &diff.added_nodes {
+          labels.insert(node.id, node.label.clone());
+        }
+        for edge in &diff.added_edges {
+          edges.insert((edge.from_id, edge.to_id), edge.weight.clone());
+        }
+        for change in &diff.labels_changed {
+          labels.insert(change.id, change.new.clone());
+        }
+        for change in &diff.weights_changed {
+          edges.insert((change.from_id, change.to_id), change.new.clone());
+        }
+      }
+      let mut reached = HashSet::from([GRAPH_ID]);
+      let mut queue = VecDeque::from([GRAPH_ID]);
+      while let Some(from) = queue.pop_front() {
+        for (_, to_id) in edges.keys().filter(|(from_id, _)| *from_id == from) {
+          if reached.insert(*to_id) {
+            queue.push_back(*to_id);
+          }
+        }
+      }
+      steps.push(Shown {
+        labels: labels.iter().filter(|(id, _)| **id != GRAPH_ID).map(|(_, label)| label.clone()).collect(),
+        edges: edges.iter()
+          .filter(|((from_id, _), _)| *from_id != GRAPH_ID)
+          .map(|((from_id, to_id), weight)| (labels[from_id].clone(), weight.clone(), labels[to_id].clone()))
+          .collect(),
+        reaches_every_node: reached.len() == labels.len(),
+      });
+    }
+    steps
+  }
+
+  fn in_repository(file: &str) -> String {
+    format!("{}/traces/dot/{}", env!("CARGO_MANIFEST_DIR"), file)
+  }
+
+  #[test]
+  fn names_are_read_every_way_dot_writes_them() {
+    let root = read_dot(&[&in_repository("ids.gv")]).unwrap();
+    assert_eq!(shown_steps(&root), vec![shown(
+      &["a", "_b2", "42", "-1.5", "third", "say \"hi\"", "concat", "n of ids"],
+      &[("a", "7", "_b2"), ("a", "7", "42"), ("_b2", "7", "-1.5"), ("42", "7", "-1.5"), ("say \"hi\"", "joined", "concat")],
+    )]);
+  }
+
+  #[test]
+  fn undirected_edges_point_the_way_they_are_written() {
+    let root = read_dot(&[&in_repository("undirected.gv")]).unwrap();
+    assert_eq!(shown_steps(&root), vec![shown(
+      &["a", "b", "c", "d second line", "e"],
+      &[("a", "w", "b"), ("b", "w", "c"), ("c", "", "d second line")],
+    )]);
+  }
+
+  #[test]
+  fn files_and_the_graphs_in_them_are_steps() {
+    let paths = ["steps_1.gv", "steps_2.gv", "steps_3.gv"].map(in_repository);
+    let root = read_dot(&paths.each_ref().map(String::as_str)).unwrap();
+    assert_eq!(shown_steps(&root), vec![
+      shown(&[], &[]),
+      shown(&["head", "1", "2"], &[("head", "next", "1"), ("1", "next", "2")]),
+      shown(&["head", "1", "2", "3"], &[("head", "next", "1"), ("1", "after", "2"), ("2", "next", "3")]),
+      shown(&["head", "2", "3"], &[("head", "next", "2"), ("2", "next", "3")]),
+    ]);
+    let notes: Vec<&str> = root.diffs[0].iter().map(|diff| diff.note.as_str()).collect();
+    assert_eq!(root.bases[0].note, "empty list");
+    assert_eq!(notes, vec!["two nodes".to_string(), format!("{} graph 1", paths[2]), "first removed".to_string()]);
+  }
+
+  //random graphs written every way dot allows, as sequences of files
+  #[test]
+  fn random_graphs_show_what_was_written() {
+    let mut rng = StdRng::seed_from_u64(CHECK_SEED);
+    let directory = std::env::temp_dir();
+    for case in 0..200 {
+      let directed = rng.random_bool(0.5);
+      let steps: Vec<Case> = (0..rng.random_range(1..6)).map(|_| random_case(&mut rng, directed)).collect();
+      let paths: Vec<String> = (0..steps.len())
+        .map(|step| directory.join(format!("parse_listen_dot_test_{}.gv", step)).to_string_lossy().to_string())
+        .collect();
+      for (step, path) in steps.iter().zip(&paths) {
+        std::fs::write(path, &step.text).unwrap();
+      }
+      let path_refs: Vec<&str> = paths.iter().map(String::as_str).collect();
+      let read = read_dot(&path_refs);
+      for path in &paths {
+        let _ = std::fs::remove_file(path);
+      }
+      let texts = steps.iter().map(|step| step.text.as_str()).collect::<Vec<_>>().join("\n");
+      let root = read.unwrap_or_else(|e| panic!("case {} does not read: {}\n{}", case, e, texts));
+      for (step, (actual, expected)) in shown_steps(&root).iter().zip(&steps).enumerate() {
+        assert_eq!(actual.labels, expected.labels, "case {} step {}\n{}", case, step, expected.text);
+        assert_eq!(actual.edges, expected.edges, "case {} step {}\n{}", case, step, expected.text);
+        assert!(actual.reaches_every_node, "case {} step {} leaves nodes out\n{}", case, step, expected.text);
+      }
+    }
+  }
+
+  #[test]
+  fn broken_graphs_are_turned_down() {
+    for text in ["digraph { a -- b }", "graph { a -> b }", "digraph { a -> }", "digraph { a [label] }", "digraph { \"a }", "digraph { a", "", "node { a }"] {
+      assert!(parse_dot(text).is_err(), "{:?} parses", text);
+    }
+  }
+}
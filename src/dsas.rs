@@ -1,10 +1,7 @@
 use glfw::init;
 
 use crate::json_deserialize;
-use crate::{
-    c_side::CircleSquare,
-    tree::{CS, Line, LineState, Node, NodeColor, Point},
-};
+use crate::tree::{CS, Ghost, Line, LineShape, LineState, Node, NodeColor, Point};
 use std::f32::consts::PI;
 use std::{
     cmp,
@@ -67,26 +64,33 @@ macro_rules! create_visual_node_at_position {
     }};
 }
 
-macro_rules! create_edge_between_nodes {
-  ($graph:expr, $from_id:expr, $to_id:expr, $from_pos:expr, $to_pos:expr, $weight_str:expr) => {
-    {
-      let angle = angle_between_points!($from_pos, $to_pos);
-      // println!("---- EDGE ---- from={} to={} angle={:.6} from_pos=({:.3},{:.3}) to_pos=({:.3},{:.3}) weight=\"{}\"",
-        // $from_id, $to_id, angle, $from_pos.0, $from_pos.1, $to_pos.0, $to_pos.1, $weight_str);
-      if let Some(c_edge) = $graph.edges.get_mut(&($from_id, $to_id)) {
-        *c_edge = Line::new(LineState::StartToEnd(0),
-        Point::new($from_pos.0 + DIAMETER as f32 * angle.cos(), $from_pos.1 - DIAMETER as f32 * angle.sin()),
-        Point::new($to_pos.0 - DIAMETER as f32 * angle.cos(), $to_pos.1 + DIAMETER as f32 * angle.sin()),
-        $weight_str);
-      }        // println!("    -> edge endpoints set: from=({:.3},{:.3}) to=({:.3},{:.3})",
-          // $from_pos.0 + DIAMETER as f32 * angle.cos(), $from_pos.1 - DIAMETER as f32 * angle.sin(),
-          // $to_pos.0 - DIAMETER as f32 * angle.cos(), $to_pos.1 + DIAMETER as f32 * angle.sin());
-    }
-  };
-}
 pub const DIAMETER: i32 = 30;
 pub const SPACE_NODES: i32 = 30;
 pub const DIAGONAL: f32 = (DIAMETER as f32 * 1.414); //sqrt(2) approximately
+const BEND_ANGLE: f32 = 0.35; //how far around the node a bent edge leaves, towards its bow
+
+//where an edge between two nodes starts and ends for its shape
+pub fn edge_endpoints(from: &NodeWrapper, to: &NodeWrapper, shape: LineShape) -> (Point, Point) {
+    let angle = angle_between_points!(from.center, to.center);
+    let on_border = |node: &NodeWrapper, angle: f32| {
+        let distance = node.border_distance(angle);
+        Point::new(
+            node.center.0 + distance * angle.cos(),
+            node.center.1 - distance * angle.sin(),
+        )
+    };
+    match shape {
+        LineShape::Straight => (on_border(from, angle), on_border(to, angle + PI)),
+        LineShape::Bent => (
+            on_border(from, angle - BEND_ANGLE),
+            on_border(to, angle + PI + BEND_ANGLE),
+        ),
+        LineShape::Loop(_) => (
+            Point::new(from.center.0, from.center.1),
+            Point::new(from.center.0, from.center.1),
+        ),
+    }
+}
 
 #[derive(Debug)]
 pub struct MLine {
@@ -137,12 +141,17 @@ impl MLine {
     }
 }
 #[derive(Debug)]
-pub struct Graph<'a> {
-    node_labels: &'a Vec<String>,
-    weight_strings: &'a HashMap<u32, HashMap<u32, String>>,
+pub struct Graph {
+    node_labels: Vec<String>, //copies, the trace can keep growing while the graph is shown
+    weight_strings: HashMap<u32, HashMap<u32, String>>,
     pub nodes: HashMap<usize, NodeWrapper>,
     pub edges: HashMap<(usize, usize), Line>, //index of first and then second node
     pub root: usize,
+    pub diff_step: usize, //how many diffs are applied on top of the base
+    highlighted_nodes: Vec<usize>, //what the last step touched
+    highlighted_edges: Vec<(usize, usize)>,
+    ghosts: Vec<Ghost>,
+    pub show_ghosts: bool,
     note: String,
     initial_node_position: (i32, i32),
 }
@@ -175,68 +184,149 @@ impl NodeWrapper {
             velocity: (0.0, 0.0),
         }
     }
+
+    //what the node is drawn as, a node without a visual one takes the default circle
+    pub fn outline(&self) -> CS {
+        match self.visual_node.as_ref().map(Node::outline) {
+            Some(CS::Removed) | None => CS::Circle(DIAMETER as f32),
+            Some(outline) => outline,
+        }
+    }
+
+    //from the center to the outline, towards an angle
+    pub fn border_distance(&self, angle: f32) -> f32 {
+        self.outline().border_distance(angle)
+    }
 }
 
-impl<'a> Graph<'a> {
+impl Graph {
     pub fn new(
-        node_labels: &'a Vec<String>,
-        weight_strings: &'a HashMap<u32, HashMap<u32, String>>,
+        node_labels: &[String],
+        weight_strings: &HashMap<u32, HashMap<u32, String>>,
         initial_node_position: (i32, i32),
         root: usize,
     ) -> Self {
         Graph {
-            node_labels,
-            weight_strings,
+            node_labels: node_labels.to_vec(),
+            weight_strings: weight_strings.clone(),
             nodes: HashMap::new(),
             edges: HashMap::new(),
             root,
+            diff_step: 0,
+            highlighted_nodes: Vec::new(),
+            highlighted_edges: Vec::new(),
+            ghosts: Vec::new(),
+            show_ghosts: true,
             note: String::from(""),
             initial_node_position,
         }
     }
 
+    pub fn label(&self, id: usize) -> &str {
+        self.node_labels.get(id).map_or("", String::as_str)
+    }
+
+    //what a node is drawn with from now on, a relayout included
+    pub fn set_label(&mut self, id: usize, label: &str) {
+        if let Some(shown) = self.node_labels.get_mut(id) {
+            shown.clear();
+            shown.push_str(label);
+        }
+    }
+
+    //what an edge is drawn with from now on, a relayout included
+    pub fn set_weight(&mut self, from_id: usize, to_id: usize, weight: &str) {
+        self.weight_strings
+            .entry(from_id as u32)
+            .or_default()
+            .insert(to_id as u32, weight.to_string());
+    }
+
     pub fn add_edge(&mut self, from_id: usize, to_id: usize, weight_str: &str) {
+        let shape = self.shape_for(from_id, to_id);
         let first_node = self.nodes.get(&from_id).unwrap();
         let second_node = self.nodes.get(&to_id).unwrap();
-        let angle = angle_between_points!(first_node.center, second_node.center);
+        let (start, end) = edge_endpoints(first_node, second_node, shape);
         self.edges.insert(
             (from_id, to_id),
-            Line::new(
-                LineState::StartToEnd(0),
-                Point::new(
-                    first_node.center.0 + DIAMETER as f32 * angle.cos(),
-                    first_node.center.1 - DIAMETER as f32 * angle.sin(),
-                ),
-                Point::new(
-                    second_node.center.0 - DIAMETER as f32 * angle.cos(),
-                    second_node.center.1 + DIAMETER as f32 * angle.sin(),
-                ),
-                weight_str,
-            ),
+            Line::with_shape(LineState::StartToEnd(0), shape, start, end, weight_str),
         );
     }
     pub fn update_existing_edge(&mut self, from_id: usize, to_id: usize) {
         let first_node = self.nodes.get(&from_id).unwrap();
         let second_node = self.nodes.get(&to_id).unwrap();
-        let angle = angle_between_points!(first_node.center, second_node.center);
         let edge = self.edges.get_mut(&(from_id, to_id)).unwrap();
-        edge.override_line(
-            Point::new(
-                first_node.center.0 + DIAMETER as f32 * angle.cos(),
-                first_node.center.1 - DIAMETER as f32 * angle.sin(),
-            ),
-            Point::new(
-                second_node.center.0 - DIAMETER as f32 * angle.cos(),
-                second_node.center.1 + DIAMETER as f32 * angle.sin(),
-            ),
-        );
+        let (start, end) = edge_endpoints(first_node, second_node, edge.shape());
+        edge.override_line(start, end);
+    }
+
+    //antiparallel edges both bend so they do not overlap, the one already drawn is bent here
+    fn shape_for(&mut self, from_id: usize, to_id: usize) -> LineShape {
+        if from_id == to_id {
+            return LineShape::Loop(self.nodes.get(&from_id).unwrap().border_distance(PI / 2.0));
+        }
+        match self.edges.get_mut(&(to_id, from_id)) {
+            Some(reverse) if reverse.is_visual() => {
+                let (start, end) = edge_endpoints(
+                    self.nodes.get(&to_id).unwrap(),
+                    self.nodes.get(&from_id).unwrap(),
+                    LineShape::Bent,
+                );
+                reverse.set_shape(LineShape::Bent, start, end);
+                LineShape::Bent
+            }
+            _ => LineShape::Straight,
+        }
+    }
+
+    //a node whose neighbors were all placed before it still has edges to them, self-loops and back edges
+    fn draw_edges_of_placed(
+        &mut self,
+        id: usize,
+        weight_strings: &HashMap<u32, HashMap<u32, String>>,
+    ) {
+        for neighbor_id in self.nodes.get(&id).unwrap().neighbors.clone() {
+            let weight = weight_strings
+                .get(&(id as u32))
+                .and_then(|m| m.get(&(neighbor_id as u32)))
+                .map_or("", |weight| weight.as_str());
+            if id == neighbor_id {
+                self.draw_self_loop(id, weight);
+                continue;
+            }
+            match self.edges.get(&(id, neighbor_id)) {
+                Some(edge) if edge.is_visual() => {
+                    self.update_existing_edge(id, neighbor_id);
+                    self.edges
+                        .get_mut(&(id, neighbor_id))
+                        .unwrap()
+                        .weight_line(weight);
+                }
+                _ => self.add_edge(id, neighbor_id, weight),
+            }
+        }
+    }
+
+    //self-loops are drawn as edges on top of their node
+    fn draw_self_loop(&mut self, id: usize, weight_str: &str) {
+        match self.edges.get(&(id, id)) {
+            Some(edge) if edge.is_visual() => {
+                self.update_existing_edge(id, id);
+                self.edges.get_mut(&(id, id)).unwrap().weight_line(weight_str);
+            }
+            _ => self.add_edge(id, id, weight_str),
+        }
     }
 
     pub fn node_exists(&self, id: usize) -> bool {
         self.nodes.contains_key(&id)
     }
+
+    pub fn is_built(&self) -> bool {
+        !self.nodes.is_empty()
+    }
     //bigbang algorithm used here
-    pub fn build_base(&mut self, base_info: &'a json_deserialize::BaseInfo) -> &Self {
+    pub fn build_base(&mut self, base_info: &json_deserialize::BaseInfo) -> &Self {
         let mut queue: VecDeque<usize> = VecDeque::new();
         let mut visited: HashSet<usize> = HashSet::new();
 
@@ -265,6 +355,9 @@ impl<'a> Graph<'a> {
                 velocity: (0.0, 0.0),
             };
 
+            // sorted so the same trace always lays out the same way
+            let mut neighbors: Vec<(&u32, &String)> = neighbors.iter().collect();
+            neighbors.sort_by_key(|(neighbor_id, _)| **neighbor_id);
             for (neighbor_id, weight_str) in neighbors {
                 c_neighbors.push((*neighbor_id) as usize);
                 if !visited.contains(&(*neighbor_id as usize)) {
@@ -287,10 +380,10 @@ impl<'a> Graph<'a> {
             current_node.neighbors = c_neighbors;
             new_nodes.insert(c_id, current_node);
 
-            if c_id == (layer_ending_id) {
-                if let Some(last_id) = queue.back() {
-                    layer_ending_id = *last_id;
-                }
+            if c_id == layer_ending_id
+                && let Some(last_id) = queue.back()
+            {
+                layer_ending_id = *last_id;
             }
         }
         self.nodes = new_nodes;
@@ -298,9 +391,9 @@ impl<'a> Graph<'a> {
         self
     }
     pub fn step_build(&mut self) -> &Self {
-        let self_nodes: *mut HashMap<usize, NodeWrapper> = &mut self.nodes;
-
-        let root_node = unsafe { (*self_nodes).get_mut(&self.root).unwrap() };
+        //moved out while the edges below are drawn through &mut self, put back at the end
+        let weight_strings = std::mem::take(&mut self.weight_strings);
+        let root_node = self.nodes.get_mut(&self.root).unwrap();
         root_node.center = (
             self.initial_node_position.0 as f32,
             self.initial_node_position.1 as f32,
@@ -314,7 +407,7 @@ impl<'a> Graph<'a> {
             .visual_node
             .as_mut()
             .unwrap()
-            .color_node(NodeColor::Purple);
+            .color_node(NodeColor::Root);
         let global_center = root_node.center;
 
         // Setup BFS queue and visited set
@@ -322,7 +415,7 @@ impl<'a> Graph<'a> {
         let mut visited: HashSet<usize> = HashSet::new();
         visited.insert(self.root);
 
-        self.setup_neighbors_checked(&mut queue, &mut visited);
+        self.setup_neighbors_checked(&mut queue, &mut visited, &weight_strings);
         self.update_new_neighbors();
 
         println!("\n\n");
@@ -339,41 +432,32 @@ impl<'a> Graph<'a> {
 
         let mut position: (f32, f32);
         while let Some((current_id, c_arch_radians)) = queue.pop_front() {
-            let current_node = unsafe { (*self_nodes).get_mut(&current_id).unwrap() };
+            let current_node = self.nodes.get(&current_id).unwrap();
             if current_node.new_neighbors == 0 {
+                self.draw_edges_of_placed(current_id, &weight_strings);
                 continue;
             }
             let (neighbor_angles, initial_position) =
                 self.calculate_neighbor_positioning(current_node, c_arch_radians, global_center);
-            let current_node = unsafe { (*self_nodes).get_mut(&current_id).unwrap() };
+            let current_node = self.nodes.get_mut(&current_id).unwrap();
             current_node
                 .visual_node
                 .as_mut()
                 .unwrap()
                 .color_node(NodeColor::Default);
             position = initial_position;
-            for &neighbor_id in &current_node.neighbors.clone() {
+            for neighbor_id in current_node.neighbors.clone() {
+                let weight = weight_strings
+                    .get(&(current_id as u32))
+                    .and_then(|m| m.get(&(neighbor_id as u32)));
                 if current_id == neighbor_id {
-                    current_node
-                        .visual_node
-                        .as_mut()
-                        .unwrap()
-                        .color_node(NodeColor::Blue);
-                    current_node.visual_node.as_mut().unwrap().weight_node(
-                        self.weight_strings
-                            .get(&(current_id as u32))
-                            .unwrap()
-                            .get(&(neighbor_id as u32))
-                            .unwrap()
-                            .as_str(),
-                    );
+                    self.draw_self_loop(current_id, weight.unwrap().as_str());
                     continue;
                 }
 
-                let c_neighbor_node = unsafe { (*self_nodes).get_mut(&neighbor_id).unwrap() };
-                if !visited.contains(&neighbor_id) {
-                    visited.insert(neighbor_id);
+                if visited.insert(neighbor_id) {
                     queue.push_back((neighbor_id, neighbor_angles));
+                    let c_neighbor_node = self.nodes.get_mut(&neighbor_id).unwrap();
                     c_neighbor_node.center = position;
                     c_neighbor_node
                         .visual_node
@@ -383,62 +467,47 @@ impl<'a> Graph<'a> {
                     position = rotate_around!(global_center, position, -neighbor_angles);
                 }
 
-                let weight = self
-                    .weight_strings
-                    .get(&(current_id as u32))
-                    .and_then(|m| m.get(&(neighbor_id as u32)));
+                //no node is borrowed any more, the edge is drawn through &mut self
                 if !self.edges.contains_key(&(current_id, neighbor_id)) {
-                    // Print properties of the edge to be created for debugging.
-                    let weight_debug = format!("{:?}", weight);
-
-                    self.add_edge(
-                        current_id,
-                        neighbor_id,
-                        if !weight.is_none() {
-                            weight.unwrap().as_str()
-                        } else {
-                            ""
-                        },
-                    );
+                    self.add_edge(current_id, neighbor_id, weight.map_or("", String::as_str));
                 } else {
                     self.update_existing_edge(current_id, neighbor_id);
-                    if !weight.is_none() {
+                    if let Some(weight) = weight {
                         self.edges
                             .get_mut(&(current_id, neighbor_id))
-                            .as_mut()
                             .unwrap()
-                            .weight_line(weight.unwrap().as_str()); //this might be a problem
+                            .weight_line(weight.as_str()); //this might be a problem
                     }
                 }
             }
         }
+        self.weight_strings = weight_strings;
         self
     }
     pub fn update_new_neighbors(&mut self) {
-        let self_nodes: *mut HashMap<usize, NodeWrapper> = &mut self.nodes;
-
         // Initialize root node and get global parameters
-        let root_node = unsafe { (*self_nodes).get_mut(&self.root).unwrap() };
+        let root_node = self.nodes.get_mut(&self.root).unwrap();
         let mut queue: VecDeque<usize> = VecDeque::new();
         let mut visited: HashSet<usize> = HashSet::new();
         visited.insert(self.root);
 
         root_node.new_neighbors = root_node.neighbors.len() as u32;
         for neighbor in &root_node.neighbors {
-            queue.push_back(*neighbor);
+            if visited.insert(*neighbor) {
+                queue.push_back(*neighbor);
+            }
         }
 
-        let mut position: (f32, f32);
         while let Some(current_id) = queue.pop_front() {
-            let current_node = unsafe { (*self_nodes).get_mut(&current_id).unwrap() };
+            let current_node = self.nodes.get_mut(&current_id).unwrap();
             current_node.new_neighbors = 0;
 
-            for &neighbor_id in &current_node.neighbors.clone() {
+            for &neighbor_id in &current_node.neighbors {
                 if current_id == neighbor_id {
                     continue;
                 }
-                let c_neighbor_node = unsafe { (*self_nodes).get_mut(&neighbor_id).unwrap() };
-                if !visited.contains(&neighbor_id) {
+                //cycles, like a pair of antiparallel edges, would queue forever otherwise
+                if visited.insert(neighbor_id) {
                     current_node.new_neighbors += 1;
                     queue.push_back(neighbor_id);
                 }
@@ -446,10 +515,8 @@ impl<'a> Graph<'a> {
         }
     }
     pub fn bigbang_base(&mut self, weight_strings: &HashMap<u32, HashMap<u32, String>>) -> &Self {
-        let self_nodes: *mut HashMap<usize, NodeWrapper> = &mut self.nodes;
-
         // Initialize root node and get global parameters
-        let root_node = unsafe { (*self_nodes).get_mut(&self.root).unwrap() };
+        let root_node = self.nodes.get_mut(&self.root).unwrap();
         root_node.center = (
             self.initial_node_position.0 as f32,
             self.initial_node_position.1 as f32,
@@ -469,65 +536,42 @@ impl<'a> Graph<'a> {
         visited.insert(self.root);
 
         // Setup root's direct neighbors
-        self.setup_root_neighbors(
-            self_nodes,
-            global_center,
-            &mut queue,
-            weight_strings,
-            &mut visited,
-        );
+        self.setup_root_neighbors(global_center, &mut queue, weight_strings, &mut visited);
 
         let mut position: (f32, f32);
         while let Some((current_id, c_arch_radians)) = queue.pop_front() {
-            let current_node = unsafe { (*self_nodes).get_mut(&current_id).unwrap() };
+            let current_node = self.nodes.get(&current_id).unwrap();
 
             if current_node.new_neighbors == 0 {
+                self.draw_edges_of_placed(current_id, weight_strings);
                 continue;
             }
 
             let (neighbor_angles, initial_position) =
                 self.calculate_neighbor_positioning(current_node, c_arch_radians, global_center);
 
-            let current_node = unsafe { (*self_nodes).get_mut(&current_id).unwrap() };
             position = initial_position;
 
-            for &neighbor_id in &current_node.neighbors.clone() {
+            for neighbor_id in current_node.neighbors.clone() {
+                let weight = weight_strings
+                    .get(&(current_id as u32))
+                    .and_then(|m| m.get(&(neighbor_id as u32)));
                 if current_id == neighbor_id {
-                    current_node
-                        .visual_node
-                        .as_mut()
-                        .unwrap()
-                        .color_node(NodeColor::Blue);
-                    current_node.visual_node.as_mut().unwrap().weight_node(
-                        weight_strings
-                            .get(&(current_id as u32))
-                            .unwrap()
-                            .get(&(neighbor_id as u32))
-                            .unwrap()
-                            .as_str(),
-                    );
+                    self.draw_self_loop(current_id, weight.unwrap().as_str());
                     continue;
                 }
 
-                let c_neighbor_node = unsafe { (*self_nodes).get_mut(&neighbor_id).unwrap() };
-                if !visited.contains(&neighbor_id) {
-                    visited.insert(neighbor_id);
+                if visited.insert(neighbor_id) {
                     queue.push_back((neighbor_id, neighbor_angles));
+                    let c_neighbor_node = self.nodes.get_mut(&neighbor_id).unwrap();
                     create_visual_node_at_position!(self, c_neighbor_node, neighbor_id, position);
                     position = rotate_around!(global_center, position, -neighbor_angles);
                 }
 
-                let weight = weight_strings
-                    .get(&(current_id as u32))
-                    .and_then(|m| m.get(&(neighbor_id as u32)));
-                create_edge_between_nodes!(
-                    self,
-                    current_id,
-                    neighbor_id,
-                    current_node.center,
-                    c_neighbor_node.center,
-                    weight.unwrap().as_str()
-                );
+                //the edge build_base left without a visual is drawn
+                if self.edges.contains_key(&(current_id, neighbor_id)) {
+                    self.add_edge(current_id, neighbor_id, weight.unwrap().as_str());
+                }
             }
         }
 
@@ -537,9 +581,9 @@ impl<'a> Graph<'a> {
         &mut self,
         queue: &mut VecDeque<(usize, f32)>,
         visited: &mut HashSet<usize>,
+        weight_strings: &HashMap<u32, HashMap<u32, String>>,
     ) {
-        let self_nodes: *mut HashMap<usize, NodeWrapper> = &mut self.nodes;
-        let root_node = unsafe { (*self_nodes).get_mut(&self.root).unwrap() };
+        let root_node = self.nodes.get(&self.root).unwrap();
         let global_center: (f32, f32) = root_node.center;
 
         let mut c_rotation = 2.0 * PI / root_node.new_neighbors as f32;
@@ -551,47 +595,24 @@ impl<'a> Graph<'a> {
         let distance_from_root = distance_center!(2.0 * PI, 0.0);
 
         let mut position = (global_center.0 + distance_from_root, global_center.1);
-        let mut neighbor_id_ptr = root_node.neighbors.as_ptr();
-
-        // println!("[setup_root_neighbors] root_id={} neighbors_order={:?} c_rotation={:.6} distance_from_root={:.4} start_pos=({:.4},{:.4})",
-        //   self.root, root_node.neighbors, c_rotation, distance_from_root, position.0, position.1);
-
-        for _ in 0..root_node.neighbors.len() {
-            let neighbor_id = unsafe { *neighbor_id_ptr };
 
+        for neighbor_id in root_node.neighbors.clone() {
+            let w = weight_strings
+                .get(&(self.root as u32))
+                .and_then(|m| m.get(&(neighbor_id as u32)));
             if self.root == neighbor_id {
-                unsafe { neighbor_id_ptr = neighbor_id_ptr.add(1) };
-                root_node
-                    .visual_node
-                    .as_mut()
-                    .unwrap()
-                    .color_node(NodeColor::Blue);
-                root_node.visual_node.as_mut().unwrap().weight_node(
-                    self.weight_strings
-                        .get(&(self.root as u32))
-                        .unwrap()
-                        .get(&(neighbor_id as u32))
-                        .unwrap()
-                        .as_str(),
-                );
+                self.draw_self_loop(self.root, w.unwrap().as_str());
                 continue;
             }
 
-            let c_node = unsafe { (*self_nodes).get_mut(&neighbor_id).unwrap() };
+            queue.push_back((neighbor_id, c_rotation));
+            visited.insert(neighbor_id);
+            let c_node = self.nodes.get_mut(&neighbor_id).unwrap();
             c_node
                 .visual_node
                 .as_mut()
                 .unwrap()
                 .color_node(NodeColor::Default);
-
-            queue.push_back((neighbor_id, c_rotation));
-            // println!("[setup_root_neighbors] queued neighbor id={} arch_rad={:.6}", neighbor_id, c_rotation);
-            // Detailed initial placement trace for the first child
-            visited.insert(neighbor_id);
-            let w = self
-                .weight_strings
-                .get(&(self.root as u32))
-                .and_then(|m| m.get(&(neighbor_id as u32)));
             c_node.center = position;
             c_node
                 .visual_node
@@ -599,35 +620,22 @@ impl<'a> Graph<'a> {
                 .unwrap()
                 .move_node(position.0, position.1);
             if !self.edges.contains_key(&(self.root, neighbor_id)) {
-                // Print properties of the edge to be created for debugging.
-                let w_debug = format!("{:?}", w);
-
-                self.add_edge(
-                    self.root,
-                    neighbor_id,
-                    if !w.is_none() {
-                        w.unwrap().as_str()
-                    } else {
-                        ""
-                    },
-                );
+                self.add_edge(self.root, neighbor_id, w.map_or("", String::as_str));
             } else {
                 self.update_existing_edge(self.root, neighbor_id);
             }
 
             position = rotate_around!(global_center, position, c_rotation);
-            unsafe { neighbor_id_ptr = neighbor_id_ptr.add(1) };
         }
     }
     fn setup_root_neighbors(
         &mut self,
-        self_nodes: *mut HashMap<usize, NodeWrapper>,
         global_center: (f32, f32),
         queue: &mut VecDeque<(usize, f32)>,
         weight_strings: &HashMap<u32, HashMap<u32, String>>,
         visited: &mut HashSet<usize>,
     ) {
-        let root_node = unsafe { (*self_nodes).get_mut(&self.root).unwrap() };
+        let root_node = self.nodes.get(&self.root).unwrap();
 
         let mut c_rotation = 2.0 * PI / root_node.new_neighbors as f32;
         c_rotation = if c_rotation > PI {
@@ -638,63 +646,25 @@ impl<'a> Graph<'a> {
         let distance_from_root = distance_center!(2.0 * PI, 0.0);
 
         let mut position = (global_center.0 + distance_from_root, global_center.1);
-        let mut neighbor_id_ptr = root_node.neighbors.as_ptr();
-
-        // println!("[setup_root_neighbors] root_id={} neighbors_order={:?} c_rotation={:.6} distance_from_root={:.4} start_pos=({:.4},{:.4})",
-        //   self.root, root_node.neighbors, c_rotation, distance_from_root, position.0, position.1);
-
-        for i in 0..root_node.neighbors.len() {
-            let neighbor_id = unsafe { *neighbor_id_ptr };
 
+        for neighbor_id in root_node.neighbors.clone() {
+            let w = weight_strings
+                .get(&(self.root as u32))
+                .and_then(|m| m.get(&(neighbor_id as u32)));
             if self.root == neighbor_id {
-                unsafe { neighbor_id_ptr = neighbor_id_ptr.add(1) };
-                root_node
-                    .visual_node
-                    .as_mut()
-                    .unwrap()
-                    .color_node(NodeColor::Blue);
-                root_node.visual_node.as_mut().unwrap().weight_node(
-                    weight_strings
-                        .get(&(self.root as u32))
-                        .unwrap()
-                        .get(&(neighbor_id as u32))
-                        .unwrap()
-                        .as_str(),
-                );
+                self.draw_self_loop(self.root, w.unwrap().as_str());
                 continue;
             }
 
-            let c_node = unsafe { (*self_nodes).get_mut(&neighbor_id).unwrap() };
-
             queue.push_back((neighbor_id, c_rotation));
-            // println!("[setup_root_neighbors] queued neighbor id={} arch_rad={:.6}", neighbor_id, c_rotation);
-            // Detailed initial placement trace for the first child
-            if i == 0 {
-                let start_pos = (global_center.0 + distance_from_root, global_center.1);
-                // println!("\n*** ROOT-FIRST-CHILD TRACE (root_id={} -> neighbor_id={}) ***", self.root, neighbor_id);
-                // println!("  distance_from_root = {:.6}", distance_from_root);
-                // println!("  unrotated_start_pos = ({:.6},{:.6})", start_pos.0, start_pos.1);
-                // println!("  rotation_about_global (c_rotation) = {:.6} rad (~{:.1} deg)", c_rotation, c_rotation.to_degrees());
-                // show the position after the rotate_around call that will be used
-                let rotated = rotate_around!(global_center, start_pos, c_rotation);
-                // println!("  start_pos AFTER rotate_about_global = ({:.6},{:.6})\n", rotated.0, rotated.1);
-            }
             visited.insert(neighbor_id);
+            let c_node = self.nodes.get_mut(&neighbor_id).unwrap();
             create_visual_node_at_position!(self, c_node, neighbor_id, position);
-            let w = weight_strings
-                .get(&(self.root as u32))
-                .and_then(|m| m.get(&(neighbor_id as u32)));
-            create_edge_between_nodes!(
-                self,
-                self.root,
-                neighbor_id,
-                root_node.center,
-                c_node.center,
-                w.unwrap().as_str()
-            );
+            if self.edges.contains_key(&(self.root, neighbor_id)) {
+                self.add_edge(self.root, neighbor_id, w.unwrap().as_str());
+            }
 
             position = rotate_around!(global_center, position, c_rotation);
-            unsafe { neighbor_id_ptr = neighbor_id_ptr.add(1) };
         }
     }
 
@@ -778,13 +748,112 @@ impl<'a> Graph<'a> {
             .neighbors
             .retain(|&n| n != to_id);
         self.edges.remove(&(from_id, to_id));
+        if let Some(reverse) = self.edges.get_mut(&(to_id, from_id))
+            && reverse.shape() == LineShape::Bent
+        {
+            let (start, end) = edge_endpoints(
+                self.nodes.get(&to_id).unwrap(),
+                self.nodes.get(&from_id).unwrap(),
+                LineShape::Straight,
+            );
+            reverse.set_shape(LineShape::Straight, start, end);
+        }
     }
 
     fn add_node(&mut self, key: usize, node: NodeWrapper) {
         self.nodes.insert(key, node);
     }
 
+    //missing nodes are skipped, a diff may add and remove the same node
+    pub fn highlight_node(&mut self, id: usize, color: NodeColor) {
+        if let Some(visual_node) = self
+            .nodes
+            .get_mut(&id)
+            .and_then(|node| node.visual_node.as_mut())
+        {
+            visual_node.highlight_node(Some(color));
+            self.highlighted_nodes.push(id);
+        }
+    }
+
+    pub fn highlight_edge(&mut self, from_id: usize, to_id: usize, color: NodeColor) {
+        if let Some(edge) = self.edges.get_mut(&(from_id, to_id)) {
+            edge.highlight_line(Some(color));
+            self.highlighted_edges.push((from_id, to_id));
+        }
+    }
+
+    //call before removing the node, its edges go with it so they get ghosts too
+    pub fn ghost_node(&mut self, id: usize, color: NodeColor, edge_color: NodeColor) {
+        if !self.show_ghosts {
+            return;
+        }
+        if let Some(visual_node) = self.nodes.get(&id).and_then(|node| node.visual_node.as_ref()) {
+            self.ghosts.push(visual_node.ghost(color));
+        }
+        let mut incident: Vec<(usize, usize)> = self
+            .edges
+            .keys()
+            .filter(|&&(from_id, to_id)| from_id == id || to_id == id)
+            .copied()
+            .collect();
+        incident.sort();
+        for (from_id, to_id) in incident {
+            self.ghost_edge(from_id, to_id, edge_color);
+        }
+    }
+
+    pub fn ghost_edge(&mut self, from_id: usize, to_id: usize, color: NodeColor) {
+        if !self.show_ghosts {
+            return;
+        }
+        if let Some(edge) = self.edges.get(&(from_id, to_id)) {
+            self.ghosts.push(edge.ghost(color));
+        }
+    }
+
+    //the highlights of a step only last until the next one
+    pub fn clear_highlights(&mut self) {
+        for id in self.highlighted_nodes.drain(..) {
+            if let Some(visual_node) = self
+                .nodes
+                .get_mut(&id)
+                .and_then(|node| node.visual_node.as_mut())
+            {
+                visual_node.highlight_node(None);
+            }
+        }
+        for key in self.highlighted_edges.drain(..) {
+            if let Some(edge) = self.edges.get_mut(&key) {
+                edge.highlight_line(None);
+            }
+        }
+        self.remove_ghosts();
+    }
+
+    pub fn remove_ghosts(&mut self) {
+        for ghost in self.ghosts.iter_mut() {
+            ghost.remove();
+        }
+        self.ghosts.clear();
+    }
+
+    //ghosts that are no longer visible are removed
+    pub fn fade_ghosts(&mut self, amount: f32) {
+        self.ghosts.retain_mut(|ghost| {
+            let alpha = ghost.alpha() - amount;
+            if alpha <= 0.0 {
+                ghost.remove();
+                false
+            } else {
+                ghost.set_alpha(alpha);
+                true
+            }
+        });
+    }
+
     pub fn clean_graph(&mut self) {
+        self.clear_highlights();
         for node in self.nodes.values_mut() {
             if let Some(c_node) = node.visual_node.as_mut() {
                 c_node.remove_node();
@@ -0,0 +1,511 @@
+use crate::binary::{self, is_binary};
+use crate::dot;
+use crate::json_deserialize::{
+  BaseInfo, DiffInfo, Edge, Node, Root, ShapeHint, Style, StyleChange, StyleTarget, TimelineCount, TimelineOrder,
+  base_info_helper, deserialize_json, diff_helper, keeps_order, style_helper,
+};
+use serde_json::{Map, Value, json};
+use std::error::Error;
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use trace::diff::{LabelChange, WeightChange};
+
+//one line of a streamed or newline delimited trace, node labels have to come before a base or diff uses them
+//{"type": "node", "id": 3, "label": "7", "style": {"fill": "red"}}
+//{"type": "edge_style", "from_id": 0, "to_id": 3, "style": {"stroke": "gray"}}
+//{"type": "base", "listener": 0, "base": {"root": 0, "0": [{"1": "left"}], "labels": {"1": "8"}}}
+//{"type": "diff", "listener": 0, "diff": {"added_nodes": [...], "note": "", "root_id": 0}}
+#[derive(Debug)]
+pub enum TraceEvent {
+  Node { id: u32, label: String, style: Style },
+  EdgeStyle { from_id: u32, to_id: u32, style: Style }, //like the trace's edge_styles, for every step
+  Base { listener_id: u8, base: BaseInfo }, //starts the listener over
+  Diff { listener_id: u8, diff: DiffInfo },
+}
+
+pub fn parse_event(line: &str) -> Result<TraceEvent, String> {
+  let value: Value = serde_json::from_str(line).map_err(|e| e.to_string())?;
+  let listener_id = || {
+    value.get("listener")
+      .and_then(Value::as_u64)
+      .filter(|id| *id < u8::MAX as u64)
+      .map(|id| id as u8)
+      .ok_or_else(|| String::from("no listener id"))
+  };
+  let id = |key: &str| value.get(key).and_then(Value::as_u64).map(|id| id as u32).ok_or(format!("no {}", key));
+  let object = |key: &str| value.get(key).filter(|inner| inner.is_object()).ok_or(format!("no {} object", key));
+  match value.get("type").and_then(Value::as_str) {
+    Some("node") => Ok(TraceEvent::Node {
+      id: id("id")?,
+      label: value.get("label").and_then(Value::as_str).unwrap_or_default().to_string(),
+      style: value.get("style").map(style_helper).unwrap_or_default(),
+    }),
+    Some("edge_style") => Ok(TraceEvent::EdgeStyle {
+      from_id: id("from_id")?,
+      to_id: id("to_id")?,
+      style: value.get("style").map(style_helper).unwrap_or_default(),
+    }),
+    Some("base") => Ok(TraceEvent::Base { listener_id: listener_id()?, base: base_info_helper(object("base")?)? }),
+    Some("diff") => Ok(TraceEvent::Diff { listener_id: listener_id()?, diff: diff_helper(object("diff")?)? }),
+    Some(other) => Err(format!("unknown type {:?}", other)),
+    None => Err(String::from("no type")),
+  }
+}
+
+//a base or diff naming nodes that were never registered is turned down and leaves the root as it was,
+//so is a diff that would leave the trace without one order
+pub fn apply_event(root: &mut Root, event: TraceEvent) -> Result<(), String> {
+  match &event {
+    TraceEvent::Base { listener_id, base } => {
+      base.check_ids(root.nodes.len())?;
+      let cleared = root.diffs.get(*listener_id as usize).map(Vec::as_slice).unwrap_or_default();
+      let mut timeline = root.timeline;
+      cleared.iter().for_each(|diff| timeline.remove(diff));
+      let others = root.diffs.iter().enumerate().filter(|(id, _)| *id != *listener_id as usize);
+      check_timeline(root.timeline, timeline, others.map(|(_, diffs)| diffs))?;
+    }
+    TraceEvent::Diff { listener_id, diff } => {
+      diff.check_ids(root.nodes.len())?;
+      let mut timeline = root.timeline;
+      timeline.add(diff);
+      let order = check_timeline(root.timeline, timeline, root.diffs.iter())?;
+      if let Some(previous) = root.diffs.get(*listener_id as usize).and_then(|diffs| diffs.last()) {
+        keeps_order(order, previous, diff)?;
+      }
+    }
+    TraceEvent::Node { .. } | TraceEvent::EdgeStyle { .. } => {}
+  }
+  match event {
+    TraceEvent::Node { id, label, style } => root.register_node(id, label, style),
+    TraceEvent::EdgeStyle { from_id, to_id, style } => {
+      root.edge_styles.insert((from_id, to_id), style);
+    }
+    TraceEvent::Base { listener_id, base } => {
+      root.ensure_listener(listener_id);
+      root.bases[listener_id as usize] = base;
+      root.clear_diffs(listener_id as usize);
+    }
+    TraceEvent::Diff { listener_id, diff } => {
+      root.ensure_listener(listener_id);
+      root.push_diff(listener_id as usize, diff);
+    }
+  }
+  Ok(())
+}
+
+//only the counts are looked at while the order stays the same. when it changes every diff that
+//stays is checked against the new one, a trace does that a handful of times at most
+fn check_timeline<'a>(
+  before: TimelineCount,
+  after: TimelineCount,
+  listeners: impl Iterator<Item = &'a Vec<DiffInfo>>,
+) -> Result<TimelineOrder, String> {
+  let order = after.order()?;
+  if before.order() != Ok(order) {
+    for diffs in listeners {
+      for pair in diffs.windows(2) {
+        keeps_order(order, &pair[0], &pair[1])?;
+      }
+    }
+  }
+  Ok(order)
+}
+
+//any of the formats, told apart by how the file starts
+pub fn read_trace(path: &str) -> Result<Root, Box<dyn Error>> {
+  if dot::is_dot_path(path) {
+    return dot::read_dot(&[path]);
+  }
+  let mut reader = BufReader::new(File::open(path)?);
+  if is_binary(reader.fill_buf()?) {
+    return binary::read_binary(path);
+  }
+  let mut first_line = String::new();
+  reader.read_line(&mut first_line)?;
+  let is_event = serde_json::from_str::<Value>(first_line.trim())
+    .map(|value| value.get("type").is_some())
+    .unwrap_or(false);
+  if is_event { read_lines(path) } else { deserialize_json(path) }
+}
+
+//builds the root as the lines come in. a program that crashed mid-write leaves a cut off last
+//line that does not parse, that one is dropped. any other bad line fails the whole read
+pub fn read_lines(path: &str) -> Result<Root, Box<dyn Error>> {
+  let mut reader = BufReader::new(File::open(path)?);
+  let mut root = Root::new(0);
+  let mut line = String::new();
+  let mut line_number = 0;
+  loop {
+    line.clear();
+    if reader.read_line(&mut line)? == 0 {
+      break;
+    }
+    line_number += 1;
+    let text = line.trim();
+    if text.is_empty() {
+      continue;
+    }
+    let event = match parse_event(text) {
+      Ok(event) => event,
+      Err(_) if !line.ends_with('\n') => {
+        println!("Ignoring the unfinished last line {} of {}", line_number, path);
+        break;
+      }
+      Err(message) => return Err(format!("line {}: {}", line_number, message).into()),
+    };
+    apply_event(&mut root, event).map_err(|message| format!("line {}: {}", line_number, message))?;
+  }
+  Ok(root)
+}
+
+//every node first, then each listener's base followed by its diffs
+pub fn root_events(root: &Root) -> Vec<TraceEvent> {
+  let mut events = Vec::new();
+  for (id, label) in root.nodes.iter().enumerate() {
+    let style = root.node_styles.get(id).copied().unwrap_or_default();
+    events.push(TraceEvent::Node { id: id as u32, label: label.clone(), style });
+  }
+  let mut edge_styles: Vec<(&(u32, u32), &Style)> = root.edge_styles.iter().collect();
+  edge_styles.sort_by_key(|(ends, _)| **ends);
+  for (&(from_id, to_id), style) in edge_styles {
+    events.push(TraceEvent::EdgeStyle { from_id, to_id, style: *style });
+  }
+  for listener_id in 0..root.total_listeners {
+    events.push(TraceEvent::Base { listener_id, base: root.bases[listener_id as usize].clone() });
+    for diff in &root.diffs[listener_id as usize] {
+      events.push(TraceEvent::Diff { listener_id, diff: diff.clone() });
+    }
+  }
+  events
+}
+
+pub fn event_value(event: &TraceEvent) -> Value {
+  let mut value = match event {
+    TraceEvent::Node { id, label, style } => node_value(*id, label, style),
+    TraceEvent::EdgeStyle { from_id, to_id, style } => json!({"from_id": from_id, "to_id": to_id, "style": style_value(style)}),
+    TraceEvent::Base { listener_id, base } => json!({"listener": listener_id, "base": base_value(base)}),
+    TraceEvent::Diff { listener_id, diff } => json!({"listener": listener_id, "diff": diff_value(diff)}),
+  };
+  let kind = match event {
+    TraceEvent::Node { .. } => "node",
+    TraceEvent::EdgeStyle { .. } => "edge_style",
+    TraceEvent::Base { .. } => "base",
+    TraceEvent::Diff { .. } => "diff",
+  };
+  value["type"] = Value::from(kind);
+  value
+}
+
+//the single object format deserialize_json reads
+pub fn root_value(root: &Root) -> Value {
+  let nodes: Vec<Value> = root.nodes.iter()
+    .enumerate()
+    .map(|(id, label)| node_value(id as u32, label, &root.node_styles.get(id).copied().unwrap_or_default()))
+    .collect();
+  let mut edge_styles: Vec<(&(u32, u32), &Style)> = root.edge_styles.iter().collect();
+  edge_styles.sort_by_key(|(ends, _)| **ends);
+  let edge_styles: Vec<Value> = edge_styles.into_iter()
+    .map(|(&(from_id, to_id), style)| json!({"from_id": from_id, "to_id": to_id, "style": style_value(style)}))
+    .collect();
+  let mut bases = Map::new();
+  let mut diffs = Map::new();
+  for listener_id in 0..root.total_listeners as usize {
+    bases.insert(format!("L{}", listener_id), base_value(&root.bases[listener_id]));
+    diffs.insert(format!("L{}", listener_id), root.diffs[listener_id].iter().map(diff_value).collect());
+  }
+  json!({
+    "specifiers": {"total_listeners": root.total_listeners},
+    "nodes": nodes,
+    "edge_styles": edge_styles,
+    "bases": bases,
+    "diffs": diffs,
+  })
+}
+
+fn node_value(id: u32, label: &str, style: &Style) -> Value {
+  let mut value = json!({"id": id, "label": label});
+  if *style != Style::default() {
+    value["style"] = style_value(style);
+  }
+  value
+}
+
+fn style_value(style: &Style) -> Value {
+  let mut value = Map::new();
+  let color = |(r, g, b): (f32, f32, f32)| json!([r, g, b]);
+  if let Some(fill) = style.fill {
+    value.insert(String::from("fill"), color(fill));
+  }
+  if let Some(stroke) = style.stroke {
+    value.insert(String::from("stroke"), color(stroke));
+  }
+  if let Some(shape) = style.shape {
+    let name = match shape {
+      ShapeHint::Circle => "circle",
+      ShapeHint::Square => "square",
+      ShapeHint::Rectangle => "rectangle",
+    };
+    value.insert(String::from("shape"), Value::from(name));
+  }
+  if let Some((width, height)) = style.size {
+    value.insert(String::from("size"), json!([width, height]));
+  }
+  if let Some(thickness) = style.thickness {
+    value.insert(String::from("thickness"), Value::from(thickness));
+  }
+  Value::Object(value)
+}
+
+fn base_value(base: &BaseInfo) -> Value {
+  let mut value = Map::new();
+  value.insert(String::from("root"), Value::from(base.root_id));
+  value.insert(String::from("note"), Value::from(base.note.as_str()));
+  if !base.labels.is_empty() {
+    let mut labels: Vec<(&u32, &String)> = base.labels.iter().collect();
+    labels.sort();
+    let labels: Map<String, Value> = labels.into_iter()
+      .map(|(id, label)| (id.to_string(), Value::from(label.as_str())))
+      .collect();
+    value.insert(String::from("labels"), Value::Object(labels));
+  }
+  for (from_id, connections) in &base.edges {
+    let mut connections: Vec<(&u32, &String)> = connections.iter().collect();
+    connections.sort();
+    let connections: Vec<Value> = connections.into_iter()
+      .map(|(to_id, weight)| json!({to_id.to_string(): weight}))
+      .collect();
+    value.insert(from_id.to_string(), Value::from(connections));
+  }
+  Value::Object(value)
+}
+
+fn diff_value(diff: &DiffInfo) -> Value {
+  let edges = |edges: &[Edge]| -> Value {
+    edges.iter().map(|edge| json!({"from_id": edge.from_id, "to_id": edge.to_id, "weight": edge.weight})).collect()
+  };
+  let nodes = |nodes: &[Node]| -> Value {
+    nodes.iter().map(|node| json!({"id": node.id, "label": node.label})).collect()
+  };
+  let mut value = json!({
+    "added_edges": edges(&diff.added_edges),
+    "removed_edges": edges(&diff.removed_edges),
+    "added_nodes": nodes(&diff.added_nodes),
+    "removed_nodes": nodes(&diff.removed_nodes),
+    "weights_changed": diff.weights_changed.iter().map(WeightChange::to_value).collect::<Value>(),
+    "labels_changed": diff.labels_changed.iter().map(LabelChange::to_value).collect::<Value>(),
+    "styles_changed": diff.styles_changed.iter().map(style_change_value).collect::<Value>(),
+    "note": diff.note,
+    "root_id": diff.root_id,
+  });
+  if let Some(seq) = diff.seq {
+    value["seq"] = Value::from(seq);
+  }
+  if let Some(timestamp) = diff.timestamp {
+    value["timestamp"] = Value::from(timestamp);
+  }
+  value
+}
+
+fn style_change_value(change: &StyleChange) -> Value {
+  match change.target {
+    StyleTarget::Node(id) => json!({"id": id, "style": style_value(&change.style)}),
+    StyleTarget::Edge(from_id, to_id) => json!({"from_id": from_id, "to_id": to_id, "style": style_value(&change.style)}),
+  }
+}
+
+fn write_trace(root: &Root, path: &str) -> Result<(), Box<dyn Error>> {
+  if path.ends_with(".pltr") {
+    return binary::write_binary(root, path);
+  }
+  let mut writer = BufWriter::new(File::create(path)?);
+  if path.ends_with(".ndjson") || path.ends_with(".jsonl") {
+    for event in root_events(root) {
+      writeln!(writer, "{}", event_value(&event))?;
+    }
+  } else {
+    serde_json::to_writer(&mut writer, &root_value(root))?;
+  }
+  writer.flush()?;
+  Ok(())
+}
+
+//--convert <trace> <output>, the output is newline delimited when it ends in .ndjson or .jsonl
+//and binary when it ends in .pltr. several dot files become one trace with a step for each graph
+pub fn run_convert_command(args: &[String]) -> i32 {
+  let Some((output, inputs)) = args.split_last().filter(|(_, inputs)| !inputs.is_empty()) else {
+    println!("usage: --convert <trace | dot files...> <output.json|output.ndjson|output.pltr>");
+    return 2;
+  };
+  let read = match inputs {
+    [input] => read_trace(input),
+    _ if inputs.iter().all(|input| dot::is_dot_path(input)) => {
+      dot::read_dot(&inputs.iter().map(String::as_str).collect::<Vec<_>>())
+    }
+    _ => Err("only dot files can be read one after another".into()),
+  };
+  let root = match read {
+    Ok(root) => root,
+    Err(e) => {
+      println!("Could not read {}: {}", inputs.join(", "), e);
+      return 2;
+    }
+  };
+  if let Err(e) = write_trace(&root, output) {
+    println!("Could not write {}: {}", output, e);
+    return 1;
+  }
+  println!("Wrote {} with {} listeners and {} nodes", output, root.total_listeners, root.nodes.len());
+  0
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn root_with_node() -> Root {
+    let mut root = Root::new(0);
+    apply_event(&mut root, parse_event(r#"{"type": "node", "id": 0, "label": "a"}"#).unwrap()).unwrap();
+    apply_event(&mut root, parse_event(r#"{"type": "base", "listener": 0, "base": {"root": 0}}"#).unwrap()).unwrap();
+    root
+  }
+
+  #[test]
+  fn bad_lines_are_errors() {
+    let lines = [
+      r#"[1, 2]"#,
+      r#"{"type": "diff", "listener": 0, "diff": []}"#,
+      r#"{"type": "diff", "listener": 0, "diff": {"added_nodes": [{"id": "x"}], "root_id": 0}}"#,
+      r#"{"type": "diff", "listener": 0, "diff": {"added_nodes": {}, "root_id": 0}}"#,
+      r#"{"type": "diff", "listener": 0, "diff": {"labels_changed": [{"id": 0}], "root_id": 0}}"#,
+      r#"{"type": "diff", "listener": 0, "diff": {"note": ""}}"#,
+      r#"{"type": "base", "listener": 0, "base": {"root": "zero"}}"#,
+      r#"{"type": "base", "listener": 0, "base": {"0": [{"x": "left"}]}}"#,
+      r#"{"type": "base", "listener": 0, "base": {"leaves": 4}}"#,
+    ];
+    for line in lines {
+      assert!(parse_event(line).is_err(), "{} was read", line);
+    }
+  }
+
+  #[test]
+  fn unknown_ids_are_turned_down() {
+    let mut root = root_with_node();
+    let lines = [
+      r#"{"type": "diff", "listener": 0, "diff": {"added_nodes": [{"id": 5, "label": "b"}], "root_id": 0}}"#,
+      r#"{"type": "diff", "listener": 0, "diff": {"added_edges": [{"from_id": 0, "to_id": 1, "weight": ""}], "root_id": 0}}"#,
+      r#"{"type": "diff", "listener": 0, "diff": {"root_id": 2}}"#,
+      r#"{"type": "base", "listener": 1, "base": {"root": 0, "0": [{"3": ""}]}}"#,
+    ];
+    for line in lines {
+      assert!(apply_event(&mut root, parse_event(line).unwrap()).is_err(), "{} was applied", line);
+    }
+    assert_eq!(root.total_listeners, 1);
+    assert!(root.diffs[0].is_empty());
+
+    let fine = r#"{"type": "diff", "listener": 0, "diff": {"labels_changed": [{"id": 0, "old": "a", "new": "b"}], "root_id": 0}}"#;
+    apply_event(&mut root, parse_event(fine).unwrap()).unwrap();
+    assert_eq!(root.diffs[0].len(), 1);
+    assert_eq!(root.diffs[0][0].note, "");
+  }
+  #[test]
+  fn diffs_have_to_keep_the_trace_in_one_order() {
+    let mut root = root_with_node();
+    let seq = r#"{"type": "diff", "listener": 0, "diff": {"root_id": 0, "seq": 1}}"#;
+    let timestamp = r#"{"type": "diff", "listener": 1, "diff": {"root_id": 0, "timestamp": 0.5}}"#;
+    apply_event(&mut root, parse_event(seq).unwrap()).unwrap();
+    assert!(apply_event(&mut root, parse_event(timestamp).unwrap()).is_err());
+    assert_eq!(root.diffs[0].len(), 1);
+  }
+
+  fn temp_path(name: &str) -> String {
+    std::env::temp_dir().join(format!("parse_listen_events_{}", name)).to_string_lossy().into_owned()
+  }
+
+  fn bst_lines() -> Vec<String> {
+    let root = read_trace("traces/bst.json").unwrap();
+    root_events(&root).iter().map(|event| event_value(event).to_string()).collect()
+  }
+
+  #[test]
+  fn a_cut_off_last_line_is_dropped() {
+    let path = temp_path("cut_off.ndjson");
+    let lines = bst_lines();
+    let whole = lines.join("\n") + "\n";
+    let last = lines.last().unwrap();
+    let cut = &whole[..whole.len() - last.len() / 2];
+    std::fs::write(&path, cut).unwrap();
+    let read = read_lines(&path);
+    std::fs::write(&path, whole.trim_end()).unwrap();
+    let unterminated = read_lines(&path);
+    let _ = std::fs::remove_file(&path);
+
+    let full = read_trace("traces/bst.json").unwrap();
+    let diffs = |root: &Root| root.diffs.iter().map(Vec::len).sum::<usize>();
+    assert_eq!(diffs(&read.unwrap()), diffs(&full) - 1);
+    assert_eq!(root_value(&unterminated.unwrap()), root_value(&full));
+  }
+
+  #[test]
+  fn bad_lines_before_the_end_fail_the_read() {
+    let path = temp_path("bad_line.ndjson");
+    let mut lines = bst_lines();
+    let middle = lines.len() / 2;
+    let half = lines[middle].len() / 2;
+    lines[middle].truncate(half);
+    std::fs::write(&path, lines.join("\n") + "\n").unwrap();
+    let broken = read_lines(&path).map(|_| ()).map_err(|e| e.to_string());
+
+    //an unfinished last line that parses but names a node nobody registered is not a crash
+    let mut lines = bst_lines();
+    lines.push(String::from(r#"{"type": "diff", "listener": 0, "diff": {"root_id": 99}}"#));
+    std::fs::write(&path, lines.join("\n")).unwrap();
+    let unknown = read_lines(&path).map(|_| ()).map_err(|e| e.to_string());
+    let _ = std::fs::remove_file(&path);
+
+    assert!(broken.as_ref().is_err_and(|message| message.starts_with(&format!("line {}:", middle + 1))), "{:?}", broken);
+    assert!(unknown.as_ref().is_err_and(|message| message.starts_with(&format!("line {}:", lines.len()))), "{:?}", unknown);
+  }
+
+  #[test]
+  fn traces_convert_to_lines_and_back() {
+    for trace in ["bst", "rbtree", "lattice"] {
+      let original = format!("traces/{}.json", trace);
+      let (lines, json) = (temp_path(&format!("{}.ndjson", trace)), temp_path(&format!("{}.json", trace)));
+      let to_lines = run_convert_command(&[original.clone(), lines.clone()]);
+      let to_json = run_convert_command(&[lines.clone(), json.clone()]);
+      let (from_lines, from_json) = (read_lines(&lines), read_trace(&json));
+      let _ = std::fs::remove_file(&lines);
+      let _ = std::fs::remove_file(&json);
+
+      assert_eq!((to_lines, to_json), (0, 0), "{} did not convert", trace);
+      let expected = root_value(&read_trace(&original).unwrap());
+      assert_eq!(root_value(&from_lines.unwrap()), expected, "{} reads back different from lines", trace);
+      assert_eq!(root_value(&from_json.unwrap()), expected, "{} reads back different from json", trace);
+    }
+  }
+
+  #[test]
+  fn a_listener_cannot_go_back_in_the_order() {
+    let mut root = root_with_node();
+    let diff = |listener_id: u8, order: &str| {
+      let line = format!(r#"{{"type": "diff", "listener": {}, "diff": {{"root_id": 0, {}}}}}"#, listener_id, order);
+      parse_event(&line).unwrap()
+    };
+    apply_event(&mut root, diff(0, r#""seq": 5, "timestamp": 2.0"#)).unwrap();
+    apply_event(&mut root, diff(1, r#""seq": 1, "timestamp": 9.0"#)).unwrap();
+    apply_event(&mut root, diff(0, r#""seq": 5, "timestamp": 1.0"#)).unwrap();
+    assert!(apply_event(&mut root, diff(0, r#""seq": 4"#)).is_err());
+    assert!(apply_event(&mut root, diff(1, r#""seq": 0"#)).is_err());
+    //without a seq the trace goes by timestamps, listener 0 already went from 2 back to 1
+    assert!(apply_event(&mut root, diff(1, r#""timestamp": 10.0"#)).is_err());
+    assert_eq!((root.diffs[0].len(), root.diffs[1].len()), (2, 1));
+
+    //a new base drops listener 0's diffs, and with them the step back
+    let base = r#"{"type": "base", "listener": 0, "base": {"root": 0}}"#;
+    apply_event(&mut root, parse_event(base).unwrap()).unwrap();
+    apply_event(&mut root, diff(1, r#""timestamp": 10.0"#)).unwrap();
+    assert!(apply_event(&mut root, diff(0, r#""timestamp": 9.5"#)).is_ok());
+    assert!(apply_event(&mut root, diff(1, r#""timestamp": 9.5"#)).is_err());
+    assert!(root.check().is_ok());
+  }
+}
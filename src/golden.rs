@@ -0,0 +1,292 @@
+use crate::events::read_trace;
+use crate::graph_draw::{GhostMode, GraphDrawBuilder};
+use crate::json_deserialize::Root;
+use crate::render::{self, Handle, Recording, RecordingBackend, Rgb, ShapeKind};
+use crate::raster::option_value;
+use crate::routing::{self, EdgeRouting};
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+use std::fs;
+use std::rc::Rc;
+
+const GOLDEN_SEED: u64 = 0x5eed;
+const PRECISION: f32 = 100.0; //floats are stored with two decimals so tiny drifts do not fail
+
+//what is on screen after replaying a listener to a step
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct Scene {
+  pub listener_id: usize,
+  pub step: usize,
+  pub sprites: Vec<SceneSprite>,
+  pub texts: Vec<SceneText>,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct SceneSprite {
+  pub id: Handle,
+  pub kind: ShapeKind,
+  pub position: (f32, f32),
+  pub size: (f32, f32),
+  pub thickness: f32,
+  pub color: Rgb,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct SceneText {
+  pub id: Handle,
+  pub text: String,
+  pub center: (i32, i32),
+  pub max_size: (i32, i32),
+  pub rotation: f32,
+}
+
+fn round(value: f32) -> f32 {
+  (value * PRECISION).round() / PRECISION
+}
+
+impl Scene {
+  pub fn from_recording(recording: &Recording, listener_id: usize, step: usize) -> Self {
+    let mut sprites: Vec<SceneSprite> = recording.sprites.iter()
+      .map(|(id, sprite)| SceneSprite {
+        id: *id,
+        kind: sprite.kind,
+        position: (round(sprite.x), round(sprite.y)),
+        size: (round(sprite.width), round(sprite.height)),
+        thickness: round(sprite.thickness),
+        color: (round(sprite.color.0), round(sprite.color.1), round(sprite.color.2)),
+      })
+      .collect();
+    sprites.sort_by_key(|sprite| sprite.id);
+
+    let mut texts: Vec<SceneText> = recording.texts.iter()
+      .map(|(id, text)| SceneText {
+        id: *id,
+        text: text.text.clone(),
+        center: text.center,
+        max_size: text.max_size,
+        rotation: round(text.rotation),
+      })
+      .collect();
+    texts.sort_by_key(|text| text.id);
+
+    Scene { listener_id, step, sprites, texts }
+  }
+}
+
+//replays a listener to a step headlessly, layout_iterations force steps are run afterwards
+//returns what was drawn and the camera of the tile showing the listener
+pub fn replay_recording(root: &Root, listener_id: usize, step: usize, layout_iterations: usize,
+  routing: EdgeRouting)
+  -> (Rc<RefCell<Recording>>, (f32, f32)) {
+  let backend = RecordingBackend::new();
+  let recording = backend.recording();
+  let previous = render::set_backend(Box::new(backend));
+  let camera;
+  {
+    let mut graph_draw = GraphDrawBuilder::new()
+      .viewport((0, 0), (1920, 1080))
+      .root(root)
+      .listener_id(listener_id)
+      .seed(GOLDEN_SEED)
+      .ghost_mode(GhostMode::Keep) //a picture of a step should still show what it removed
+      .edge_routing(routing)
+      .build()
+      .expect("Error building graph drawer");
+    for i in 0..(root.total_listeners as usize) {
+      graph_draw.add_new_graph(i);
+    }
+    graph_draw.seek(listener_id, step);
+    for _ in 0..layout_iterations {
+      if graph_draw.simulation_step() {
+        break;
+      }
+    }
+    camera = graph_draw.focused_tile().camera;
+  }
+  render::set_backend(previous);
+  (recording, camera)
+}
+
+//with 0 layout iterations the scene only depends on the trace, edges are only routed while the
+//layout runs
+pub fn replay_scene(root: &Root, listener_id: usize, step: usize, layout_iterations: usize, routing: EdgeRouting) -> Scene {
+  let (recording, _) = replay_recording(root, listener_id, step, layout_iterations, routing);
+  Scene::from_recording(&recording.borrow(), listener_id, step)
+}
+
+//one line per difference, empty when the scenes match
+pub fn diff_scenes(expected: &Scene, actual: &Scene) -> Vec<String> {
+  let mut differences = Vec::new();
+  if expected.listener_id != actual.listener_id || expected.step != actual.step {
+    differences.push(format!("scene is listener {} step {}, expected listener {} step {}",
+      actual.listener_id, actual.step, expected.listener_id, expected.step));
+  }
+
+  for sprite in &expected.sprites {
+    match actual.sprites.iter().find(|other| other.id == sprite.id) {
+      None => differences.push(format!("sprite {} ({:?}) is missing", sprite.id, sprite.kind)),
+      Some(other) if other != sprite => {
+        let mut changes = Vec::new();
+        if other.kind != sprite.kind {
+          changes.push(format!("kind {:?} -> {:?}", sprite.kind, other.kind));
+        }
+        if other.position != sprite.position {
+          changes.push(format!("position {:?} -> {:?}", sprite.position, other.position));
+        }
+        if other.size != sprite.size {
+          changes.push(format!("size {:?} -> {:?}", sprite.size, other.size));
+        }
+        if other.thickness != sprite.thickness {
+          changes.push(format!("thickness {} -> {}", sprite.thickness, other.thickness));
+        }
+        if other.color != sprite.color {
+          changes.push(format!("color {:?} -> {:?}", sprite.color, other.color));
+        }
+        differences.push(format!("sprite {} ({:?}): {}", sprite.id, sprite.kind, changes.join(", ")));
+      }
+      Some(_) => {}
+    }
+  }
+  for sprite in &actual.sprites {
+    if !expected.sprites.iter().any(|other| other.id == sprite.id) {
+      differences.push(format!("sprite {} ({:?}) is new at {:?}", sprite.id, sprite.kind, sprite.position));
+    }
+  }
+
+  for text in &expected.texts {
+    match actual.texts.iter().find(|other| other.id == text.id) {
+      None => differences.push(format!("text {} {:?} is missing", text.id, text.text)),
+      Some(other) if other != text => {
+        let mut changes = Vec::new();
+        if other.text != text.text {
+          changes.push(format!("text {:?} -> {:?}", text.text, other.text));
+        }
+        if other.center != text.center {
+          changes.push(format!("center {:?} -> {:?}", text.center, other.center));
+        }
+        if other.max_size != text.max_size {
+          changes.push(format!("box {:?} -> {:?}", text.max_size, other.max_size));
+        }
+        if other.rotation != text.rotation {
+          changes.push(format!("rotation {} -> {}", text.rotation, other.rotation));
+        }
+        differences.push(format!("text {} {:?}: {}", text.id, text.text, changes.join(", ")));
+      }
+      Some(_) => {}
+    }
+  }
+  for text in &actual.texts {
+    if !expected.texts.iter().any(|other| other.id == text.id) {
+      differences.push(format!("text {} {:?} is new", text.id, text.text));
+    }
+  }
+  differences
+}
+
+//bless writes the current scene as the new golden file
+pub fn check_golden(root: &Root, listener_id: usize, step: usize, layout_iterations: usize, routing: EdgeRouting,
+  golden_path: &str, bless: bool) -> Result<(), String> {
+  let scene = replay_scene(root, listener_id, step, layout_iterations, routing);
+  if bless {
+    let serialized = serde_json::to_string_pretty(&scene).map_err(|e| e.to_string())?;
+    return fs::write(golden_path, serialized).map_err(|e| e.to_string());
+  }
+
+  let golden_text = fs::read_to_string(golden_path).map_err(|e| format!("{}: {}", golden_path, e))?;
+  let golden: Scene = serde_json::from_str(&golden_text).map_err(|e| format!("{}: {}", golden_path, e))?;
+  let differences = diff_scenes(&golden, &scene);
+  if differences.is_empty() {
+    Ok(())
+  } else {
+    Err(format!("{} differences against {}:\n  {}", differences.len(), golden_path, differences.join("\n  ")))
+  }
+}
+
+//--golden <trace> <listener> <step> <golden file> [--layout iterations] [--routing name] [--bless]
+pub fn run_golden_command(args: &[String]) -> i32 {
+  if args.len() < 4 {
+    println!("usage: --golden <trace> <listener> <step> <golden file> [--layout iterations] [--routing name] [--bless]");
+    return 2;
+  }
+  let root = match read_trace(&args[0]) {
+    Ok(root) => root,
+    Err(e) => {
+      println!("Could not read {}: {}", args[0], e);
+      return 2;
+    }
+  };
+  let (Ok(listener_id), Ok(step)) = (args[1].parse::<usize>(), args[2].parse::<usize>()) else {
+    println!("listener and step must be numbers");
+    return 2;
+  };
+  let bless = args.iter().any(|arg| arg == "--bless");
+  let layout_iterations = match option_value(args, "--layout").map(str::parse::<usize>) {
+    None => 0,
+    Some(Ok(iterations)) => iterations,
+    Some(Err(_)) => {
+      println!("--layout must be a number");
+      return 2;
+    }
+  };
+  let routing = match option_value(args, "--routing") {
+    None => EdgeRouting::Straight,
+    Some(name) => match EdgeRouting::from_name(name) {
+      Some(routing) => routing,
+      None => {
+        println!("Unknown routing {:?}, pick one of {:?}", name, routing::routing_names());
+        return 2;
+      }
+    },
+  };
+
+  match check_golden(&root, listener_id, step, layout_iterations, routing, &args[3], bless) {
+    Ok(()) => {
+      println!("{} listener {} step {}: ok", args[3], listener_id, step);
+      0
+    }
+    Err(message) => {
+      println!("{}", message);
+      1
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  //trace, listener, step, layout iterations, routing and the golden file, from the repository root
+  const GOLDENS: [(&str, usize, usize, usize, EdgeRouting, &str); 4] = [
+    ("traces/bst.json", 0, 2, 0, EdgeRouting::Straight, "traces/golden/bst_0_2.json"),
+    ("traces/rbtree.json", 0, 2, 0, EdgeRouting::Straight, "traces/golden/rbtree_0_2.json"),
+    ("traces/lattice.json", 0, 2, 300, EdgeRouting::Curved, "traces/golden/lattice_0_2_curved.json"),
+    ("traces/lattice.json", 0, 2, 300, EdgeRouting::Orthogonal, "traces/golden/lattice_0_2_orthogonal.json"),
+  ];
+
+  fn in_repository(file: &str) -> String {
+    format!("{}/{}", env!("CARGO_MANIFEST_DIR"), file)
+  }
+
+  #[test]
+  fn scenes_match_their_goldens() {
+    let mut failures = Vec::new();
+    for (trace, listener_id, step, layout_iterations, routing, golden) in GOLDENS {
+      let root = read_trace(&in_repository(trace)).unwrap();
+      if let Err(message) = check_golden(&root, listener_id, step, layout_iterations, routing, &in_repository(golden), false) {
+        failures.push(message);
+      }
+    }
+    assert!(failures.is_empty(), "{}", failures.join("\n"));
+  }
+
+  //the lattice's diagonals run through its middle, so a routing that stopped going around nodes
+  //would draw what straight edges do
+  #[test]
+  fn routed_goldens_do_not_show_straight_edges() {
+    let root = read_trace(&in_repository("traces/lattice.json")).unwrap();
+    let straight = replay_scene(&root, 0, 2, 300, EdgeRouting::Straight);
+    for routing in [EdgeRouting::Curved, EdgeRouting::Orthogonal] {
+      assert_ne!(replay_scene(&root, 0, 2, 300, routing).sprites, straight.sprites, "{} draws straight edges", routing.name());
+    }
+  }
+}
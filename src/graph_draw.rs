@@ -1,13 +1,22 @@
-use crate::dsas::{DIAGONAL, DIAMETER, Graph, NodeWrapper, SPACE_NODES};
-use crate::hashgrid::HashGrid;
+use crate::dsas::{DIAGONAL, DIAMETER, Graph, NodeWrapper, SPACE_NODES, edge_endpoints};
+use crate::inspector;
 use crate::json_deserialize;
-use crate::json_deserialize::{Edge, Node};
-use crate::tree::{CS, Line, NodeColor, Point};
+use crate::json_deserialize::global_timeline;
+use crate::labels::LabelPlacer;
+use crate::render;
+use crate::picking::{EDGE_REACH, PickIndex, Picked};
+use crate::routing::{EdgeRouting, Obstacles};
+use crate::events::{self, TraceEvent};
+use crate::theme;
+use crate::tree::{CS, Line, LineShape, NodeColor, Panel, Point, Tooltip};
 use crate::{
     angle_between_points, create_visual_node_at_position, distance_between_points, rotate_around,
     tree,
 };
 use core::hash;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::borrow::Cow;
 use std::collections::{HashMap, HashSet};
 use std::f32::consts::PI;
 use std::hash::Hash;
@@ -21,10 +30,17 @@ const DAMPING_CONSTANT: f32 = 8.0; // Higher damping to quickly reduce velocity
 const EMPTY_SPACE: f32 = 300.0; // Reasonable spacing for clarity
 const RESTING_LENGTH: f32 = DIAGONAL * 2.0 + SPACE_NODES as f32 + EMPTY_SPACE;
 const OVERLAP_COULOMB_MULTIPLIER: f32 = 300.0; // Lower to avoid explosive repulsion on overlap
-const GRID_SPACE: u16 = 5; // Keep as is
+const GRID_SPACE: u16 = 40; //cells of the picking grids, a bit smaller than a node
 const DT: f32 = 0.08; // Small time step for stability
 const STABLE_VELOCITY: f32 = 3.0; // Lower threshold for stability
 const MAX_COULOMB_FORCE: f32 = 5000.0; // Clamp to avoid force spikes
+const WORLD_SEPARATION: i32 = 100_000; // listeners live far apart so tiles never show each other
+const MAX_TILES: usize = 9;
+const GHOST_FADE: f32 = 0.006; //alpha lost per frame, about two seconds at 60 fps
+const LABEL_CELL: f32 = 24.0; //cells of the index weights are placed with, about a label high
+const PANEL_MARGIN: f32 = 12.0; //between the inspector and the corner of its tile
+const MIN_ZOOM: f32 = 0.2;
+const MAX_ZOOM: f32 = 5.0;
 
 #[derive(Debug)]
 pub enum Algorithm {
@@ -32,8 +48,46 @@ pub enum Algorithm {
     Randomized,
 }
 
-pub trait Render {
-    fn render(viewport: ((i32, i32), (i32, i32)), number: usize);
+//what happens to the ghosts removed nodes and edges leave behind
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GhostMode {
+    Fade, //fades out while the step plays
+    Keep, //stays until the next step
+    Hidden,
+}
+
+#[derive(Debug)]
+pub struct Tile {
+    pub viewport: ((i32, i32), (i32, i32)), //top left, bottom right
+    pub listener_id: usize,
+    pub camera: (f32, f32), //world position of the top left corner
+    pub zoom: f32,          //screen pixels per world unit
+    pub stable: bool,
+}
+
+impl Tile {
+    pub fn dimensions(&self) -> (f32, f32) {
+        (
+            (self.viewport.1.0 - self.viewport.0.0) as f32,
+            (self.viewport.1.1 - self.viewport.0.1) as f32,
+        )
+    }
+
+    pub fn contains(&self, point: (f32, f32)) -> bool {
+        let ((left, top), (right, bottom)) = self.viewport;
+        point.0 >= left as f32
+            && point.0 < right as f32
+            && point.1 >= top as f32
+            && point.1 < bottom as f32
+    }
+
+    //a point of the window to the world through this tile's camera
+    pub fn to_world(&self, point: (f32, f32)) -> (f32, f32) {
+        (
+            self.camera.0 + (point.0 - self.viewport.0.0 as f32) / self.zoom,
+            self.camera.1 + (point.1 - self.viewport.0.1 as f32) / self.zoom,
+        )
+    }
 }
 
 struct PolarNode {
@@ -45,24 +99,38 @@ struct PolarNode {
 pub struct GraphDraw<'a> {
     viewport: ((i32, i32), (i32, i32)), //top left, bottom right
     grid_spacing: u16,
-    grid: HashGrid<i32>,
-    root: &'a json_deserialize::Root,
+    picks: Vec<PickIndex>, //one per listener, indexed like graphs
+    root: Cow<'a, json_deserialize::Root>, //owned when it keeps growing while shown
     initial_algorithm: Algorithm,
-    listener_id: usize, //which listener to draw
+    tiles: Vec<Tile>, //one tile per visible listener
+    focused: usize,   //tile that receives steps and camera moves
+    split: bool,
+    page: usize, //the MAX_TILES listeners the split grid shows and the number keys pick from
+    timeline: Vec<(usize, usize)>, //(listener id, diff index) in program order
+    global_step: usize,            //entries of the timeline that are applied
+    ghost_mode: GhostMode,
+    edge_routing: EdgeRouting,
+    hovered: Option<(usize, usize)>, //listener and node under the cursor with a tooltip up
+    tooltip: Option<Tooltip>,
+    inspected: Option<(usize, usize)>, //listener and node the inspector shows
+    inspector: Option<Panel>,
+    notice: Option<Panel>,
+    notice_lines: Vec<String>, //kept to draw the notice again after a theme switch
+    dragged: Option<(usize, usize, (f32, f32))>, //listener, node and where it was grabbed from its center
     initial_position: (i32, i32),
     spring_constant: f32,
     coulomb_constant: f32,
     mass_constant: f32,
     damping_constant: f32,
     resting_length: f32,
-    pub diff_step: usize,
-    pub graphs: Vec<Graph<'a>>,
+    rng: StdRng,
+    pub graphs: Vec<Graph>,
 }
 
 pub struct GraphDrawBuilder<'a> {
     viewport: Option<((i32, i32), (i32, i32))>, //top left, bottom right
     grid_spacing: Option<u16>,
-    root: Option<&'a json_deserialize::Root>,
+    root: Option<Cow<'a, json_deserialize::Root>>,
     initial_algorithm: Option<Algorithm>,
     listener_id: Option<usize>, //which listener to draw
     initial_position: Option<(i32, i32)>,
@@ -71,11 +139,39 @@ pub struct GraphDrawBuilder<'a> {
     damping_constant: Option<f32>,
     mass_constant: Option<f32>,
     resting_length: Option<f32>,
+    seed: Option<u64>,
+    ghost_mode: Option<GhostMode>,
+    edge_routing: Option<EdgeRouting>,
+}
+
+//listeners on a page of the split grid, the last page may have fewer
+fn page_listeners(page: usize, total_listeners: usize) -> std::ops::Range<usize> {
+    let start = page * MAX_TILES;
+    start.min(total_listeners)..(start + MAX_TILES).min(total_listeners)
 }
 
 impl<'a> GraphDraw<'a> {
+    //steps every tile that has not settled yet, true when all of them are stable
     pub fn simulation_step(&mut self) -> bool {
-        let graph: *mut Graph<'a> = &mut self.graphs[self.listener_id];
+        if self.ghost_mode == GhostMode::Fade {
+            for graph in self.graphs.iter_mut() {
+                graph.fade_ghosts(GHOST_FADE);
+            }
+        }
+        let mut all_stable = true;
+        for tile_index in 0..self.tiles.len() {
+            if self.tiles[tile_index].stable {
+                continue;
+            }
+            let stable = self.simulate_graph(self.tiles[tile_index].listener_id);
+            self.tiles[tile_index].stable = stable;
+            all_stable &= stable;
+        }
+        all_stable
+    }
+
+    fn simulate_graph(&mut self, listener_id: usize) -> bool {
+        let graph: *mut Graph = &mut self.graphs[listener_id];
         let graph_nodes = unsafe { &mut (*graph).nodes };
         let mut graph_edges = unsafe { &mut (*graph).edges };
         let diameter = DIAMETER as f32;
@@ -129,7 +225,9 @@ impl<'a> GraphDraw<'a> {
                     dist = 0.0001;
                 }
 
-                let overlap_multiplier = if dist < DIAMETER as f32 * 2.0 + SPACE_NODES as f32 {
+                //grown nodes are rectangles, so how close is too close depends on the direction
+                let touching = node.border_distance(angle) + other_node.border_distance(angle);
+                let overlap_multiplier = if dist < touching + SPACE_NODES as f32 {
                     OVERLAP_COULOMB_MULTIPLIER
                 } else {
                     1.0
@@ -175,8 +273,13 @@ impl<'a> GraphDraw<'a> {
             total_force.1 += *fy;
         }
 
+        let dragged = self
+            .dragged
+            .filter(|(dragged_listener, _, _)| *dragged_listener == listener_id)
+            .map(|(_, id, _)| id);
         for (id, node) in graph_nodes.iter_mut() {
-            if unsafe { *id == (*graph).root } {
+            //the cursor holds a dragged node in place
+            if unsafe { *id == (*graph).root } || dragged == Some(*id) {
                 continue;
             }
             let force = forces.get(id).unwrap();
@@ -196,36 +299,121 @@ impl<'a> GraphDraw<'a> {
                 .move_node(node.center.0, node.center.1);
         }
 
-        for ((from_id, to_id), line) in graph_edges.iter_mut() {
-            let first_node_center = graph_nodes.get(&from_id).unwrap().center;
-            let second_node_center = graph_nodes.get(&to_id).unwrap().center;
-            let angle = angle_between_points!(first_node_center, second_node_center);
-            line.override_line(
-                Point::new(
-                    first_node_center.0 + DIAMETER as f32 * angle.cos(),
-                    first_node_center.1 - DIAMETER as f32 * angle.sin(),
-                ),
-                Point::new(
-                    second_node_center.0 - DIAMETER as f32 * angle.cos(),
-                    second_node_center.1 + DIAMETER as f32 * angle.sin(),
-                ),
+        let routing = self.edge_routing;
+        let obstacles = (routing != EdgeRouting::Straight).then(|| {
+            Obstacles::new(
+                graph_nodes
+                    .iter()
+                    .map(|(id, node)| (*id, (node.center, node.outline())))
+                    .collect(),
+                diameter,
+            )
+        });
+        //a routed edge makes sprites for its segments as it goes, in a fixed order they get the same handles every run
+        let mut edges: Vec<(&(usize, usize), &mut Line)> = graph_edges.iter_mut().collect();
+        if obstacles.is_some() {
+            edges.sort_unstable_by_key(|(ends, _)| **ends);
+        }
+        for ((from_id, to_id), line) in edges {
+            //bent pairs and self-loops keep their own shape
+            let route = match (&obstacles, line.shape()) {
+                (Some(obstacles), LineShape::Straight) => {
+                    obstacles.route(*from_id, *to_id, routing)
+                }
+                _ => None,
+            };
+            if let Some(path) = route {
+                line.route_line(path);
+                continue;
+            }
+            let (start, end) = edge_endpoints(
+                graph_nodes.get(from_id).unwrap(),
+                graph_nodes.get(to_id).unwrap(),
+                line.shape(),
             );
+            line.override_line(start, end);
+        }
+        self.place_weights(listener_id);
+        self.index_listener(listener_id);
+        //keep simulating while a node is dragged so its edges follow
+        stable && dragged.is_none()
+    }
+
+    //moves the boxes of whatever the last step moved
+    fn index_listener(&mut self, listener_id: usize) {
+        let graph = &self.graphs[listener_id];
+        let picks = &mut self.picks[listener_id];
+        let mut nodes = HashSet::new();
+        for (id, node) in graph.nodes.iter() {
+            let Some(visual_node) = node.visual_node.as_ref() else {
+                continue;
+            };
+            let Some(half_size) = visual_node.outline().half_size() else {
+                continue;
+            };
+            picks.update_node(*id, node.center, half_size);
+            nodes.insert(*id);
+        }
+        let mut edges = HashSet::new();
+        for (key, line) in graph.edges.iter() {
+            if line.is_visual() {
+                picks.update_edge(key.0, key.1, line.path());
+                edges.insert(*key);
+            }
+        }
+        picks.retain(&nodes, &edges);
+    }
+
+    //moves weights off nodes and other weights, edges in id order so a still layout does not flicker
+    fn place_weights(&mut self, listener_id: usize) {
+        let graph = &mut self.graphs[listener_id];
+        let mut keys: Vec<(usize, usize)> = graph
+            .edges
+            .iter()
+            .filter(|(_, line)| line.is_visual() && !line.weight().is_empty())
+            .map(|(key, _)| *key)
+            .collect();
+        keys.sort();
+        let candidates: Vec<Vec<(Point, f32)>> = keys
+            .iter()
+            .map(|key| graph.edges[key].weight_candidates())
+            .collect();
+        let nodes: HashMap<usize, ((f32, f32), CS)> = graph
+            .nodes
+            .iter()
+            .map(|(id, node)| (*id, (node.center, node.outline())))
+            .collect();
+        if nodes.is_empty() {
+            return;
+        }
+        let mut placer = LabelPlacer::new(&nodes, LABEL_CELL);
+        for (key, candidates) in keys.iter().zip(candidates) {
+            let line = graph.edges.get_mut(key).unwrap();
+            let chosen = placer.place(&candidates, line.weight_size());
+            let (center, angle) = candidates[chosen];
+            line.place_weight(center, angle);
         }
-        stable
     }
 
     pub fn forward_diff(&mut self) {
+        self.forward_diff_of(self.focused_listener());
+    }
+
+    pub fn forward_diff_of(&mut self, listener_id: usize) {
         //PROBLEM when it comes to removed edges,
-        if self.diff_step >= self.root.diffs[self.listener_id].len() {
+        let diff_step = self.graphs[listener_id].diff_step;
+        if diff_step >= self.root.diffs[listener_id].len() {
             return;
         }
         let current_diff: *const json_deserialize::DiffInfo =
-            &self.root.diffs[self.listener_id][self.diff_step];
+            &self.root.diffs[listener_id][diff_step];
+        self.hide_tooltip();
+        self.graphs[listener_id].clear_highlights();
         let mut nodes_to_place = HashSet::new();
-        let old_root_id = self.graphs[self.listener_id].root;
-        self.graphs[self.listener_id].root = unsafe { (*current_diff).root_id as usize };
+        let old_root_id = self.graphs[listener_id].root;
+        self.graphs[listener_id].root = unsafe { (*current_diff).root_id as usize };
         {
-            let old_root_node = self.graphs[self.listener_id]
+            let old_root_node = self.graphs[listener_id]
                 .nodes
                 .get_mut(&old_root_id)
                 .unwrap();
@@ -235,7 +423,7 @@ impl<'a> GraphDraw<'a> {
                 .unwrap()
                 .color_node(NodeColor::Default);
 
-            let root_node = self.graphs[self.listener_id]
+            let root_node = self.graphs[listener_id]
                 .nodes
                 .get_mut(&unsafe { (*current_diff).root_id as usize })
                 .unwrap();
@@ -243,47 +431,49 @@ impl<'a> GraphDraw<'a> {
                 .visual_node
                 .as_mut()
                 .unwrap()
-                .color_node(NodeColor::Blue);
+                .color_node(NodeColor::Root);
         }
 
         for node in unsafe { &(*current_diff).labels_changed } {
-            let c_node = self.graphs[self.listener_id]
+            self.graphs[listener_id].set_label(node.id as usize, &node.new);
+            let c_node = self.graphs[listener_id]
                 .nodes
                 .get_mut(&(node.id as usize))
                 .unwrap();
-            let after_str_index = node.label.find("===").unwrap();
             c_node
                 .visual_node
                 .as_mut()
                 .unwrap()
-                .label_node(&node.label[after_str_index + 3..]);
+                .label_node(&node.new);
         }
 
         for edge in unsafe { &(*current_diff).weights_changed } {
-            let c_edge = self.graphs[self.listener_id]
+            self.graphs[listener_id].set_weight(edge.from_id as usize, edge.to_id as usize, &edge.new);
+            let c_edge = self.graphs[listener_id]
                 .edges
                 .get_mut(&(edge.from_id as usize, edge.to_id as usize))
                 .unwrap();
-            let after_str_index = edge.weight.find("===").unwrap();
-            c_edge.weight_line(&edge.weight[after_str_index + 3..]);
+            c_edge.weight_line(&edge.new);
         }
 
+        //an added node comes with the label it has now, the one it was registered with can be older
         for node in unsafe { &(*current_diff).added_nodes } {
-            let global_root_id = self.graphs[self.listener_id].root;
-            let global_root_center = self.graphs[self.listener_id]
+            self.graphs[listener_id].set_label(node.id as usize, &node.label);
+            let global_root_id = self.graphs[listener_id].root;
+            let global_root_center = self.graphs[listener_id]
                 .nodes
                 .get(&global_root_id)
                 .unwrap()
                 .center;
-            self.graphs[self.listener_id].nodes.insert(
+            self.graphs[listener_id].nodes.insert(
                 node.id as usize,
                 NodeWrapper {
                     visual_node: Some(tree::Node::new(
                         CS::Circle(DIAMETER as f32),
-                        &self.root.nodes[node.id as usize],
+                        &node.label,
                         0.0,
                         0.0,
-                        NodeColor::Red,
+                        NodeColor::Default,
                     )),
                     center: global_root_center,
                     neighbors: Vec::new(),
@@ -295,30 +485,37 @@ impl<'a> GraphDraw<'a> {
             nodes_to_place.insert(node.id as usize);
         }
 
+        //the edges are drawn when their nodes are placed, with the weights the graph holds by then
         for edge in unsafe { &(*current_diff).added_edges } {
+            self.graphs[listener_id].set_weight(edge.from_id as usize, edge.to_id as usize, &edge.weight);
             let first_tobe_placed = nodes_to_place.contains(&(edge.from_id as usize));
             let second_tobe_placed = nodes_to_place.contains(&(edge.to_id as usize));
 
             if first_tobe_placed {
                 let new_center = (
-                    rand::random::<f32>() * unsafe { crate::DIMENSIONS.0 },
-                    rand::random::<f32>() * unsafe { crate::DIMENSIONS.1 },
+                    (listener_id as i32 * WORLD_SEPARATION) as f32
+                        + self.rng.random::<f32>() * unsafe { crate::DIMENSIONS.0 },
+                    self.rng.random::<f32>() * unsafe { crate::DIMENSIONS.1 },
                 );
 
-                let first_node = self.graphs[self.listener_id]
+                let label = self.graphs[listener_id].label(edge.from_id as usize).to_string();
+                let first_node = self.graphs[listener_id]
                     .nodes
                     .get_mut(&(edge.from_id as usize))
                     .unwrap();
                 first_node.center = new_center;
+                if let Some(old_visual) = first_node.visual_node.as_mut() {
+                    old_visual.remove_node();
+                }
                 first_node.visual_node = Some(tree::Node::new(
                     CS::Circle(DIAMETER as f32),
-                    &self.root.nodes[edge.from_id as usize],
+                    &label,
                     first_node.center.0,
                     first_node.center.1,
-                    NodeColor::Red,
+                    NodeColor::Default,
                 ));
             }
-            let first_node = self.graphs[self.listener_id]
+            let first_node = self.graphs[listener_id]
                 .nodes
                 .get_mut(&(edge.from_id as usize))
                 .unwrap();
@@ -326,69 +523,80 @@ impl<'a> GraphDraw<'a> {
         }
 
         for edge in unsafe { &(*current_diff).removed_edges } {
-            self.graphs[self.listener_id].remove_edge(edge.from_id as usize, edge.to_id as usize);
+            self.graphs[listener_id].ghost_edge(
+                edge.from_id as usize,
+                edge.to_id as usize,
+                NodeColor::RemovedEdge,
+            );
+            self.graphs[listener_id].remove_edge(edge.from_id as usize, edge.to_id as usize);
         }
 
         for node in unsafe { &(*current_diff).removed_nodes } {
-            if node.id as usize == self.graphs[self.listener_id].root {
-                self.graphs[self.listener_id].root =
-                    *self.graphs[self.listener_id].nodes.iter().next().unwrap().0;
+            self.graphs[listener_id].ghost_node(
+                node.id as usize,
+                NodeColor::Removed,
+                NodeColor::RemovedEdge,
+            );
+            if node.id as usize == self.graphs[listener_id].root {
+                self.graphs[listener_id].root =
+                    *self.graphs[listener_id].nodes.iter().next().unwrap().0;
             }
-            self.graphs[self.listener_id].remove_node(node.id as usize);
+            self.graphs[listener_id].remove_node(node.id as usize);
         }
 
-        self.graphs[self.listener_id].step_build();
-        for node_id in nodes_to_place {
-            self.graphs[self.listener_id]
-                .nodes
-                .get_mut(&node_id)
-                .unwrap()
-                .visual_node
-                .as_mut()
-                .unwrap()
-                .color_node(NodeColor::Red);
-        }
-        unsafe {
-            crate::STABLE_HAPPENED = false;
+        self.graphs[listener_id].step_build();
+        self.highlight_diff(listener_id, diff_step);
+        self.unsettle(listener_id);
+        self.graphs[listener_id].diff_step += 1;
+        self.apply_styles(listener_id);
+        if self.inspected.is_some_and(|(inspected, _)| inspected == listener_id) {
+            self.refresh_inspector();
         }
-        self.diff_step += 1;
     }
 
     pub fn backward_diff(&mut self) {
-        if self.diff_step == 0 {
+        self.backward_diff_of(self.focused_listener());
+    }
+
+    pub fn backward_diff_of(&mut self, listener_id: usize) {
+        if self.graphs[listener_id].diff_step == 0 {
             println!("Already at the beginning, cannot go backward");
             return;
         }
 
         // Move to previous diff step
-        self.diff_step -= 1;
+        self.graphs[listener_id].diff_step -= 1;
+        let diff_step = self.graphs[listener_id].diff_step;
 
-        if self.diff_step >= self.root.diffs[self.listener_id].len() {
+        if diff_step >= self.root.diffs[listener_id].len() {
             return;
         }
 
         let current_diff: *const json_deserialize::DiffInfo =
-            &self.root.diffs[self.listener_id][self.diff_step];
+            &self.root.diffs[listener_id][diff_step];
         unsafe {
             println!("Diff step: {:#?}", *current_diff);
         }
+        self.hide_tooltip();
+        self.graphs[listener_id].clear_highlights();
         let mut nodes_to_remove = HashSet::new();
 
         // First, add back removed nodes (opposite of removing them)
         for node in unsafe { &(*current_diff).removed_nodes } {
             println!("Adding back removed node: {}", node.id);
-            let global_root_id = self.graphs[self.listener_id].root;
-            let global_root_center = self.graphs[self.listener_id]
+            self.graphs[listener_id].set_label(node.id as usize, &node.label);
+            let global_root_id = self.graphs[listener_id].root;
+            let global_root_center = self.graphs[listener_id]
                 .nodes
                 .get(&global_root_id)
                 .unwrap()
                 .center;
-            self.graphs[self.listener_id].nodes.insert(
+            self.graphs[listener_id].nodes.insert(
                 node.id as usize,
                 NodeWrapper {
                     visual_node: Some(tree::Node::new(
                         CS::Circle(DIAMETER as f32),
-                        &self.root.nodes[node.id as usize],
+                        &node.label,
                         0.0,
                         0.0,
                         NodeColor::Default,
@@ -408,12 +616,13 @@ impl<'a> GraphDraw<'a> {
                 "Adding back removed edge: from {} to {}",
                 edge.from_id, edge.to_id
             );
-            self.graphs[self.listener_id].add_edge(
+            self.graphs[listener_id].set_weight(edge.from_id as usize, edge.to_id as usize, &edge.weight);
+            self.graphs[listener_id].add_edge(
                 edge.from_id as usize,
                 edge.to_id as usize,
                 &edge.weight,
             );
-            let first_node = self.graphs[self.listener_id]
+            let first_node = self.graphs[listener_id]
                 .nodes
                 .get_mut(&(edge.from_id as usize))
                 .unwrap();
@@ -426,75 +635,85 @@ impl<'a> GraphDraw<'a> {
                 "Removing added edge: from {} to {}",
                 edge.from_id, edge.to_id
             );
-            self.graphs[self.listener_id].remove_edge(edge.from_id as usize, edge.to_id as usize);
+            self.graphs[listener_id].ghost_edge(
+                edge.from_id as usize,
+                edge.to_id as usize,
+                NodeColor::AddedEdge,
+            );
+            self.graphs[listener_id].remove_edge(edge.from_id as usize, edge.to_id as usize);
         }
 
         // Remove added nodes (opposite of adding them)
         for node in unsafe { &(*current_diff).added_nodes } {
             println!("Removing added node: {}", node.id);
+            self.graphs[listener_id].ghost_node(
+                node.id as usize,
+                NodeColor::Added,
+                NodeColor::AddedEdge,
+            );
             nodes_to_remove.insert(node.id as usize);
-            if node.id as usize == self.graphs[self.listener_id].root {
+            if node.id as usize == self.graphs[listener_id].root {
                 println!("Root node being removed, changing root.");
                 // Find another node to be root
-                if let Some((new_root_id, _)) = self.graphs[self.listener_id]
+                if let Some((new_root_id, _)) = self.graphs[listener_id]
                     .nodes
                     .iter()
                     .find(|(id, _)| **id != node.id as usize)
                 {
-                    self.graphs[self.listener_id].root = *new_root_id;
+                    self.graphs[listener_id].root = *new_root_id;
                 }
             }
-            self.graphs[self.listener_id].remove_node(node.id as usize);
+            self.graphs[listener_id].remove_node(node.id as usize);
         }
 
-        // Reverse weight changes (use text before "===")
+        // Reverse weight changes
         for edge in unsafe { &(*current_diff).weights_changed } {
             println!(
                 "Reversing weight change for edge from {} to {}",
                 edge.from_id, edge.to_id
             );
-            let c_edge = self.graphs[self.listener_id]
+            self.graphs[listener_id].set_weight(edge.from_id as usize, edge.to_id as usize, &edge.old);
+            let c_edge = self.graphs[listener_id]
                 .edges
                 .get_mut(&(edge.from_id as usize, edge.to_id as usize))
                 .unwrap();
-            let before_str_index = edge.weight.find("===").unwrap();
-            c_edge.weight_line(&edge.weight[..before_str_index]);
+            c_edge.weight_line(&edge.old);
         }
 
-        // Reverse label changes (use text before "===")
+        // Reverse label changes
         for node in unsafe { &(*current_diff).labels_changed } {
             println!("Reversing label change for node: {}", node.id);
-            let c_node = self.graphs[self.listener_id]
+            self.graphs[listener_id].set_label(node.id as usize, &node.old);
+            let c_node = self.graphs[listener_id]
                 .nodes
                 .get_mut(&(node.id as usize))
                 .unwrap();
-            let before_str_index = node.label.find("===").unwrap();
             c_node
                 .visual_node
                 .as_mut()
                 .unwrap()
-                .label_node(&node.label[..before_str_index]);
+                .label_node(&node.old);
         }
 
         // Reverse root change
-        let new_root_id = self.graphs[self.listener_id].root;
+        let new_root_id = self.graphs[listener_id].root;
         println!("new root id: {}", new_root_id);
 
         // Determine the previous root (this would be the root from the previous diff or initial state)
-        let previous_root_id = if self.diff_step == 0 {
+        let previous_root_id = if diff_step == 0 {
             // If we're back to the beginning, use the original root from base state
-            self.root.bases[self.listener_id].root_id as usize
+            self.root.bases[listener_id].root_id as usize
         } else {
             // Otherwise, use the root from the previous diff
-            self.root.diffs[self.listener_id][self.diff_step - 1].root_id as usize
+            self.root.diffs[listener_id][diff_step - 1].root_id as usize
         };
 
         println!("previous root id: {}", previous_root_id);
-        self.graphs[self.listener_id].root = previous_root_id;
+        self.graphs[listener_id].root = previous_root_id;
 
         // Update visual colors
         {
-            let old_root_node = self.graphs[self.listener_id]
+            let old_root_node = self.graphs[listener_id]
                 .nodes
                 .get_mut(&new_root_id)
                 .unwrap();
@@ -503,7 +722,7 @@ impl<'a> GraphDraw<'a> {
                 .as_mut()
                 .unwrap()
                 .color_node(NodeColor::Default);
-            let root_node = self.graphs[self.listener_id]
+            let root_node = self.graphs[listener_id]
                 .nodes
                 .get_mut(&previous_root_id)
                 .unwrap();
@@ -511,54 +730,752 @@ impl<'a> GraphDraw<'a> {
                 .visual_node
                 .as_mut()
                 .unwrap()
-                .color_node(NodeColor::Blue);
-        }
-        self.graphs[self.listener_id].step_build();
-        for node in unsafe { &(*current_diff).removed_nodes } {
-            self.graphs[self.listener_id]
-                .nodes
-                .get_mut(&(node.id as usize))
-                .unwrap()
-                .visual_node
-                .as_mut()
-                .unwrap()
-                .color_node(NodeColor::Red);
+                .color_node(NodeColor::Root);
         }
+        self.graphs[listener_id].step_build();
+        self.highlight_diff(listener_id, diff_step);
 
-        unsafe {
-            crate::STABLE_HAPPENED = false;
+        self.unsettle(listener_id);
+        self.apply_styles(listener_id);
+        if self.inspected.is_some_and(|(inspected, _)| inspected == listener_id) {
+            self.refresh_inspector();
         }
     }
 
     pub fn add_new_graph(&mut self, listener_id: usize) {
+        let new_graph = self.base_graph(listener_id);
+        self.graphs.push(new_graph);
+        self.picks.push(PickIndex::new(self.grid_spacing));
+        self.apply_styles(listener_id);
+    }
+
+    //the listener's base, built only when a tile shows it
+    fn base_graph(&self, listener_id: usize) -> Graph {
         let root_id = self.root.bases[listener_id].root_id as usize;
         let mut new_graph = Graph::new(
-            &self.root.nodes,
+            &self.root.bases[listener_id].node_labels(&self.root.nodes),
             &self.root.bases[listener_id].edges,
-            self.initial_position,
+            self.world_position(listener_id),
             root_id,
         );
-        if listener_id == 0 {
+        if self.tiles.iter().any(|tile| tile.listener_id == listener_id) {
             new_graph.build_base(&(self.root.bases[listener_id]));
             new_graph.bigbang_base(&(self.root.bases[listener_id].edges));
         }
-        self.graphs.push(new_graph);
+        new_graph.show_ghosts = self.ghost_mode != GhostMode::Hidden;
+        new_graph
+    }
+
+    //adds what a streamed trace sent since the last frame. listeners showing their
+    //newest step keep up with it, the others stay where they were scrubbed to
+    pub fn apply_events(&mut self, events: Vec<TraceEvent>) {
+        for event in events {
+            match event {
+                TraceEvent::Node { .. } => {
+                    self.apply_event(event);
+                }
+                TraceEvent::EdgeStyle { .. } => {
+                    self.apply_event(event);
+                    for listener_id in 0..self.graphs.len() {
+                        self.apply_styles(listener_id);
+                    }
+                }
+                TraceEvent::Base { listener_id, .. } => {
+                    if self.apply_event(event) {
+                        self.restart_listener(listener_id as usize);
+                    }
+                }
+                TraceEvent::Diff { listener_id, .. } => {
+                    let listener_id = listener_id as usize;
+                    if listener_id >= self.graphs.len() {
+                        println!("Diff for listener {} before its base, skipping it", listener_id + 1);
+                        continue;
+                    }
+                    let newest = self.graphs[listener_id].diff_step == self.root.diffs[listener_id].len();
+                    if self.apply_event(event) && newest {
+                        self.seek(listener_id, self.root.diffs[listener_id].len());
+                    }
+                }
+            }
+        }
+        self.timeline = global_timeline(&self.root);
+        self.global_step = if self.at_newest() {
+            self.timeline.len()
+        } else {
+            self.global_step.min(self.timeline.len())
+        };
+    }
+
+    //false when the event was turned down, the trace stays as it was
+    fn apply_event(&mut self, event: TraceEvent) -> bool {
+        match events::apply_event(self.root.to_mut(), event) {
+            Ok(()) => true,
+            Err(e) => {
+                println!("Skipping a trace event: {}", e);
+                false
+            }
+        }
+    }
+
+    //drops what a listener showed and starts it over from its base
+    fn restart_listener(&mut self, listener_id: usize) {
+        if listener_id >= self.graphs.len() {
+            for new_id in self.graphs.len()..=listener_id {
+                self.add_new_graph(new_id);
+            }
+            return;
+        }
+        if self.inspected.is_some_and(|(inspected, _)| inspected == listener_id) {
+            self.inspected = None;
+            self.refresh_inspector();
+        }
+        if self.dragged.is_some_and(|(dragged, _, _)| dragged == listener_id) {
+            self.dragged = None;
+        }
+        self.hide_tooltip();
+        self.graphs[listener_id].remove_ghosts();
+        self.graphs[listener_id].clean_graph();
+        self.graphs[listener_id] = self.base_graph(listener_id);
+        self.picks[listener_id] = PickIndex::new(self.grid_spacing);
+        self.apply_styles(listener_id);
+        self.unsettle(listener_id);
+    }
+
+    fn at_newest(&self) -> bool {
+        self.graphs
+            .iter()
+            .zip(&self.root.diffs)
+            .all(|(graph, diffs)| graph.diff_step == diffs.len())
+    }
+
+    //swaps in a reloaded trace. listeners keep their step and tiles their camera
+    //where the new trace still has them, the rest starts over
+    pub fn replace_root(&mut self, root: json_deserialize::Root) {
+        let steps: Vec<usize> = self.graphs.iter().map(|graph| graph.diff_step).collect();
+        self.hide_tooltip();
+        self.dragged = None;
+        for graph in self.graphs.iter_mut() {
+            graph.remove_ghosts();
+            graph.clean_graph();
+        }
+        self.graphs.clear();
+        self.picks.clear();
+        self.root = Cow::Owned(root);
+        self.timeline = global_timeline(&self.root);
+        let total_listeners = self.root.total_listeners as usize;
+
+        let old_tiles = std::mem::take(&mut self.tiles);
+        let focused_listener = old_tiles[self.focused].listener_id;
+        self.page = self.page.min(total_listeners.saturating_sub(1) / MAX_TILES);
+        let (listeners, viewports) = if self.split {
+            let listeners: Vec<usize> = page_listeners(self.page, total_listeners).collect();
+            let viewports = self.split_viewports(listeners.len());
+            (listeners, viewports)
+        } else if focused_listener < total_listeners {
+            (vec![focused_listener], vec![self.viewport])
+        } else {
+            (vec![0], vec![self.viewport])
+        };
+        for (listener_id, viewport) in listeners.into_iter().zip(viewports) {
+            let tile = match old_tiles.iter().find(|tile| tile.listener_id == listener_id) {
+                Some(old) => Tile {
+                    viewport,
+                    listener_id,
+                    camera: old.camera,
+                    zoom: old.zoom,
+                    stable: false,
+                },
+                None => self.new_tile(viewport, listener_id),
+            };
+            self.tiles.push(tile);
+        }
+        self.focused = self
+            .tiles
+            .iter()
+            .position(|tile| tile.listener_id == focused_listener)
+            .unwrap_or(0);
+
+        for listener_id in 0..total_listeners {
+            self.add_new_graph(listener_id);
+        }
+        for (listener_id, step) in steps.into_iter().enumerate().take(total_listeners) {
+            self.seek(listener_id, step);
+        }
+        self.global_step = self.global_step.min(self.timeline.len());
+        self.refresh_inspector();
+        self.place_notice();
+    }
+
+    //every listener to the last step that has arrived
+    pub fn follow_latest(&mut self) {
+        for listener_id in 0..self.graphs.len() {
+            self.seek(listener_id, self.root.diffs[listener_id].len());
+        }
+        self.global_step = self.timeline.len();
     }
 
     pub fn change_listener_id(&mut self, new_id: usize) {
         println!("number of graphs registered are {}", self.graphs.len());
-        if new_id > self.graphs.len() || new_id == 0 {
+        let listener_id = self.page * MAX_TILES + new_id.wrapping_sub(1);
+        if new_id == 0 || new_id > MAX_TILES || listener_id >= self.graphs.len() {
             println!("Listener id out of bounds");
             return;
         }
-        self.graphs[self.listener_id].clean_graph();
-        self.diff_step = 0;
-        self.listener_id = new_id - 1;
-        self.graphs[self.listener_id].build_base(&(self.root.bases[self.listener_id]));
-        self.graphs[self.listener_id].bigbang_base(&(self.root.bases[self.listener_id].edges));
+        if let Some(tile_index) = self
+            .tiles
+            .iter()
+            .position(|tile| tile.listener_id == listener_id)
+            && tile_index != self.focused
+        {
+            self.focused = tile_index;
+            self.place_notice();
+            return;
+        }
+        let old_listener = self.tiles[self.focused].listener_id;
+        self.graphs[old_listener].clean_graph();
+        self.build_listener(listener_id);
+
+        let viewport = self.tiles[self.focused].viewport;
+        self.tiles[self.focused] = self.new_tile(viewport, listener_id);
+        self.refresh_inspector();
+        self.place_notice();
+    }
 
-        unsafe {
-            crate::STABLE_HAPPENED = false;
+    //switches between a single view of the focused listener and a grid of all listeners
+    pub fn toggle_split(&mut self) {
+        let focused_listener = self.focused_listener();
+        if self.split {
+            for tile in &self.tiles {
+                if tile.listener_id != focused_listener {
+                    self.graphs[tile.listener_id].clean_graph();
+                }
+            }
+            self.tiles = vec![self.new_tile(self.viewport, focused_listener)];
+            self.focused = 0;
+        } else {
+            self.page = focused_listener / MAX_TILES;
+            self.tile_page();
+            self.focused = focused_listener % MAX_TILES;
+            self.announce_page();
+        }
+        self.split = !self.split;
+        self.refresh_inspector();
+        self.place_notice();
+    }
+
+    //the next MAX_TILES listeners, in the split grid and under the number keys
+    pub fn next_page(&mut self) {
+        let pages = self.graphs.len().div_ceil(MAX_TILES);
+        if pages <= 1 {
+            return;
+        }
+        self.page = (self.page + 1) % pages;
+        if self.split {
+            for tile in &self.tiles {
+                self.graphs[tile.listener_id].clean_graph();
+            }
+            self.tile_page();
+            self.focused = 0;
+            self.refresh_inspector();
+            self.place_notice();
+        }
+        self.announce_page();
+    }
+
+    //one tile per listener of the page, building the ones that are not drawn yet
+    fn tile_page(&mut self) {
+        let listeners: Vec<usize> = page_listeners(self.page, self.graphs.len()).collect();
+        let viewports = self.split_viewports(listeners.len());
+        let mut tiles = Vec::with_capacity(listeners.len());
+        for (listener_id, viewport) in listeners.into_iter().zip(viewports) {
+            if !self.graphs[listener_id].is_built() {
+                self.build_listener(listener_id);
+            }
+            tiles.push(self.new_tile(viewport, listener_id));
+        }
+        self.tiles = tiles;
+    }
+
+    //says which listeners are reachable when they do not all fit on one page
+    fn announce_page(&self) {
+        let total = self.graphs.len();
+        if total > MAX_TILES {
+            let listeners = page_listeners(self.page, total);
+            println!(
+                "Listeners {}-{} of {}, P shows the next ones",
+                listeners.start + 1,
+                listeners.end,
+                total
+            );
+        }
+    }
+
+    //zooms the tile under the cursor, the world point under the cursor stays put
+    pub fn zoom_at(&mut self, cursor: (f32, f32), factor: f32) {
+        let Some(tile) = self.tiles.iter_mut().find(|tile| tile.contains(cursor)) else {
+            return;
+        };
+        let anchor = tile.to_world(cursor);
+        tile.zoom = (tile.zoom * factor).clamp(MIN_ZOOM, MAX_ZOOM);
+        tile.camera = (
+            anchor.0 - (cursor.0 - tile.viewport.0.0 as f32) / tile.zoom,
+            anchor.1 - (cursor.1 - tile.viewport.0.1 as f32) / tile.zoom,
+        );
+        self.place_inspector();
+        self.place_notice();
+    }
+
+    pub fn focus_next(&mut self) {
+        self.focused = (self.focused + 1) % self.tiles.len();
+        self.place_notice();
+    }
+
+    //moves by screen pixels, so a zoomed in camera moves less of the world
+    pub fn pan_focused(&mut self, cam_horizontal: f32, cam_vertical: f32) {
+        let tile = &mut self.tiles[self.focused];
+        tile.camera.0 += cam_horizontal / tile.zoom;
+        tile.camera.1 += cam_vertical / tile.zoom;
+        self.place_inspector();
+        self.place_notice();
+    }
+
+    //window was resized, tiles keep their cameras and get the new area
+    pub fn resize_viewport(&mut self, top_left: (i32, i32), bottom_right: (i32, i32)) {
+        self.viewport = (top_left, bottom_right);
+        let viewports = if self.split {
+            self.split_viewports(self.tiles.len())
+        } else {
+            vec![self.viewport]
+        };
+        for (tile, viewport) in self.tiles.iter_mut().zip(viewports) {
+            tile.viewport = viewport;
+        }
+        self.place_inspector();
+        self.place_notice();
+    }
+
+    //near square grid of equally sized tiles over the whole viewport
+    fn split_viewports(&self, count: usize) -> Vec<((i32, i32), (i32, i32))> {
+        let columns = (count as f32).sqrt().ceil() as i32;
+        let rows = (count as i32 + columns - 1) / columns;
+        let (top_left, bottom_right) = self.viewport;
+        let tile_width = (bottom_right.0 - top_left.0) / columns;
+        let tile_height = (bottom_right.1 - top_left.1) / rows;
+
+        (0..count as i32)
+            .map(|index| {
+                let tile_top_left = (
+                    top_left.0 + (index % columns) * tile_width,
+                    top_left.1 + (index / columns) * tile_height,
+                );
+                (
+                    tile_top_left,
+                    (tile_top_left.0 + tile_width, tile_top_left.1 + tile_height),
+                )
+            })
+            .collect()
+    }
+
+    pub fn tiles(&self) -> &[Tile] {
+        &self.tiles
+    }
+
+    pub fn focused_tile(&self) -> &Tile {
+        &self.tiles[self.focused]
+    }
+
+    pub fn focused_index(&self) -> usize {
+        self.focused
+    }
+
+    pub fn focused_listener(&self) -> usize {
+        self.tiles[self.focused].listener_id
+    }
+
+    pub fn is_split(&self) -> bool {
+        self.split
+    }
+
+    //builds the base and replays the diffs up to the listener's step cursor
+    fn build_listener(&mut self, listener_id: usize) {
+        let target_step = self.graphs[listener_id].diff_step;
+        self.graphs[listener_id].diff_step = 0;
+        self.graphs[listener_id].build_base(&(self.root.bases[listener_id]));
+        self.graphs[listener_id].bigbang_base(&(self.root.bases[listener_id].edges));
+        self.apply_styles(listener_id);
+        self.seek(listener_id, target_step);
+    }
+
+    //moves a listener's step cursor, graphs that are not built only remember it
+    pub fn seek(&mut self, listener_id: usize, step: usize) {
+        let step = step.min(self.root.diffs[listener_id].len());
+        if !self.graphs[listener_id].is_built() {
+            self.graphs[listener_id].diff_step = step;
+            return;
+        }
+        while self.graphs[listener_id].diff_step < step {
+            self.forward_diff_of(listener_id);
+        }
+        while self.graphs[listener_id].diff_step > step {
+            self.backward_diff_of(listener_id);
+        }
+    }
+
+    //applies the next diff in program order, whichever listener it belongs to
+    pub fn global_forward(&mut self) {
+        self.sync_to_global();
+        if let Some(&(listener_id, diff_index)) = self.timeline.get(self.global_step) {
+            self.seek(listener_id, diff_index + 1);
+            self.global_step += 1;
+            println!(
+                "Global step {}/{} (listener {})",
+                self.global_step,
+                self.timeline.len(),
+                listener_id + 1
+            );
+        }
+    }
+
+    pub fn global_backward(&mut self) {
+        self.sync_to_global();
+        if self.global_step == 0 {
+            println!("Already at the beginning, cannot go backward");
+            return;
+        }
+        self.global_step -= 1;
+        let (listener_id, diff_index) = self.timeline[self.global_step];
+        self.seek(listener_id, diff_index);
+        println!(
+            "Global step {}/{} (listener {})",
+            self.global_step,
+            self.timeline.len(),
+            listener_id + 1
+        );
+    }
+
+    //local steps may have moved single listeners, bring every one of them back
+    //to the state the global cursor describes
+    fn sync_to_global(&mut self) {
+        let mut cursors = vec![0; self.graphs.len()];
+        for &(listener_id, diff_index) in &self.timeline[..self.global_step] {
+            cursors[listener_id] = diff_index + 1;
+        }
+        for (listener_id, step) in cursors.into_iter().enumerate() {
+            if self.graphs[listener_id].diff_step != step {
+                self.seek(listener_id, step);
+            }
+        }
+    }
+
+    //brings every node and edge to the trace's style hints for the current step,
+    //in id order so the handles they get do not depend on hash map order
+    fn apply_styles(&mut self, listener_id: usize) {
+        let graph = &mut self.graphs[listener_id];
+        if !graph.is_built() {
+            return;
+        }
+        let (node_styles, edge_styles) =
+            json_deserialize::styles_at(&self.root, listener_id, graph.diff_step);
+
+        let mut node_ids: Vec<usize> = graph.nodes.keys().copied().collect();
+        node_ids.sort();
+        for node_id in node_ids {
+            let style = node_styles.get(&(node_id as u32)).copied().unwrap_or_default();
+            if let Some(visual_node) = graph.nodes.get_mut(&node_id).unwrap().visual_node.as_mut() {
+                visual_node.set_style(style);
+            }
+        }
+
+        let mut edge_ids: Vec<(usize, usize)> = graph.edges.keys().copied().collect();
+        edge_ids.sort();
+        for edge_id in edge_ids {
+            let style = edge_styles
+                .get(&(edge_id.0 as u32, edge_id.1 as u32))
+                .copied()
+                .unwrap_or_default();
+            graph.edges.get_mut(&edge_id).unwrap().style_line(style);
+        }
+    }
+
+    //colors what a diff touched, the same way whichever direction it was crossed in.
+    //whatever the step made disappear is left as a ghost instead
+    fn highlight_diff(&mut self, listener_id: usize, diff_index: usize) {
+        let diff = &self.root.diffs[listener_id][diff_index];
+        let graph = &mut self.graphs[listener_id];
+        for node in &diff.added_nodes {
+            graph.highlight_node(node.id as usize, NodeColor::Added);
+        }
+        for node in &diff.removed_nodes {
+            graph.highlight_node(node.id as usize, NodeColor::Removed);
+        }
+        for node in &diff.labels_changed {
+            graph.highlight_node(node.id as usize, NodeColor::Changed);
+        }
+        for edge in &diff.added_edges {
+            graph.highlight_edge(edge.from_id as usize, edge.to_id as usize, NodeColor::AddedEdge);
+        }
+        for edge in &diff.removed_edges {
+            graph.highlight_edge(edge.from_id as usize, edge.to_id as usize, NodeColor::RemovedEdge);
+        }
+        for edge in &diff.weights_changed {
+            graph.highlight_edge(edge.from_id as usize, edge.to_id as usize, NodeColor::WeightChanged);
+        }
+    }
+
+    pub fn cycle_ghost_mode(&mut self) -> GhostMode {
+        self.ghost_mode = match self.ghost_mode {
+            GhostMode::Fade => GhostMode::Keep,
+            GhostMode::Keep => GhostMode::Hidden,
+            GhostMode::Hidden => GhostMode::Fade,
+        };
+        for graph in self.graphs.iter_mut() {
+            graph.show_ghosts = self.ghost_mode != GhostMode::Hidden;
+            if !graph.show_ghosts {
+                graph.remove_ghosts();
+            }
+        }
+        self.ghost_mode
+    }
+
+    pub fn cycle_edge_routing(&mut self) -> EdgeRouting {
+        self.edge_routing = self.edge_routing.next();
+        //edges are only routed while the layout moves
+        for tile in self.tiles.iter_mut() {
+            tile.stable = false;
+        }
+        self.edge_routing
+    }
+
+    //what is under a point of the window, the cursor goes through the camera of the tile it is over.
+    //nodes win over edges since edges end on their outlines
+    pub fn pick(&self, cursor: (f32, f32)) -> Option<(usize, Picked)> {
+        let tile = self.tiles.iter().find(|tile| tile.contains(cursor))?;
+        let world = tile.to_world(cursor);
+        let graph = self.graphs.get(tile.listener_id)?;
+        let picked = self.picks[tile.listener_id].at(world).into_iter().find(|picked| match picked {
+            Picked::Node(id) => graph
+                .nodes
+                .get(id)
+                .and_then(|node| node.visual_node.as_ref())
+                .is_some_and(|visual_node| visual_node.contains(world.0, world.1)),
+            Picked::Edge(from_id, to_id) => graph
+                .edges
+                .get(&(*from_id, *to_id))
+                .is_some_and(|line| line.distance_to(world.0, world.1) <= EDGE_REACH),
+        })?;
+        Some((tile.listener_id, picked))
+    }
+
+    pub fn node_at(&self, cursor: (f32, f32)) -> Option<(usize, usize)> {
+        match self.pick(cursor)? {
+            (listener_id, Picked::Node(id)) => Some((listener_id, id)),
+            _ => None,
+        }
+    }
+
+    //grabs the node under the cursor, false when there is none
+    pub fn start_drag(&mut self, cursor: (f32, f32)) -> bool {
+        let Some((listener_id, id)) = self.node_at(cursor) else {
+            return false;
+        };
+        let tile = self.tiles.iter().find(|tile| tile.contains(cursor)).unwrap();
+        let world = tile.to_world(cursor);
+        let center = self.graphs[listener_id].nodes[&id].center;
+        self.dragged = Some((listener_id, id, (center.0 - world.0, center.1 - world.1)));
+        true
+    }
+
+    //the grabbed node follows the cursor through the camera of its tile
+    pub fn drag(&mut self, cursor: (f32, f32)) {
+        let Some((listener_id, id, offset)) = self.dragged else {
+            return;
+        };
+        let Some(tile) = self.tiles.iter().find(|tile| tile.listener_id == listener_id) else {
+            self.dragged = None;
+            return;
+        };
+        let world = tile.to_world(cursor);
+        let Some(node) = self.graphs[listener_id].nodes.get_mut(&id) else {
+            self.dragged = None;
+            return;
+        };
+        let center = (world.0 + offset.0, world.1 + offset.1);
+        if center == node.center {
+            return;
+        }
+        node.center = center;
+        node.velocity = (0.0, 0.0);
+        if let Some(visual_node) = node.visual_node.as_ref() {
+            visual_node.move_node(center.0, center.1);
+        }
+        self.unsettle(listener_id);
+    }
+
+    //lets the layout take the node back from where it was dropped
+    pub fn end_drag(&mut self) {
+        if let Some((listener_id, _, _)) = self.dragged.take() {
+            self.unsettle(listener_id);
+        }
+    }
+
+    //shows the whole label of a node that could not fit it while the cursor is over it
+    pub fn hover(&mut self, cursor: (f32, f32)) {
+        let hovered = self.node_at(cursor).filter(|(listener_id, id)| {
+            self.graphs[*listener_id].nodes[id]
+                .visual_node
+                .as_ref()
+                .is_some_and(|visual_node| visual_node.is_truncated())
+        });
+        if hovered != self.hovered {
+            self.hide_tooltip();
+            if let Some((listener_id, id)) = hovered {
+                let visual_node = self.graphs[listener_id].nodes[&id].visual_node.as_ref().unwrap();
+                self.tooltip = Some(Tooltip::new(visual_node.label(), visual_node.top()));
+                self.hovered = hovered;
+            }
+            return;
+        }
+        //the layout may still be moving the node
+        if let (Some((listener_id, id)), Some(tooltip)) = (self.hovered, self.tooltip.as_ref()) {
+            let visual_node = self.graphs[listener_id]
+                .nodes
+                .get(&id)
+                .and_then(|node| node.visual_node.as_ref());
+            if let Some(visual_node) = visual_node {
+                tooltip.move_to(visual_node.top());
+            }
+        }
+    }
+
+    //a click on a node opens the inspector for it, a click anywhere else closes it
+    pub fn inspect(&mut self, cursor: (f32, f32)) {
+        self.inspected = self.node_at(cursor);
+        self.refresh_inspector();
+    }
+
+    //the panel is rebuilt whenever what it shows may have changed
+    fn refresh_inspector(&mut self) {
+        if let Some(panel) = self.inspector.take() {
+            panel.remove();
+        }
+        let Some((listener_id, id)) = self.inspected else {
+            return;
+        };
+        let shown = self.tiles.iter().any(|tile| tile.listener_id == listener_id);
+        if !shown || !self.graphs[listener_id].nodes.contains_key(&id) {
+            self.inspected = None;
+            return;
+        }
+        let details = inspector::inspect(&self.root, &self.graphs[listener_id], listener_id, id);
+        self.inspector = Some(Panel::new(&details.lines(), self.inspector_corner(listener_id)));
+    }
+
+    //top right of the tile showing the listener, in world space
+    fn inspector_corner(&self, listener_id: usize) -> Point {
+        let tile = self
+            .tiles
+            .iter()
+            .find(|tile| tile.listener_id == listener_id)
+            .unwrap();
+        let (width, _) = tile.dimensions();
+        Point::new(
+            tile.camera.0 + (width - PANEL_MARGIN) / tile.zoom,
+            tile.camera.1 + PANEL_MARGIN / tile.zoom,
+        )
+    }
+
+    //keeps the panel in its corner while the camera moves
+    fn place_inspector(&self) {
+        if let (Some((listener_id, _)), Some(panel)) = (self.inspected, self.inspector.as_ref()) {
+            panel.move_to(self.inspector_corner(listener_id));
+        }
+    }
+
+    //a message over the focused tile until it is cleared, like why a trace did not reload
+    pub fn show_notice(&mut self, lines: Vec<String>) {
+        self.notice_lines = lines;
+        self.refresh_notice();
+    }
+
+    pub fn clear_notice(&mut self) {
+        self.show_notice(Vec::new());
+    }
+
+    fn refresh_notice(&mut self) {
+        if let Some(panel) = self.notice.take() {
+            panel.remove();
+        }
+        if self.notice_lines.is_empty() {
+            return;
+        }
+        self.notice = Some(Panel::new(&self.notice_lines, Point::new(0.0, 0.0)));
+        self.place_notice();
+    }
+
+    //top left of the focused tile, the inspector has the top right
+    fn place_notice(&self) {
+        if let Some(panel) = self.notice.as_ref() {
+            let tile = &self.tiles[self.focused];
+            let (half_width, _) = panel.half_size();
+            panel.move_to(Point::new(
+                tile.camera.0 + PANEL_MARGIN / tile.zoom + 2.0 * half_width,
+                tile.camera.1 + PANEL_MARGIN / tile.zoom,
+            ));
+        }
+    }
+
+    fn hide_tooltip(&mut self) {
+        if let Some(tooltip) = self.tooltip.take() {
+            tooltip.remove();
+        }
+        self.hovered = None;
+    }
+
+    //after a theme switch every drawn node and edge takes the new colors
+    pub fn refresh_colors(&mut self) {
+        render::with_backend(|backend| backend.color_text(theme::current().text));
+        self.hide_tooltip();
+        self.refresh_inspector();
+        self.refresh_notice();
+        for graph in self.graphs.iter_mut() {
+            for node in graph.nodes.values_mut() {
+                if let Some(visual_node) = node.visual_node.as_mut() {
+                    visual_node.recolor();
+                }
+            }
+            for line in graph.edges.values_mut() {
+                line.recolor();
+            }
+        }
+    }
+
+    //tiles showing this listener have to run the layout again
+    fn unsettle(&mut self, listener_id: usize) {
+        for tile in self.tiles.iter_mut() {
+            if tile.listener_id == listener_id {
+                tile.stable = false;
+            }
+        }
+    }
+
+    fn world_position(&self, listener_id: usize) -> (i32, i32) {
+        (
+            self.initial_position.0 + listener_id as i32 * WORLD_SEPARATION,
+            self.initial_position.1,
+        )
+    }
+
+    //centers the listener's root inside the tile
+    fn new_tile(&self, viewport: ((i32, i32), (i32, i32)), listener_id: usize) -> Tile {
+        let center = self.world_position(listener_id);
+        let width = (viewport.1.0 - viewport.0.0) as f32;
+        let height = (viewport.1.1 - viewport.0.1) as f32;
+        Tile {
+            viewport,
+            listener_id,
+            camera: (center.0 as f32 - width / 2.0, center.1 as f32 - height / 2.0),
+            zoom: 1.0,
+            stable: false,
         }
     }
 }
@@ -577,6 +1494,9 @@ impl<'a> GraphDrawBuilder<'a> {
             mass_constant: None,
             resting_length: None,
             damping_constant: None,
+            seed: None,
+            ghost_mode: None,
+            edge_routing: None,
         }
     }
 
@@ -596,7 +1516,13 @@ impl<'a> GraphDrawBuilder<'a> {
     }
 
     pub fn root(mut self, root: &'a json_deserialize::Root) -> Self {
-        self.root = Some(root);
+        self.root = Some(Cow::Borrowed(root));
+        self
+    }
+
+    //for a trace that is still being streamed in
+    pub fn owned_root(mut self, root: json_deserialize::Root) -> Self {
+        self.root = Some(Cow::Owned(root));
         self
     }
 
@@ -635,16 +1561,47 @@ impl<'a> GraphDrawBuilder<'a> {
         self
     }
 
+    //fixed seed makes placement of new nodes reproducible
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+
+    pub fn ghost_mode(mut self, mode: GhostMode) -> Self {
+        self.ghost_mode = Some(mode);
+        self
+    }
+
+    pub fn edge_routing(mut self, routing: EdgeRouting) -> Self {
+        self.edge_routing = Some(routing);
+        self
+    }
+
     pub fn build(self) -> Result<GraphDraw<'a>, &'static str> {
         let root = self.root.ok_or("Root is required")?;
+        let timeline = global_timeline(&root);
 
         let mut new_graph_draw = GraphDraw {
             viewport: self.viewport.unwrap_or(((0, 0), (800, 600))),
             grid_spacing: self.grid_spacing.unwrap_or(GRID_SPACE),
-            grid: HashGrid::default(),
+            picks: Vec::new(),
             root,
             initial_algorithm: self.initial_algorithm.unwrap_or(Algorithm::BigBang),
-            listener_id: self.listener_id.unwrap_or(0),
+            tiles: Vec::new(),
+            focused: 0,
+            split: false,
+            page: 0,
+            timeline,
+            global_step: 0,
+            ghost_mode: self.ghost_mode.unwrap_or(GhostMode::Fade),
+            edge_routing: self.edge_routing.unwrap_or(EdgeRouting::Straight),
+            hovered: None,
+            tooltip: None,
+            inspected: None,
+            inspector: None,
+            notice: None,
+            notice_lines: Vec::new(),
+            dragged: None,
             initial_position: self.initial_position.unwrap_or({
                 let vp = self.viewport.unwrap_or(((0, 0), (800, 600)));
                 let tl = vp.0;
@@ -656,21 +1613,77 @@ impl<'a> GraphDrawBuilder<'a> {
             mass_constant: self.mass_constant.unwrap_or(NODE_MASS),
             damping_constant: self.damping_constant.unwrap_or(DAMPING_CONSTANT),
             resting_length: self.resting_length.unwrap_or(RESTING_LENGTH),
-            diff_step: 0,
+            rng: match self.seed {
+                Some(seed) => StdRng::seed_from_u64(seed),
+                None => StdRng::from_os_rng(),
+            },
             graphs: Vec::new(),
         };
-
-        let viewport = (
-            (
-                new_graph_draw.viewport.0.0 as f32,
-                new_graph_draw.viewport.0.1 as f32,
-            ),
-            (
-                new_graph_draw.viewport.1.0 as f32,
-                new_graph_draw.viewport.1.1 as f32,
-            ),
-        );
-        new_graph_draw.grid = HashGrid::new(viewport, new_graph_draw.grid_spacing);
+        render::with_backend(|backend| backend.color_text(theme::current().text));
+        let first_tile =
+            new_graph_draw.new_tile(new_graph_draw.viewport, self.listener_id.unwrap_or(0));
+        new_graph_draw.tiles.push(first_tile);
         Ok(new_graph_draw)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::render::RecordingBackend;
+    use crate::json_deserialize::Style;
+
+    #[test]
+    fn listeners_past_the_first_page_can_be_paged_to() {
+        let previous = render::set_backend(Box::new(RecordingBackend::new()));
+        let mut root = json_deserialize::Root::new(12);
+        root.register_node(0, "a".to_string(), Style::default());
+        for base in root.bases.iter_mut() {
+            base.edges.insert(0, HashMap::new());
+        }
+        let mut graph_draw = GraphDrawBuilder::new()
+            .viewport((0, 0), (1920, 1080))
+            .root(&root)
+            .build()
+            .expect("Error building graph drawer");
+        for listener_id in 0..12 {
+            graph_draw.add_new_graph(listener_id);
+        }
+        let shown = |graph_draw: &GraphDraw| -> Vec<usize> {
+            graph_draw.tiles().iter().map(|tile| tile.listener_id).collect()
+        };
+
+        graph_draw.toggle_split();
+        assert_eq!(shown(&graph_draw), (0..9).collect::<Vec<_>>());
+        graph_draw.next_page();
+        assert_eq!(shown(&graph_draw), vec![9, 10, 11]);
+        graph_draw.change_listener_id(2);
+        assert_eq!(graph_draw.focused_listener(), 10);
+        graph_draw.toggle_split();
+        assert_eq!(shown(&graph_draw), vec![10]);
+        graph_draw.toggle_split();
+        assert_eq!(shown(&graph_draw), vec![9, 10, 11]);
+        assert_eq!(graph_draw.focused_listener(), 10);
+        graph_draw.next_page();
+        assert_eq!(shown(&graph_draw), (0..9).collect::<Vec<_>>());
+        render::set_backend(previous);
+    }
+
+    #[test]
+    fn text_takes_the_color_of_the_theme() {
+        let backend = RecordingBackend::new();
+        let recording = backend.recording();
+        let previous = render::set_backend(Box::new(backend));
+        let root = json_deserialize::Root::new(1);
+        let mut graph_draw = GraphDrawBuilder::new()
+            .root(&root)
+            .build()
+            .expect("Error building graph drawer");
+        assert_eq!(recording.borrow().text_color, theme::DARK.text);
+        theme::set_theme("light");
+        graph_draw.refresh_colors();
+        assert_eq!(recording.borrow().text_color, theme::LIGHT.text);
+        theme::set_theme("dark");
+        render::set_backend(previous);
+    }
+}
@@ -1,75 +1,71 @@
-use std::collections::{hash_set, HashSet};
+use std::collections::{HashMap, HashSet};
 use std::hash::Hash;
-use std::thread::current;
-use rand::seq::index;
 use std::ops::Index;
+use std::time::Instant;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
 
-use crate::{CAMERA_SHIFT, DIMENSIONS};
+type Rectangle = ((f32, f32), (f32, f32)); //center, dimensions
+type Cell = (i32, i32); //column and row, cell (0, 0) starts at the world origin
 
-macro_rules! point_in_screen_space {
-    ($self:expr, $point:expr) => {
-        $point.0 <= $self.screen_space.1.0 
-            && $point.0 >= $self.screen_space.0.0
-            && $point.1 <= $self.screen_space.1.1 
-            && $point.1 >= $self.screen_space.0.1
-    };
-}
-
-const FIXED_ARRAY_SIZE: usize = 3;
+const INLINE_ITEMS: usize = 3;//most cells hold a few items, only crowded ones spill to the heap
 #[derive(Debug)]
-struct Array3e<T>{
-  pub data: [T; FIXED_ARRAY_SIZE],
+struct CellItems<T>{
+  pub data: [T; INLINE_ITEMS],
   pub size: usize,
+  overflow: Vec<T>,
 }
 
-impl<T: Default + Copy + PartialEq> Array3e<T>{
+impl<T: Default + Copy + PartialEq> CellItems<T>{
   pub fn new() -> Self {
-    Array3e {
-      data: [T::default(); FIXED_ARRAY_SIZE],
+    CellItems {
+      data: [T::default(); INLINE_ITEMS],
       size: 0,
+      overflow: Vec::new(),
     }
   }
-  pub fn insert(&mut self, element: T) -> Result<(), ()>{
-    if self.size == FIXED_ARRAY_SIZE {
-      return Err(())
-    } 
-    self.data[self.size] = element;
+
+  pub fn insert(&mut self, element: T) {
+    if self.size < INLINE_ITEMS {
+      self.data[self.size] = element;
+    }
+    else {
+      self.overflow.push(element);
+    }
     self.size += 1;
-    Ok(())
   }
 
   pub fn remove(&mut self, index: usize) -> Result<(), ()>{
     if index >= self.size{
       return Err(())
     }
-    if (self.size - 1) == index {
-      self.size -= 1;
+    //the last item fills the hole, order within a cell does not matter
+    let last = self[self.size - 1];
+    if index < INLINE_ITEMS {
+      self.data[index] = last;
     }
     else {
-      for i in index..(self.size - 1) {
-        self.data[i] = self.data[i+1];
-      }
-      self.size -= 1;
+      self.overflow[index - INLINE_ITEMS] = last;
     }
-    Ok(())
+    self.remove_end()
   }
   
   pub fn remove_element(&mut self, element: &T) -> Result<(), ()> {
-    for i in 0..self.size{
-      if &self.data[i] == element{
-        return self.remove(i);
-      }
+    let index = self.iter().position(|item| item == element);
+    match index {
+      Some(index) => self.remove(index),
+      None => Err(()),
     }
-    Err(())
   }
 
   pub fn remove_end(&mut self) -> Result<(),()>{
     if self.size == 0{
       return Err(())
     }
-    else{
-      self.size -= 1;
+    if self.size > INLINE_ITEMS {
+      self.overflow.pop();
     }
+    self.size -= 1;
     Ok(())
   }
 
@@ -77,173 +73,84 @@ impl<T: Default + Copy + PartialEq> Array3e<T>{
     self.size == 0
   }
 
+  pub fn iter(&self) -> impl Iterator<Item = &T> {
+    self.data[..self.size.min(INLINE_ITEMS)].iter().chain(self.overflow.iter())
+  }
 }
 
-impl<T> Index<usize> for Array3e<T>{
+impl<T> Index<usize> for CellItems<T>{
   type Output = T;
 
   fn index(&self, index: usize) -> &Self::Output {
     if index >= self.size{
       panic!("Index out of bounds");
     }
-    &self.data[index]
+    if index < INLINE_ITEMS {
+      &self.data[index]
+    }
+    else {
+      &self.overflow[index - INLINE_ITEMS]
+    }
   }
 }
 
 
+//world space split into ds sized cells, only cells holding something are kept so it needs no bounds
 #[derive(Default)]
 pub struct HashGrid<T>{
-  grid: Vec<Vec<Array3e<T>>>,
-  data: HashSet<T>,
-  screen_space: ((f32,f32), (f32,f32)), //top left, bottom right
+  cells: HashMap<Cell, CellItems<T>>,
+  data: HashMap<T, Rectangle>, //where every item was put, by item
+  bounds: Option<(Cell, Cell)>, //first and last cell of the block holding every cell in use, queries do not look past them
   ds: u16,
 }
 
-
-//remove debug
 impl<T: Eq + Default + Copy + Hash + std::fmt::Debug> HashGrid<T>{
-  pub fn new(screen_space: ((f32,f32),(f32,f32)), ds: u16) -> Self{
-    let ((tx, ty), (bx, by)) = screen_space;
-    let width = bx - tx;
-    let height = by - ty;
-    // integer-like behavior: number of cells = floor(width/ds) + 1
-    let num_columns = (width / ds as f32).floor() as usize + 1;
-    let num_rows = (height / ds as f32).floor() as usize + 1;
-
-    if (width / ds as f32).fract() != 0.0 {
-      println!("width is not direct multiple of ds\n");
-    }
-    if (height / ds as f32).fract() != 0.0{
-      println!("height is not direct multiple of ds\n");
-    }
-
-    let mut new_hash_grid = HashGrid{
-      grid: Vec::with_capacity(num_rows),
-      data: HashSet::new(),
-      screen_space,
-      ds,
-    };
-    new_hash_grid.grid.resize_with(num_rows, Vec::new);
-
-    for vec in new_hash_grid.grid.iter_mut() {
-      vec.resize_with(num_columns, Array3e::new);
-    }
-
-    new_hash_grid
-  }
-
-  pub fn get_element(&mut self, coord: (f32, f32)) -> Option<&T>{
-    let(x_index, y_index) = self.coord_to_index(coord);
-
-    if x_index >= self.grid.len() || y_index >= self.grid[x_index].len(){
-      return None;
-    }
-
-    if self.grid[x_index][y_index].is_empty() {
-      None
-    } else {
-      Some(&(self.grid[x_index][y_index][0]))
+  pub fn new(ds: u16) -> Self{
+    HashGrid{
+      cells: HashMap::new(),
+      data: HashMap::new(),
+      bounds: None,
+      ds: ds.max(1),
     }
-
   }
 
-  pub fn insert_element(&mut self, coord: (f32, f32), item: &T) {
-    let(x_index, y_index) = self.coord_to_index(coord);
-
-    self.grid[x_index][y_index].insert(item.clone());
+  //the item when the cell at coord holds it
+  #[cfg(test)]
+  pub fn get_element(&self, coord: (f32, f32), item: &T) -> Option<&T>{
+    self.cells.get(&self.cell_of(coord))?.iter().find(|existing| *existing == item)
   }
 
-  pub fn insert_rectangle(&mut self, coord: (f32, f32), dimensions: (f32,f32), item: &T) -> Result<(), HashSet<T>>{
-    let top_left = (coord.0 - (dimensions.0 / 2.0), coord.1 - (dimensions.1 / 2.0));
-    let bottom_right = (top_left.0 + dimensions.0, top_left.1 + dimensions.1);
-    let top_right = (bottom_right.0, top_left.1);
-    let bottom_left = (top_left.0, bottom_right.1);
-    
-    // Check if all corners are within screen_space
-    self.grid_dimensions(top_left, bottom_right);
-
-    if self.data.contains(item){
+  pub fn insert_rectangle(&mut self, coord: (f32, f32), dimensions: (f32,f32), item: &T) {
+    if self.data.contains_key(item){
       panic!("All keys must be unique");
     }
-    else{
-      self.data.insert(item.clone());
-    }
-    
-    // let mut collisions = HashSet::new();
-    // while current_point.0 <= bottom_right.0 {
-    //   while current_point.1 <= bottom_right.1 {
-    //     let (x_index, y_index) = self.coord_to_index(current_point);
-    //     for existing in &self.grid[x_index][y_index] {
-    //       collisions.insert(existing.clone());
-    //     }
-
-    //     current_point.1 += self.ds as f32;
-    //   }
-    //   current_point.1 = top_left.1;
-    //   current_point.0 += self.ds as f32;
-    // }
-    
-    // // Return all collisions if any found
-    // if !collisions.is_empty() {
-    //   return Err(collisions);
-    // }
-    
-    // Second pass: insert if no collisions
-    let mut current_point = top_left;
-    while current_point.0 <= bottom_right.0 {
-      while current_point.1 <= bottom_right.1 {
-        self.insert_element(current_point, item);
-        current_point.1 += self.ds as f32;
+    self.data.insert(*item, (coord, dimensions));
+    //by cell, stepping by ds from the corner missed the last cell of a rectangle narrower than ds
+    let (first, last) = self.cell_span(coord, dimensions);
+    for x_index in first.0..=last.0 {
+      for y_index in first.1..=last.1 {
+        self.cell_mut((x_index, y_index)).insert(*item);
       }
-      current_point.1 = top_left.1;
-      current_point.0 += self.ds as f32;
     }
-    
-    Ok(())
   }
 
-  pub fn move_rectangle(&mut self, from_coord: (f32, f32),dimensions:(f32,f32),to_coord:(f32,f32))->Result<(),HashSet<T>> {
-    let top_left_moved = (to_coord.0 - dimensions.0 /2.0, to_coord.1 - dimensions.1 / 2.0);
-    let bottom_right_moved = (to_coord.0 + dimensions.0 /2.0, to_coord.1 + dimensions.1 / 2.0);
-
-    if !point_in_screen_space!(self, top_left_moved) && !point_in_screen_space!(self, bottom_right_moved){
-      panic!("Moved rectangle not in grid space");
-    }
-
-    let (x_index, y_index) = self.coord_to_index(from_coord);
-    let value = &self.grid[x_index][y_index];
-
-    if value.is_empty() {
-      panic!("You cannot move something that doesn't exist!");
-    }
-    // choose the first item in the cell to be the key to move
-    let key: T = value[0].clone();
-      
-    
-    let top_left_from = (from_coord.0 - dimensions.0 / 2.0, from_coord.1 - dimensions.1 / 2.0);
-    let bottom_right_from = (from_coord.0 + dimensions.0 / 2.0, from_coord.1 + dimensions.1 / 2.0);
-
-    // indices for source rectangle (not used directly here)
-    let _ft = self.coord_to_index(top_left_from);
-    let _fb = self.coord_to_index(bottom_right_from);
-
-    let mt = self.coord_to_index(top_left_moved);
-    let mb = self.coord_to_index(bottom_right_moved);
+  #[cfg(test)]
+  pub fn move_rectangle(&mut self, key: &T, from_coord: (f32, f32),dimensions:(f32,f32),to_coord:(f32,f32))->Result<(),HashSet<T>> {
+    //a cell holds any number of items, so the one to move is looked for rather than taken first
+    let key = match self.get_element(from_coord, key) {
+      Some(key) => *key,
+      None => panic!("You cannot move something that doesn't exist!"),
+    };
 
     //check if any collision occurs
+    let (first, last) = self.cell_span(to_coord, dimensions);
     let mut collided_keys = HashSet::new();
-    let mut current = mt;
-    while current.0 <= mb.0{
-      while current.1 <= mb.1{
-        for data in &self.grid[current.0][current.1].data {
-          if (*data) != key{
-            collided_keys.insert(data.clone());
-          }
+    for x_index in first.0..=last.0 {
+      for y_index in first.1..=last.1 {
+        if let Some(cell) = self.cells.get(&(x_index, y_index)) {
+          collided_keys.extend(cell.iter().filter(|data| **data != key));
         }
-        current.1 += 1;
       }
-      current.0 += 1;
-      current.1 = mt.1;
     }
 
     if !collided_keys.is_empty(){
@@ -251,148 +158,344 @@ impl<T: Eq + Default + Copy + Hash + std::fmt::Debug> HashGrid<T>{
     }
 
     let _ = self.remove_rectangle(from_coord, dimensions, &key);
-    
-
-    println!("{:#?}, {:#?}", mt, mb);
-    println!("{:#?}", self);
-    let mut current = mt;
-    while current.0 <= mb.0{
-      while current.1 <= mb.1{
-        self.grid[current.0][current.1].insert(key.clone());
-        current.1 += 1;
+    self.data.insert(key, (to_coord, dimensions));
+    for x_index in first.0..=last.0 {
+      for y_index in first.1..=last.1 {
+        self.cell_mut((x_index, y_index)).insert(key);
       }
-      current.0 += 1;
-      current.1 = mt.1;
     }
 
-
     Ok(())
-    // TODO: Complete the rest of the function
   }
 
 
   pub fn remove_rectangle(&mut self, coord: (f32, f32), dimensions: (f32, f32), element: &T) -> Result<(), ()> {
-    //remove these extra checks. You cannot click on sth not existing
-    let top_left = (coord.0 - (dimensions.0 / 2.0), coord.1 - (dimensions.1 / 2.0));
-    let bottom_right = (top_left.0 + dimensions.0, top_left.1 + dimensions.1);
-    let top_right = (bottom_right.0, top_left.1);
-    let bottom_left = (top_left.0, bottom_right.1);
-    
-    // Check if all corners are within screen_space
-    if !point_in_screen_space!(self, top_left) || 
-       !point_in_screen_space!(self, top_right) || 
-       !point_in_screen_space!(self, bottom_left) || 
-       !point_in_screen_space!(self, bottom_right) {
+    if !self.data.contains_key(element) {
       return Err(());
     }
-
-    let top_left_indices = self.coord_to_index(top_left);
-    let bottom_right_indices = self.coord_to_index(bottom_right);
-
-    // let keys_to_remove = self.grid[top_left_indices.0][top_left_indices.1].clone();
-    // if !keys_to_remove.is_empty() {
-    //   // remove all items found in the top-left cell from the master set
-    //   for d in keys_to_remove.iter() {
-    //     self.data.remove(d);
-    //   }
-    // }
-    // else{
-    //   panic!("You cannot remove something that does not exist!");
-    // }
-    
-    // Iterate through rectangle and remove matching items from each cell
-    let mut current_indices = top_left_indices;
-    while current_indices.0 <= bottom_right_indices.0 {
-      while current_indices.1 <= bottom_right_indices.1 {
-        // remove all items that were present in the top-left cell
-        self.grid[current_indices.0][current_indices.1].remove_element(element);
-        current_indices.1 += 1 as usize;
+    let (first, last) = self.cell_span(coord, dimensions);
+    let mut edge_emptied = false;
+    for x_index in first.0..=last.0 {
+      for y_index in first.1..=last.1 {
+        let Some(cell) = self.cells.get_mut(&(x_index, y_index)) else {
+          continue;
+        };
+        let _ = cell.remove_element(element);
+        //empty cells are dropped so the grid stays as small as what it holds
+        if cell.is_empty() {
+          self.cells.remove(&(x_index, y_index));
+          edge_emptied |= self.on_edge((x_index, y_index));
+        }
       }
-      current_indices.1 = top_left_indices.1;
-      current_indices.0 += 1 as usize;
+    }
+    //an item that went far off would otherwise keep nearest searching rings out to where it was
+    if edge_emptied {
+      self.bounds = self.cells.keys().fold(None, |bounds, &cell| Some(grown(bounds, cell)));
     }
     
+    //the key can be inserted again
+    self.data.remove(element);
     Ok(())
   }
 
-  #[inline(always)]
-  fn coord_to_index(&self, coord: (f32, f32))->(usize, usize){
-    let dx = coord.0 - self.screen_space.0.0;
-    let dy = coord.1 - self.screen_space.0.1;
+  //everything in the cells a rectangle covers
+  pub fn query_rectangle(&self, coord: (f32, f32), dimensions: (f32, f32)) -> HashSet<T> {
+    let mut found = HashSet::new();
+    let Some((low, high)) = self.bounds else {
+      return found;
+    };
+    let (first, last) = self.cell_span(coord, dimensions);
+    let first = (first.0.max(low.0), first.1.max(low.1));
+    let last = (last.0.min(high.0), last.1.min(high.1));
+    if first.0 > last.0 || first.1 > last.1 {
+      return found;
+    }
+    //a rectangle over more cells than exist is cheaper to answer from the cells that do
+    let covered = (last.0 - first.0 + 1) as u64 * (last.1 - first.1 + 1) as u64;
+    if covered > self.cells.len() as u64 {
+      for (&(x_index, y_index), cell) in &self.cells {
+        if x_index >= first.0 && x_index <= last.0 && y_index >= first.1 && y_index <= last.1 {
+          found.extend(cell.iter());
+        }
+      }
+      return found;
+    }
+    for x_index in first.0..=last.0 {
+      for y_index in first.1..=last.1 {
+        if let Some(cell) = self.cells.get(&(x_index, y_index)) {
+          found.extend(cell.iter());
+        }
+      }
+    }
+    found
+  }
+
+  #[cfg(test)]
+  pub fn len(&self) -> usize {
+    self.data.len()
+  }
+
+  pub fn items(&self) -> impl Iterator<Item = &T> {
+    self.data.keys()
+  }
 
-    if dx < 0.0 || dy < 0.0{
-      panic!("Error: Negative indices!");
+  //center and dimensions an item was put with
+  pub fn rectangle(&self, item: &T) -> Option<Rectangle> {
+    self.data.get(item).copied()
+  }
+
+  //takes an item out wherever it was put, false when it is not in the grid
+  pub fn remove(&mut self, item: &T) -> bool {
+    match self.rectangle(item) {
+      Some((coord, dimensions)) => self.remove_rectangle(coord, dimensions, item).is_ok(),
+      None => false,
     }
+  }
 
-    let x = (dx / self.ds as f32).floor() as usize;
-    let y = (dy / self.ds as f32).floor() as usize;
+  //items whose own rectangle overlaps the one asked for, not just a cell of it
+  pub fn query_range(&self, coord: (f32, f32), dimensions: (f32, f32)) -> HashSet<T> {
+    self.query_rectangle(coord, dimensions)
+      .into_iter()
+      .filter(|item| overlaps(self.data[item], (coord, dimensions)))
+      .collect()
+  }
 
-    (x, y)
-    
+  //items with any part of their rectangle within radius of a point
+  #[cfg(test)]
+  pub fn query_radius(&self, point: (f32, f32), radius: f32) -> HashSet<T> {
+    self.query_rectangle(point, (2.0 * radius, 2.0 * radius))
+      .into_iter()
+      .filter(|item| distance_to_rectangle(point, self.data[item]) <= radius)
+      .collect()
   }
 
+  //the k items closest to a point and how far they are, nearest first.
+  //rings of cells are searched outwards until no unsearched cell can be closer than the kth item
+  #[cfg(test)]
+  pub fn nearest(&self, point: (f32, f32), k: usize) -> Vec<(T, f32)> {
+    let mut found: HashMap<T, f32> = HashMap::new();
+    let Some((low, high)) = self.bounds else {
+      return Vec::new();
+    };
+    if k == 0 {
+      return Vec::new();
+    }
+    let ds = self.ds as f32;
+    let cell = self.cell_of(point);
+    let center = (cell.0.clamp(low.0, high.0) as i64, cell.1.clamp(low.1, high.1) as i64);
+    let (low, high) = ((low.0 as i64, low.1 as i64), (high.0 as i64, high.1 as i64));
+    let mut kth = f32::MAX;
+    for ring in 0..=(high.0 - low.0).max(high.1 - low.1) {
+      //top and bottom rows of the ring whole, the columns between them only at both ends, all cut to the bounds
+      let x_range = (center.0 - ring).max(low.0)..=(center.0 + ring).min(high.0);
+      let y_range = (center.1 - ring + 1).max(low.1)..=(center.1 + ring - 1).min(high.1);
+      let mut ring_cells: Vec<(i64, i64)> = Vec::new();
+      for y_index in [center.1 - ring, center.1 + ring] {
+        if y_index >= low.1 && y_index <= high.1 && (ring > 0 || y_index == center.1 - ring) {
+          ring_cells.extend(x_range.clone().map(|x_index| (x_index, y_index)));
+        }
+      }
+      for x_index in [center.0 - ring, center.0 + ring] {
+        if x_index >= low.0 && x_index <= high.0 && (ring > 0 || x_index == center.0 - ring) {
+          ring_cells.extend(y_range.clone().map(|y_index| (x_index, y_index)));
+        }
+      }
+      let before = found.len();
+      for (x_index, y_index) in ring_cells {
+        let Some(cell) = self.cells.get(&(x_index as i32, y_index as i32)) else {
+          continue;
+        };
+        for item in cell.iter() {
+          found.entry(*item).or_insert_with(|| distance_to_rectangle(point, self.data[item]));
+        }
+      }
+      if found.len() < k {
+        continue;
+      }
+      //anything not found yet is past a side of the searched block that has not reached the bounds
+      let mut reach = f32::MAX;
+      if center.0 - ring > low.0 {
+        reach = reach.min(point.0 - (center.0 - ring) as f32 * ds);
+      }
+      if center.0 + ring < high.0 {
+        reach = reach.min((center.0 + ring + 1) as f32 * ds - point.0);
+      }
+      if center.1 - ring > low.1 {
+        reach = reach.min(point.1 - (center.1 - ring) as f32 * ds);
+      }
+      if center.1 + ring < high.1 {
+        reach = reach.min((center.1 + ring + 1) as f32 * ds - point.1);
+      }
+      if found.len() != before {
+        let mut distances: Vec<f32> = found.values().copied().collect();
+        kth = *distances.select_nth_unstable_by(k - 1, f32::total_cmp).1;
+      }
+      if kth <= reach {
+        break;
+      }
+    }
+    let mut nearest: Vec<(T, f32)> = found.into_iter().collect();
+    nearest.sort_by(|a, b| a.1.total_cmp(&b.1));
+    nearest.truncate(k);
+    nearest
+  }
 
-  pub fn grid_dimensions(&mut self,top_left: (f32, f32) ,bottom_right: (f32, f32)){
-    let new_screen_space = ((top_left.0.min(self.screen_space.0.0), top_left.1.min(self.screen_space.0.1)), 
-                            (bottom_right.0.max(self.screen_space.1.0), bottom_right.1.max(self.screen_space.1.1)));
-    
-    let left_diff = if self.screen_space.0.0 > new_screen_space.0.0 {
-      ((self.screen_space.0.0 - new_screen_space.0.0) / self.ds as f32).floor() as usize
-    } else { 0 };
-    let top_diff = if self.screen_space.0.1 > new_screen_space.0.1 {
-      ((self.screen_space.0.1 - new_screen_space.0.1) / self.ds as f32).floor() as usize
-    } else { 0 };
-    let width_increase = left_diff + if new_screen_space.1.0 > self.screen_space.1.0 {
-      ((new_screen_space.1.0 - self.screen_space.1.0) / self.ds as f32).floor() as usize
-    } else { 0 };
-    let height_increase = top_diff + if new_screen_space.1.1 > self.screen_space.1.1 {
-      ((new_screen_space.1.1 - self.screen_space.1.1) / self.ds as f32).floor() as usize
-    } else { 0 };
-
-    if width_increase == 0 && height_increase == 0 {
-      println!("nothing");
-      return;
-    }
-
-    self.screen_space = new_screen_space;
-    self.grid.resize_with(self.grid.len() + width_increase, Vec::new );
-    if left_diff != 0 {
-      self.grid.rotate_right(left_diff);
-    }
-
-    let new_height = ((new_screen_space.1.1 - new_screen_space.0.1) / (self.ds as f32).floor()) as usize + 1;
-    if top_diff != 0 {
-      for vec in self.grid.iter_mut() {
-        vec.resize_with(new_height as usize,|| Array3e::new() );
-        vec.rotate_right(top_diff);
+  //items a segment passes through and how far along it each is first touched, closest first.
+  //only the cells the segment crosses are looked at
+    pub fn query_segment(&self, from: (f32, f32), to: (f32, f32)) -> Vec<(T, f32)> {
+    let Some((low, high)) = self.bounds else {
+      return Vec::new();
+    };
+    let ds = self.ds as f32;
+    let origin = (low.0 as f32 * ds, low.1 as f32 * ds);
+    let far = ((high.0 + 1) as f32 * ds, (high.1 + 1) as f32 * ds);
+    let used = (((origin.0 + far.0) / 2.0, (origin.1 + far.1) / 2.0), (far.0 - origin.0, far.1 - origin.1));
+    let Some(enter) = segment_entry(from, to, used) else {
+      return Vec::new();
+    };
+    let (low, high) = ((low.0 as i64, low.1 as i64), (high.0 as i64, high.1 as i64));
+    let direction = (to.0 - from.0, to.1 - from.1);
+    let start = (from.0 + direction.0 * enter, from.1 + direction.1 * enter);
+    let mut cell = (
+      ((start.0 / ds).floor() as i64).clamp(low.0, high.0),
+      ((start.1 / ds).floor() as i64).clamp(low.1, high.1),
+    );
+    //amanatides and woo, next is the fraction of the segment where the next cell border is crossed
+    let axis = |start: f32, along: f32, index: i64| -> (i64, f32, f32) {
+      if along > 0.0 {
+        (1, ((index + 1) as f32 * ds - start) / along, ds / along)
+      } else if along < 0.0 {
+        (-1, (index as f32 * ds - start) / along, ds / -along)
+      } else {
+        (0, f32::MAX, f32::MAX)
+      }
+    };
+    let (step_x, mut next_x, delta_x) = axis(from.0, direction.0, cell.0);
+    let (step_y, mut next_y, delta_y) = axis(from.1, direction.1, cell.1);
+
+    let mut found: HashMap<T, f32> = HashMap::new();
+    let length = (direction.0 * direction.0 + direction.1 * direction.1).sqrt();
+    loop {
+      let items = self.cells.get(&(cell.0 as i32, cell.1 as i32)).into_iter().flat_map(|cell| cell.iter());
+      for item in items {
+        if found.contains_key(item) {
+          continue;
+        }
+        if let Some(t) = segment_entry(from, to, self.data[item]) {
+          found.insert(*item, t * length);
+        }
+      }
+      if next_x.min(next_y) > 1.0 {
+        break;
+      }
+      if next_x < next_y {
+        cell.0 += step_x;
+        next_x += delta_x;
+      } else {
+        cell.1 += step_y;
+        next_y += delta_y;
+      }
+      if cell.0 < low.0 || cell.1 < low.1 || cell.0 > high.0 || cell.1 > high.1 {
+        break;
       }
     }
-    else{
-      for vec in self.grid.iter_mut() {
-        vec.resize_with( new_height as usize,|| Array3e::new() );
+    let mut hits: Vec<(T, f32)> = found.into_iter().collect();
+    hits.sort_by(|a, b| a.1.total_cmp(&b.1));
+    hits
+  }
+
+  //first item a ray from origin hits within max_distance, and how far it is
+  #[cfg(test)]
+  pub fn raycast(&self, origin: (f32, f32), direction: (f32, f32), max_distance: f32) -> Option<(T, f32)> {
+    let length = (direction.0 * direction.0 + direction.1 * direction.1).sqrt();
+    if length == 0.0 {
+      return None;
+    }
+    let end = (
+      origin.0 + direction.0 / length * max_distance,
+      origin.1 + direction.1 / length * max_distance,
+    );
+    self.query_segment(origin, end).into_iter().next()
+  }
+
+  #[inline(always)]
+  fn cell_of(&self, coord: (f32, f32)) -> Cell {
+    ((coord.0 / self.ds as f32).floor() as i32, (coord.1 / self.ds as f32).floor() as i32)
+  }
+
+  //first and last cell a rectangle covers
+  fn cell_span(&self, coord: (f32, f32), dimensions: (f32, f32)) -> (Cell, Cell) {
+    (
+      self.cell_of((coord.0 - dimensions.0 / 2.0, coord.1 - dimensions.1 / 2.0)),
+      self.cell_of((coord.0 + dimensions.0 / 2.0, coord.1 + dimensions.1 / 2.0)),
+    )
+  }
+
+  //makes the cell when it is the first thing there
+  fn cell_mut(&mut self, cell: Cell) -> &mut CellItems<T> {
+    self.bounds = Some(grown(self.bounds, cell));
+    self.cells.entry(cell).or_insert_with(CellItems::new)
+  }
+
+  fn on_edge(&self, cell: Cell) -> bool {
+    self.bounds.is_some_and(|(low, high)| cell.0 == low.0 || cell.1 == low.1 || cell.0 == high.0 || cell.1 == high.1)
+  }
+}
+
+fn grown(bounds: Option<(Cell, Cell)>, cell: Cell) -> (Cell, Cell) {
+  match bounds {
+    Some((low, high)) => ((low.0.min(cell.0), low.1.min(cell.1)), (high.0.max(cell.0), high.1.max(cell.1))),
+    None => (cell, cell),
+  }
+}
+
+fn overlaps(a: Rectangle, b: Rectangle) -> bool {
+  (a.0.0 - b.0.0).abs() * 2.0 <= a.1.0 + b.1.0 && (a.0.1 - b.0.1).abs() * 2.0 <= a.1.1 + b.1.1
+}
+
+//0 inside the rectangle
+#[cfg(test)]
+fn distance_to_rectangle(point: (f32, f32), rectangle: Rectangle) -> f32 {
+  let ((x, y), (width, height)) = rectangle;
+  let dx = ((point.0 - x).abs() - width / 2.0).max(0.0);
+  let dy = ((point.1 - y).abs() - height / 2.0).max(0.0);
+  (dx * dx + dy * dy).sqrt()
+}
+
+//fraction of the segment where it enters the rectangle, 0 when it starts inside
+fn segment_entry(from: (f32, f32), to: (f32, f32), rectangle: Rectangle) -> Option<f32> {
+  let ((x, y), (width, height)) = rectangle;
+  let mut enter: f32 = 0.0;
+  let mut leave: f32 = 1.0;
+  for (start, along, low, high) in [
+    (from.0, to.0 - from.0, x - width / 2.0, x + width / 2.0),
+    (from.1, to.1 - from.1, y - height / 2.0, y + height / 2.0),
+  ] {
+    if along == 0.0 {
+      if start < low || start > high {
+        return None;
       }
+      continue;
     }
+    let (a, b) = ((low - start) / along, (high - start) / along);
+    enter = enter.max(a.min(b));
+    leave = leave.min(a.max(b));
   }
+  (enter <= leave).then_some(enter)
 }
 
 impl<T: std::fmt::Debug> std::fmt::Debug for HashGrid<T> {
   fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
     writeln!(f, "HashGrid {{")?;
-    writeln!(f, "  screen_space: {:?},", self.screen_space)?;
+    writeln!(f, "  bounds: {:?},", self.bounds)?;
     writeln!(f, "  ds: {},", self.ds)?;
-    writeln!(f, "  grid: [")?;
+    writeln!(f, "  cells: [")?;
     
-    for (row_idx, row) in self.grid.iter().enumerate() {
-      write!(f, "    [{:2}] ", row_idx)?;
-      for cell in row {
-        if cell.size == 0 {
-          write!(f, "   ·")?;
-        } else {
-          // show a short preview: number of items in cell
-          write!(f, "{:>4}", format!("#{}", cell.size))?;
-        }
-      }
-      writeln!(f)?;
+    // show a short preview: number of items in each cell
+    let mut cells: Vec<(&Cell, usize)> = self.cells.iter().map(|(cell, items)| (cell, items.size)).collect();
+    cells.sort();
+    for (cell, size) in cells {
+      writeln!(f, "    {:?} #{}", cell, size)?;
     }
     
     writeln!(f, "  ]")?;
@@ -400,3 +503,330 @@ impl<T: std::fmt::Debug> std::fmt::Debug for HashGrid<T> {
   }
 }
 
+
+/* ---- benchmark against the grid this one replaced ---- */
+
+const BENCH_SEED: u64 = 0x9e1d;
+const BENCH_QUERIES: usize = 2000;
+//the old grid only covered the screen it was made for
+const SCREEN: (f32, f32) = (1920.0, 1080.0);
+
+//what the grid was before: a dense array over the screen, at most INLINE_ITEMS items a cell and
+//whatever did not fit was lost. only kept to time the new grid against
+struct BaselineGrid {
+  cells: Vec<Vec<([u32; INLINE_ITEMS], usize)>>,
+  ds: f32,
+  dropped: usize,
+}
+
+impl BaselineGrid {
+  fn new(ds: u16) -> Self {
+    let ds = ds as f32;
+    let columns = (SCREEN.0 / ds).floor() as usize + 1;
+    let rows = (SCREEN.1 / ds).floor() as usize + 1;
+    BaselineGrid { cells: vec![vec![([0; INLINE_ITEMS], 0); rows]; columns], ds, dropped: 0 }
+  }
+
+  //first and last cell a rectangle covers, cut to the screen
+  fn span(&self, coord: (f32, f32), dimensions: (f32, f32)) -> ((usize, usize), (usize, usize)) {
+    let index = |value: f32, count: usize| ((value / self.ds).floor().max(0.0) as usize).min(count - 1);
+    let (columns, rows) = (self.cells.len(), self.cells[0].len());
+    (
+      (index(coord.0 - dimensions.0 / 2.0, columns), index(coord.1 - dimensions.1 / 2.0, rows)),
+      (index(coord.0 + dimensions.0 / 2.0, columns), index(coord.1 + dimensions.1 / 2.0, rows)),
+    )
+  }
+
+  fn insert(&mut self, coord: (f32, f32), dimensions: (f32, f32), item: u32) {
+    let (first, last) = self.span(coord, dimensions);
+    for x_index in first.0..=last.0 {
+      for y_index in first.1..=last.1 {
+        let (items, size) = &mut self.cells[x_index][y_index];
+        if *size == INLINE_ITEMS {
+          self.dropped += 1;
+          continue;
+        }
+        items[*size] = item;
+        *size += 1;
+      }
+    }
+  }
+
+  fn remove(&mut self, coord: (f32, f32), dimensions: (f32, f32), item: u32) {
+    let (first, last) = self.span(coord, dimensions);
+    for x_index in first.0..=last.0 {
+      for y_index in first.1..=last.1 {
+        let (items, size) = &mut self.cells[x_index][y_index];
+        if let Some(index) = items[..*size].iter().position(|existing| *existing == item) {
+          items.copy_within(index + 1..*size, index);
+          *size -= 1;
+        }
+      }
+    }
+  }
+
+  fn query(&self, coord: (f32, f32), dimensions: (f32, f32)) -> HashSet<u32> {
+    let (first, last) = self.span(coord, dimensions);
+    let mut found = HashSet::new();
+    for column in &self.cells[first.0..=last.0] {
+      for (items, size) in &column[first.1..=last.1] {
+        found.extend(&items[..*size]);
+      }
+    }
+    found
+  }
+}
+
+//items scattered in a few tight clusters, so plenty of cells hold more than INLINE_ITEMS
+fn random_items(rng: &mut StdRng, count: usize, space: (f32, f32)) -> HashMap<u32, Rectangle> {
+  let clusters: Vec<(f32, f32)> = (0..8).map(|_| (rng.random_range(0.0..space.0), rng.random_range(0.0..space.1))).collect();
+  (0..count as u32)
+    .map(|item| {
+      let cluster = clusters[item as usize % clusters.len()];
+      let spread = space.0.min(space.1) / 20.0;
+      let center = (
+        cluster.0 + rng.random_range(-spread..spread),
+        cluster.1 + rng.random_range(-spread..spread),
+      );
+      (item, (center, (rng.random_range(2.0..40.0), rng.random_range(2.0..40.0))))
+    })
+    .collect()
+}
+
+//--hashgrid-bench [items], times the grid against the old one on the screen the old one covered
+pub fn run_bench_command(args: &[String]) -> i32 {
+  let count = match args.first().map(|arg| arg.parse::<usize>()) {
+    None => 2000,
+    Some(Ok(count)) => count,
+    Some(Err(_)) => {
+      println!("usage: --hashgrid-bench [items]");
+      return 2;
+    }
+  };
+  let mut rng = StdRng::seed_from_u64(BENCH_SEED);
+  let items = random_items(&mut rng, count, SCREEN);
+  let mut keys: Vec<u32> = items.keys().copied().collect();
+  keys.sort();
+  let moves: Vec<(u32, (f32, f32))> = keys.iter()
+    .filter(|item| *item % 2 == 0)
+    .map(|item| {
+      let (center, _) = items[item];
+      let to = (center.0 + rng.random_range(-100.0..100.0), center.1 + rng.random_range(-100.0..100.0));
+      (*item, (to.0.clamp(0.0, SCREEN.0), to.1.clamp(0.0, SCREEN.1)))
+    })
+    .collect();
+  let queries: Vec<(f32, f32)> = (0..BENCH_QUERIES)
+    .map(|_| (rng.random_range(0.0..SCREEN.0), rng.random_range(0.0..SCREEN.1)))
+    .collect();
+  let time = |f: &mut dyn FnMut()| {
+    let started = Instant::now();
+    f();
+    started.elapsed()
+  };
+
+  let mut grid: HashGrid<u32> = HashGrid::new(40);
+  let mut moved = items.clone();
+  let grid_insert = time(&mut || for item in &keys {
+    let (center, dimensions) = items[item];
+    grid.insert_rectangle(center, dimensions, item);
+  });
+  let grid_move = time(&mut || for (item, to) in &moves {
+    let (from, dimensions) = moved[item];
+    grid.remove_rectangle(from, dimensions, item).unwrap();
+    grid.insert_rectangle(*to, dimensions, item);
+    moved.insert(*item, (*to, dimensions));
+  });
+  let mut grid_found = 0;
+  let grid_query = time(&mut || for point in &queries {
+    grid_found += grid.query_rectangle(*point, (60.0, 60.0)).len();
+  });
+  let grid_remove = time(&mut || for item in &keys {
+    let (center, dimensions) = moved[item];
+    grid.remove_rectangle(center, dimensions, item).unwrap();
+  });
+
+  let mut baseline = BaselineGrid::new(40);
+  let mut moved = items.clone();
+  let baseline_insert = time(&mut || for item in &keys {
+    let (center, dimensions) = items[item];
+    baseline.insert(center, dimensions, *item);
+  });
+  let baseline_move = time(&mut || for (item, to) in &moves {
+    let (from, dimensions) = moved[item];
+    baseline.remove(from, dimensions, *item);
+    baseline.insert(*to, dimensions, *item);
+    moved.insert(*item, (*to, dimensions));
+  });
+  let mut baseline_found = 0;
+  let baseline_query = time(&mut || for point in &queries {
+    baseline_found += baseline.query(*point, (60.0, 60.0)).len();
+  });
+  let baseline_remove = time(&mut || for item in &keys {
+    let (center, dimensions) = moved[item];
+    baseline.remove(center, dimensions, *item);
+  });
+
+  println!("{} items, the old grid lost {} cell entries to full cells", count, baseline.dropped);
+  println!("           grid / old grid");
+  println!("  insert   {:?} / {:?}", grid_insert, baseline_insert);
+  println!("  move     {:?} / {:?}", grid_move, baseline_move);
+  println!("  query    {:?} / {:?}, {} / {} items found", grid_query, baseline_query, grid_found, baseline_found);
+  println!("  remove   {:?} / {:?}", grid_remove, baseline_remove);
+  0
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  const SEED: u64 = 0x9e1d;
+  const QUERIES: usize = 1000;
+  const SPACE: f32 = 2000.0;
+
+  fn brute_range(items: &HashMap<u32, Rectangle>, coord: (f32, f32), dimensions: (f32, f32)) -> HashSet<u32> {
+    items.iter().filter(|(_, rectangle)| overlaps(**rectangle, (coord, dimensions))).map(|(item, _)| *item).collect()
+  }
+
+  fn brute_radius(items: &HashMap<u32, Rectangle>, point: (f32, f32), radius: f32) -> HashSet<u32> {
+    items.iter().filter(|(_, rectangle)| distance_to_rectangle(point, **rectangle) <= radius).map(|(item, _)| *item).collect()
+  }
+
+  fn brute_nearest(items: &HashMap<u32, Rectangle>, point: (f32, f32), k: usize) -> Vec<f32> {
+    let mut distances: Vec<f32> = items.values().map(|rectangle| distance_to_rectangle(point, *rectangle)).collect();
+    distances.sort_by(f32::total_cmp);
+    distances.truncate(k);
+    distances
+  }
+
+  fn brute_segment(items: &HashMap<u32, Rectangle>, from: (f32, f32), to: (f32, f32)) -> HashSet<u32> {
+    items.iter().filter(|(_, rectangle)| segment_entry(from, to, **rectangle).is_some()).map(|(item, _)| *item).collect()
+  }
+
+  //clustered items with every eighth one far off on the negative side, like nodes a layout pushed
+  //out, then half of them moved and a quarter removed so the queries have to keep up
+  fn scattered(rng: &mut StdRng) -> (HashGrid<u32>, HashMap<u32, Rectangle>) {
+    let mut items = random_items(rng, 2000, (SPACE, SPACE));
+    for (_, (center, _)) in items.iter_mut().filter(|(item, _)| *item % 8 == 7) {
+      *center = (center.0 - 31.0 * SPACE, center.1 + 44.0 * SPACE);
+    }
+    let mut grid: HashGrid<u32> = HashGrid::new(40);
+    let mut keys: Vec<u32> = items.keys().copied().collect();
+    keys.sort();
+    for item in &keys {
+      let (center, dimensions) = items[item];
+      grid.insert_rectangle(center, dimensions, item);
+    }
+    assert_eq!(grid.len(), items.len());
+    assert!(grid.cells.values().any(|cell| cell.size > INLINE_ITEMS));
+
+    for item in keys.iter().filter(|item| *item % 2 == 0) {
+      let (center, dimensions) = items[item];
+      let to = (center.0 + rng.random_range(-100.0..100.0), center.1 + rng.random_range(-100.0..100.0));
+      assert!(grid.remove(item));
+      grid.insert_rectangle(to, dimensions, item);
+      items.insert(*item, (to, dimensions));
+    }
+    for item in keys.iter().filter(|item| *item % 4 == 1) {
+      assert!(grid.remove(item));
+      items.remove(item);
+    }
+    (grid, items)
+  }
+
+  //pairs of points, every tenth pair where the far items are
+  fn query_points(rng: &mut StdRng) -> Vec<((f32, f32), (f32, f32))> {
+    (0..QUERIES)
+      .map(|index| {
+        let shift = if index % 10 == 0 { (-31.0 * SPACE, 44.0 * SPACE) } else { (0.0, 0.0) };
+        let mut point = || (shift.0 + rng.random_range(-100.0..SPACE + 100.0), shift.1 + rng.random_range(-100.0..SPACE + 100.0));
+        (point(), point())
+      })
+      .collect()
+  }
+
+  #[test]
+  fn range_and_radius_queries_match_a_scan() {
+    let mut rng = StdRng::seed_from_u64(SEED);
+    let (grid, items) = scattered(&mut rng);
+    for (point, other) in query_points(&mut rng) {
+      let dimensions = ((other.0 - point.0).abs() / 10.0, (other.1 - point.1).abs() / 10.0);
+      assert_eq!(grid.query_range(point, dimensions), brute_range(&items, point, dimensions), "range at {:?} {:?}", point, dimensions);
+      assert_eq!(grid.query_radius(point, dimensions.0), brute_radius(&items, point, dimensions.0), "radius at {:?} {}", point, dimensions.0);
+    }
+  }
+
+  #[test]
+  fn nearest_matches_a_scan() {
+    let mut rng = StdRng::seed_from_u64(SEED);
+    let (grid, items) = scattered(&mut rng);
+    for (index, (point, _)) in query_points(&mut rng).into_iter().enumerate() {
+      let k = index % 7 + 1;
+      let nearest: Vec<f32> = grid.nearest(point, k).into_iter().map(|(_, distance)| distance).collect();
+      assert_eq!(nearest, brute_nearest(&items, point, k), "{} nearest to {:?}", k, point);
+    }
+    assert!(HashGrid::<u32>::new(40).nearest((0.0, 0.0), 3).is_empty());
+  }
+
+  #[test]
+  fn segments_match_a_scan_in_order() {
+    let mut rng = StdRng::seed_from_u64(SEED);
+    let (grid, items) = scattered(&mut rng);
+    for (point, other) in query_points(&mut rng) {
+      let hits = grid.query_segment(point, other);
+      assert_eq!(hits.iter().map(|(item, _)| *item).collect::<HashSet<u32>>(), brute_segment(&items, point, other), "segment {:?} -> {:?}", point, other);
+      assert!(hits.windows(2).all(|pair| pair[0].1 <= pair[1].1));
+
+      //the ray is normalized first, so its distances can be off in the last digits
+      let length = ((other.0 - point.0).powi(2) + (other.1 - point.1).powi(2)).sqrt();
+      match (grid.raycast(point, (other.0 - point.0, other.1 - point.1), length), hits.first()) {
+        (Some((_, a)), Some((_, b))) => assert!((a - b).abs() <= 1e-3 * b.max(1.0)),
+        (first, hit) => assert!(first.is_none() && hit.is_none(), "ray {:?} -> {:?} hits {:?}", point, other, first),
+      }
+    }
+  }
+
+  #[test]
+  fn moving_takes_the_item_asked_for() {
+    let mut grid: HashGrid<u32> = HashGrid::new(10);
+    for item in 1..=4 {
+      grid.insert_rectangle((5.0, 5.0), (2.0, 2.0), &item);
+    }
+    grid.move_rectangle(&3, (5.0, 5.0), (2.0, 2.0), (55.0, 55.0)).unwrap();
+    assert_eq!(grid.get_element((5.0, 5.0), &3), None);
+    assert_eq!(grid.get_element((55.0, 55.0), &3), Some(&3));
+    assert_eq!(grid.rectangle(&3), Some(((55.0, 55.0), (2.0, 2.0))));
+    assert_eq!(grid.query_range((5.0, 5.0), (2.0, 2.0)), HashSet::from([1, 2, 4]));
+
+    //landing on another item is refused and leaves it where it was
+    assert_eq!(grid.move_rectangle(&4, (5.0, 5.0), (2.0, 2.0), (55.0, 55.0)), Err(HashSet::from([3])));
+    assert_eq!(grid.get_element((5.0, 5.0), &4), Some(&4));
+    assert_eq!(grid.len(), 4);
+  }
+
+  #[test]
+  fn bounds_shrink_back_when_far_items_leave() {
+    let mut grid: HashGrid<u32> = HashGrid::new(10);
+    grid.insert_rectangle((5.0, 5.0), (2.0, 2.0), &1);
+    grid.insert_rectangle((25.0, 5.0), (2.0, 2.0), &2);
+    grid.insert_rectangle((-90_000.0, 70_000.0), (2.0, 2.0), &3);
+    assert!(grid.remove(&3));
+    assert_eq!(grid.bounds, Some(((0, 0), (2, 0))));
+
+    //moving off and back leaves the bounds where the items are
+    grid.move_rectangle(&2, (25.0, 5.0), (2.0, 2.0), (5.0, 90_000.0)).unwrap();
+    grid.move_rectangle(&2, (5.0, 90_000.0), (2.0, 2.0), (15.0, 5.0)).unwrap();
+    assert_eq!(grid.bounds, Some(((0, 0), (1, 0))));
+    assert_eq!(grid.nearest((0.0, 0.0), 2).into_iter().map(|(item, _)| item).collect::<Vec<u32>>(), vec![1, 2]);
+
+    assert!(grid.remove(&1) && grid.remove(&2));
+    assert_eq!(grid.bounds, None);
+  }
+
+  #[test]
+  #[should_panic(expected = "doesn't exist")]
+  fn moving_an_item_that_is_not_there_panics() {
+    let mut grid: HashGrid<u32> = HashGrid::new(10);
+    grid.insert_rectangle((5.0, 5.0), (2.0, 2.0), &1);
+    let _ = grid.move_rectangle(&2, (5.0, 5.0), (2.0, 2.0), (55.0, 55.0));
+  }
+}
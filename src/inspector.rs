@@ -0,0 +1,110 @@
+use crate::dsas::Graph;
+use crate::json_deserialize::Root;
+
+//everything the side panel shows about one node of a listener
+#[derive(Debug)]
+pub struct NodeDetails {
+  pub id: usize,
+  pub label: String,
+  pub in_degree: usize,
+  pub out_degree: usize,
+  pub neighbors: Vec<Neighbor>,
+  pub added_at: Option<usize>, //step whose diff added the node, None when it is in the base
+  pub label_history: Vec<(usize, String)>, //step and the label it got, the base label has step 0
+  pub is_root: bool,
+  pub step: usize,
+}
+
+#[derive(Debug)]
+pub struct Neighbor {
+  pub id: usize,
+  pub outgoing: bool,
+  pub weight: String,
+}
+
+pub fn inspect(root: &Root, graph: &Graph, listener_id: usize, id: usize) -> NodeDetails {
+  let mut neighbors: Vec<Neighbor> = graph.edges.iter()
+    .filter(|((from_id, to_id), _)| *from_id == id || *to_id == id)
+    .flat_map(|(&(from_id, to_id), line)| {
+      //a self-loop is both
+      let mut ends = Vec::new();
+      if from_id == id {
+        ends.push(Neighbor { id: to_id, outgoing: true, weight: line.weight().to_string() });
+      }
+      if to_id == id {
+        ends.push(Neighbor { id: from_id, outgoing: false, weight: line.weight().to_string() });
+      }
+      ends
+    })
+    .collect();
+  neighbors.sort_by_key(|neighbor| (!neighbor.outgoing, neighbor.id));
+  let out_degree = neighbors.iter().filter(|neighbor| neighbor.outgoing).count();
+
+  let diffs = &root.diffs[listener_id];
+  let added_at = diffs.iter()
+    .take(graph.diff_step)
+    .rposition(|diff| diff.added_nodes.iter().any(|node| node.id as usize == id))
+    .map(|index| index + 1);
+  let base = &root.bases[listener_id];
+  let base_label = base.labels.get(&(id as u32)).or(root.nodes.get(id)).cloned().unwrap_or_default();
+  let mut label_history = vec![(0, base_label)];
+  for (index, diff) in diffs.iter().enumerate() {
+    //a node added again can come back with another label
+    for node in diff.added_nodes.iter().filter(|node| node.id as usize == id) {
+      if label_history.last().is_some_and(|(_, label)| *label != node.label) {
+        label_history.push((index + 1, node.label.clone()));
+      }
+    }
+    for change in diff.labels_changed.iter().filter(|change| change.id as usize == id) {
+      label_history.push((index + 1, change.new.clone()));
+    }
+  }
+
+  let label = graph.nodes.get(&id)
+    .and_then(|node| node.visual_node.as_ref())
+    .map(|visual_node| visual_node.label().to_string())
+    .unwrap_or_else(|| label_history[0].1.clone());
+  NodeDetails {
+    id,
+    label,
+    in_degree: neighbors.len() - out_degree,
+    out_degree,
+    neighbors,
+    added_at,
+    label_history,
+    is_root: graph.root == id,
+    step: graph.diff_step,
+  }
+}
+
+impl NodeDetails {
+  pub fn lines(&self) -> Vec<String> {
+    let mut lines = vec![
+      format!("node {}{}", self.id, if self.is_root { " (root)" } else { "" }),
+      format!("label: {}", self.label),
+      format!("in {} / out {}", self.in_degree, self.out_degree),
+      match self.added_at {
+        Some(step) => format!("added at step {}", step),
+        None => String::from("in the base"),
+      },
+    ];
+    if !self.neighbors.is_empty() {
+      lines.push(String::from("neighbors:"));
+      for neighbor in &self.neighbors {
+        let arrow = if neighbor.outgoing { "->" } else { "<-" };
+        let weight = if neighbor.weight.is_empty() { String::new() } else { format!(" [{}]", neighbor.weight) };
+        lines.push(format!("  {} {}{}", arrow, neighbor.id, weight));
+      }
+    }
+    if self.label_history.len() > 1 {
+      lines.push(String::from("labels:"));
+      for (step, label) in &self.label_history {
+        //changes the cursor has not reached yet
+        let ahead = if *step > self.step { " (ahead)" } else { "" };
+        let step = if *step == 0 { String::from("base") } else { format!("step {}", step) };
+        lines.push(format!("  {}: {}{}", step, label, ahead));
+      }
+    }
+    lines
+  }
+}
@@ -1,39 +1,147 @@
 use serde::{Deserialize, Serialize};
-use serde_json::Value;
+use serde_json::{Map, Value};
 use std::error::Error;
 use std::fs::File;
 use std::io::BufReader;
 use std::path::Path;
 use std::collections::HashMap;
+use trace::diff::{LabelChange, WeightChange};
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Root{
   pub bases: Vec<BaseInfo>,
   pub diffs: Vec<Vec<DiffInfo>>,
   pub nodes: Vec<String>,//node id is the index
+  pub node_styles: Vec<Style>,//node id is the index, same for every listener
+  pub edge_styles: HashMap<(u32, u32), Style>,
   pub total_listeners: u8,
+  pub timeline: TimelineCount,//kept up to date by push_diff and clear_diffs
+}
+
+impl Root{
+  pub fn new(total_listeners: u8) -> Self{
+    let mut bases = Vec::with_capacity(total_listeners as usize);
+    let mut diffs: Vec<Vec<DiffInfo>> = Vec::with_capacity(total_listeners as usize);
+
+    bases.resize(total_listeners as usize, BaseInfo::default());
+    diffs.resize_with(total_listeners as usize, Vec::new);
+
+    Root{
+      bases,
+      diffs,
+      nodes: Vec::new(),
+      node_styles: Vec::new(),
+      edge_styles: HashMap::new(),
+      total_listeners,
+      timeline: TimelineCount::default(),
+    }
+  }
+
+  pub fn push_diff(&mut self, listener_id: usize, diff: DiffInfo){
+    self.timeline.add(&diff);
+    self.diffs[listener_id].push(diff);
+  }
+
+  pub fn clear_diffs(&mut self, listener_id: usize){
+    for diff in &self.diffs[listener_id] {
+      self.timeline.remove(diff);
+    }
+    self.diffs[listener_id].clear();
+  }
+
+  //listeners a stream mentions for the first time get an empty base
+  pub fn ensure_listener(&mut self, listener_id: u8){
+    if listener_id < self.total_listeners {
+      return;
+    }
+    self.total_listeners = listener_id + 1;
+    self.bases.resize(self.total_listeners as usize, BaseInfo::default());
+    self.diffs.resize_with(self.total_listeners as usize, Vec::new);
+  }
+
+  //a node registered again keeps its id and takes the new label
+  pub fn register_node(&mut self, id: u32, label: String, style: Style){
+    let index = id as usize;
+    if index >= self.nodes.len() {
+      self.nodes.resize(index + 1, String::default());
+      self.node_styles.resize(index + 1, Style::default());
+    }
+    self.nodes[index] = label;
+    self.node_styles[index] = style;
+  }
+
+  //the viewer looks nodes up by id, a base or diff naming one that was never registered is broken.
+  //so is a trace whose diffs cannot be put in one order, or a listener whose diffs go back in it
+  pub fn check(&self) -> Result<(), String>{
+    let known = self.nodes.len();
+    for listener_id in 0..self.total_listeners as usize {
+      self.bases[listener_id].check_ids(known)
+        .map_err(|message| format!("base of listener {}: {}", listener_id + 1, message))?;
+      for (step, diff) in self.diffs[listener_id].iter().enumerate() {
+        diff.check_ids(known)
+          .map_err(|message| format!("diff {} of listener {}: {}", step + 1, listener_id + 1, message))?;
+      }
+    }
+    timeline_order(&self.diffs)?;
+    Ok(())
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ShapeHint{
+  Circle,
+  Square,
+  Rectangle,
+}
+
+//drawing hints from the traced program, unset fields keep what the viewer would pick
+//"style": {"fill": "#aa0000", "stroke": "black", "shape": "square", "size": [40, 20], "thickness": 3}
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct Style{
+  pub fill: Option<(f32, f32, f32)>,
+  pub stroke: Option<(f32, f32, f32)>,
+  pub shape: Option<ShapeHint>,
+  pub size: Option<(f32, f32)>,//radius or half width and half height
+  pub thickness: Option<f32>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StyleTarget{
+  Node(u32),
+  Edge(u32, u32),
+}
+
+//replaces every hint of the target, an empty style goes back to the defaults
+#[derive(Debug, Clone, Copy)]
+pub struct StyleChange{
+  pub target: StyleTarget,
+  pub style: Style,
 }
 
 #[derive(Clone, Default, Debug)]
 pub struct BaseInfo{
   pub edges: HashMap<u32, HashMap<u32, String>>,
   pub root_id: u32,
-  note: String,
+  pub note: String,
+  pub labels: HashMap<u32, String>,//where the base shows another label than the node was registered with
 }
 
-#[derive(Debug, Default)]
+#[derive(Debug, Default, Clone)]
 pub struct DiffInfo{
   pub added_edges: Vec<Edge>,
   pub removed_edges: Vec<Edge>,
   pub added_nodes: Vec<Node>,
   pub removed_nodes: Vec<Node>,
-  pub weights_changed: Vec<Edge>,
-  pub labels_changed: Vec<Node>,
+  pub weights_changed: Vec<WeightChange>,
+  pub labels_changed: Vec<LabelChange>,
+  pub styles_changed: Vec<StyleChange>,
   pub note: String,
   pub root_id: u32,
+  pub seq: Option<u64>,//global sequence number shared by all listeners
+  pub timestamp: Option<f64>,
 }
 
-#[derive(Debug,Default)]
+#[derive(Debug, Default, Clone)]
 pub struct Edge{
   pub from_id: u32,
   pub to_id: u32,
@@ -51,208 +159,578 @@ pub fn deserialize_json(path: &str) -> Result<Root,Box<dyn Error>>{
   let reader = BufReader::new(file);
 
   let json_raw: Value = serde_json::from_reader(reader)?;
-  let total_listeners = json_raw.get("specifiers")
-    .unwrap()
-    .as_object()
-    .unwrap()
-    .get("total_listeners")
-    .unwrap()
+  let total_listeners = field(object_helper(&json_raw)?, "specifiers")
+    .and_then(object_helper)
+    .and_then(|specifiers| field(specifiers, "total_listeners"))?
     .as_u64()
-    .unwrap() as u8;
+    .filter(|count| *count <= u8::MAX as u64)
+    .ok_or("total_listeners is not a listener count")? as u8;
 
-  let mut bases = Vec::with_capacity(total_listeners as usize);
-  let mut diffs: Vec<Vec<DiffInfo>> = Vec::with_capacity(total_listeners as usize);
+  let mut root = Root::new(total_listeners);
 
-  bases.resize(total_listeners as usize, BaseInfo::default());
-  diffs.resize_with(total_listeners as usize, Vec::new);
+  destructure_base(&mut root, &json_raw)?;
+
+  Ok(root)
+}
 
-  let mut root = Root{
-    bases,
-    diffs,
-    nodes: Vec::new(),
-    total_listeners,
+//the parts of a trace that are not where the format wants them are errors, not panics
+pub fn destructure_base(root: &mut Root, raw_json: &Value) -> Result<(), String>{
+  let raw_obj = object_helper(raw_json)?;
+  let listener_id = |key: &str| -> Result<usize, String>{
+    key.strip_prefix('L')
+      .and_then(|digits| digits.parse::<usize>().ok())
+      .filter(|listener_id| *listener_id < root.total_listeners as usize)
+      .ok_or(format!("{:?} is not one of the trace's listeners", key))
   };
 
-  destructure_base(&mut root, &json_raw);
+  for (key, value) in object_helper(field(raw_obj, "bases")?)? {
+    let listener_id = listener_id(key)?;
+    root.bases[listener_id] = base_info_helper(value)?;
+  }
 
-  Ok(root)
+  let mut diffs = Vec::new();
+  for (key, value) in object_helper(field(raw_obj, "diffs")?)? {
+    diffs.push((listener_id(key)?, diff_info_helper(value)?));
+  }
+  for (listener_id, listener_diffs) in diffs {
+    for diff in listener_diffs {
+      root.push_diff(listener_id, diff);
+    }
+  }
+
+  root.nodes = node_info_helper(field(raw_obj, "nodes")?)?;
+  root.node_styles = node_style_helper(field(raw_obj, "nodes")?)?;
+  if let Some(edge_styles_value) = raw_obj.get("edge_styles") {
+    for change in style_change_helper(edge_styles_value)? {
+      if let StyleTarget::Edge(from_id, to_id) = change.target {
+        root.edge_styles.insert((from_id, to_id), change.style);
+      }
+    }
+  }
+
+  root.check()
+}
+
+fn object_helper(value: &Value) -> Result<&Map<String, Value>, String>{
+  value.as_object().ok_or(format!("{} is not an object", shorten(value)))
 }
 
-pub fn destructure_base(root: &mut Root, raw_json: &Value){
+fn array_helper(value: &Value) -> Result<&Vec<Value>, String>{
+  value.as_array().ok_or(format!("{} is not an array", shorten(value)))
+}
 
-  raw_json.get("bases")
-    .unwrap()
-    .as_object()
-    .unwrap()
-    .iter()
-    .for_each(|(key, value)|{
-      let listener_id: u8 =  (&key[1..]).parse().expect("Digit could not be parsed to u8");
-      root.bases[listener_id as usize] = base_info_helper(value);
-    });
+fn field<'a>(obj: &'a Map<String, Value>, key: &str) -> Result<&'a Value, String>{
+  obj.get(key).ok_or(format!("no {}", key))
+}
 
-  raw_json.get("diffs")
-    .unwrap()
-    .as_object()
-    .unwrap()
-    .iter()
-    .for_each(|(key, value)|{
-      let listener_id: u8 =  (&key[1..]).parse().expect("Digit could not be parsed to u8");
-      root.diffs[listener_id as usize] = diff_info_helper(value);
-    });
+fn id_helper(obj: &Map<String, Value>, key: &str) -> Result<u32, String>{
+  field(obj, key)?
+    .as_u64()
+    .filter(|id| *id <= u32::MAX as u64)
+    .map(|id| id as u32)
+    .ok_or(format!("{} is not an id", key))
+}
 
-  root.nodes = node_info_helper(raw_json.get("nodes").unwrap());
+fn text_helper(obj: &Map<String, Value>, key: &str) -> Result<String, String>{
+  field(obj, key)?.as_str().map(str::to_string).ok_or(format!("{} is not a string", key))
 }
 
-pub fn node_info_helper(node_value: &Value) -> Vec<String>{
-  let array_unwrapped = node_value.as_array().unwrap();
-  let mut new_array = Vec::with_capacity(array_unwrapped.len());
-  new_array.resize(array_unwrapped.len(),String::default());
+//a missing text is an empty one
+fn optional_text_helper(obj: &Map<String, Value>, key: &str) -> Result<String, String>{
+  match obj.get(key) {
+    Some(_) => text_helper(obj, key),
+    None => Ok(String::new()),
+  }
+}
+
+//the start of a value for error messages, a whole diff would flood the console
+fn shorten(value: &Value) -> String{
+  let text = value.to_string();
+  match text.char_indices().nth(60) {
+    Some((end, _)) => format!("{}...", &text[..end]),
+    None => text,
+  }
+}
+
+pub fn node_style_helper(node_value: &Value) -> Result<Vec<Style>, String>{
+  let array_unwrapped = array_helper(node_value)?;
+  let mut styles = vec![Style::default(); array_unwrapped.len()];
   for node_obj in array_unwrapped{
-    let node_map = node_obj.as_object().unwrap();
-    let id_index: u32 = node_map.get("id").unwrap().as_u64().unwrap() as u32;
-    new_array[id_index as usize] = node_map.get("label").unwrap().as_str().unwrap().to_string();
-  }
-  new_array
-}
-
-pub fn diff_info_helper(diff_value: &Value) -> Vec<DiffInfo>{
-  let diff_array = diff_value.as_array().unwrap();
-  let mut diff_infos = Vec::new();
-  
-  for diff_obj in diff_array {
-    let obj = diff_obj.as_object().unwrap();
-    
-    let mut added_edges = Vec::new();
-    if let Some(added_edges_value) = obj.get("added_edges") {
-      for edge_value in added_edges_value.as_array().unwrap() {
-        let edge_obj = edge_value.as_object().unwrap();
-        added_edges.push(Edge {
-          from_id: edge_obj.get("from_id").unwrap().as_u64().unwrap() as u32,
-          to_id: edge_obj.get("to_id").unwrap().as_u64().unwrap() as u32,
-          weight: edge_obj.get("weight").unwrap().as_str().unwrap().to_string(),
-        });
+    let node_map = object_helper(node_obj)?;
+    if let Some(style_value) = node_map.get("style") {
+      let id_index = id_helper(node_map, "id")? as usize;
+      if id_index >= styles.len() {
+        return Err(format!("node id {} is past the end of the nodes", id_index));
       }
+      styles[id_index] = style_helper(style_value);
     }
-    
-    let mut removed_edges = Vec::new();
-    if let Some(removed_edges_value) = obj.get("removed_edges") {
-      for edge_value in removed_edges_value.as_array().unwrap() {
-        let edge_obj = edge_value.as_object().unwrap();
-        removed_edges.push(Edge {
-          from_id: edge_obj.get("from_id").unwrap().as_u64().unwrap() as u32,
-          to_id: edge_obj.get("to_id").unwrap().as_u64().unwrap() as u32,
-          weight: edge_obj.get("weight").unwrap().as_str().unwrap().to_string(),
-        });
-      }
+  }
+  Ok(styles)
+}
+
+//"#rgb", "#rrggbb", a few names or [r, g, b] with components between 0 and 1
+pub fn color_helper(color_value: &Value) -> Option<(f32, f32, f32)>{
+  if let Some(components) = color_value.as_array() {
+    if components.len() != 3 {
+      println!("Color {} needs three components, ignoring it", color_value);
+      return None;
     }
-    
-    let mut added_nodes = Vec::new();
-    if let Some(added_nodes_value) = obj.get("added_nodes") {
-      for node_value in added_nodes_value.as_array().unwrap() {
-        let node_obj = node_value.as_object().unwrap();
-        added_nodes.push(Node {
-          id: node_obj.get("id").unwrap().as_u64().unwrap() as u32,
-          label: node_obj.get("label").map(|v| v.as_str().unwrap().to_string()).unwrap_or_default(),
-        });
-      }
+    let component = |i: usize| components[i].as_f64().unwrap_or(0.0).clamp(0.0, 1.0) as f32;
+    return Some((component(0), component(1), component(2)));
+  }
+
+  let text = color_value.as_str()?.trim().to_lowercase();
+  let named = match text.as_str() {
+    "black" => Some((0.0, 0.0, 0.0)),
+    "white" => Some((1.0, 1.0, 1.0)),
+    "red" => Some((1.0, 0.0, 0.0)),
+    "green" => Some((0.0, 1.0, 0.0)),
+    "blue" => Some((0.0, 0.0, 1.0)),
+    "yellow" => Some((1.0, 1.0, 0.0)),
+    "orange" => Some((1.0, 0.5, 0.0)),
+    "purple" => Some((0.5, 0.0, 1.0)),
+    "gray" | "grey" => Some((0.5, 0.5, 0.5)),
+    _ => None,
+  };
+  if named.is_some() {
+    return named;
+  }
+
+  let hex = text.strip_prefix('#').unwrap_or(&text);
+  let digits: Option<Vec<u32>> = hex.chars().map(|c| c.to_digit(16)).collect();
+  let parsed = match digits.as_deref() {
+    Some(&[r, g, b]) => Some(((r * 17) as f32 / 255.0, (g * 17) as f32 / 255.0, (b * 17) as f32 / 255.0)),
+    Some(&[r1, r2, g1, g2, b1, b2]) => Some((
+      (r1 * 16 + r2) as f32 / 255.0,
+      (g1 * 16 + g2) as f32 / 255.0,
+      (b1 * 16 + b2) as f32 / 255.0,
+    )),
+    _ => None,
+  };
+  if parsed.is_none() {
+    println!("Unknown color {}, ignoring it", color_value);
+  }
+  parsed
+}
+
+pub fn style_helper(style_value: &Value) -> Style{
+  let mut style = Style::default();
+  let Some(style_obj) = style_value.as_object() else {
+    println!("Style {} is not an object, ignoring it", style_value);
+    return style;
+  };
+  style.fill = style_obj.get("fill").and_then(color_helper);
+  style.stroke = style_obj.get("stroke").and_then(color_helper);
+  style.shape = match style_obj.get("shape").and_then(|v| v.as_str()) {
+    Some("circle") => Some(ShapeHint::Circle),
+    Some("square") => Some(ShapeHint::Square),
+    Some("rectangle") => Some(ShapeHint::Rectangle),
+    Some(other) => {
+      println!("Unknown shape {}, ignoring it", other);
+      None
     }
-    
-    let mut removed_nodes = Vec::new();
-    if let Some(removed_nodes_value) = obj.get("removed_nodes") {
-      for node_value in removed_nodes_value.as_array().unwrap() {
-        let node_obj = node_value.as_object().unwrap();
-        removed_nodes.push(Node {
-          id: node_obj.get("id").unwrap().as_u64().unwrap() as u32,
-          label: node_obj.get("label").map(|v| v.as_str().unwrap().to_string()).unwrap_or_default(),
-        });
-      }
+    None => None,
+  };
+  style.size = match style_obj.get("size") {
+    Some(Value::Array(sizes)) if sizes.len() == 2 => {
+      Some((sizes[0].as_f64().unwrap_or(0.0) as f32, sizes[1].as_f64().unwrap_or(0.0) as f32))
     }
-    
-    let mut weights_changed = Vec::new();
-    if let Some(weights_changed_value) = obj.get("weights_changed") {
-      for edge_value in weights_changed_value.as_array().unwrap() {
-        let edge_obj = edge_value.as_object().unwrap();
-        weights_changed.push(Edge {
-          from_id: edge_obj.get("from_id").unwrap().as_u64().unwrap() as u32,
-          to_id: edge_obj.get("to_id").unwrap().as_u64().unwrap() as u32,
-          weight: edge_obj.get("label").unwrap().as_str().unwrap().to_string(),
-        });
+    Some(size) => size.as_f64().map(|size| (size as f32, size as f32)),
+    None => None,
+  };
+  style.thickness = style_obj.get("thickness").and_then(|v| v.as_f64()).map(|v| v as f32);
+  style
+}
+
+//entries with "id" style a node, entries with "from_id" and "to_id" style an edge
+pub fn style_change_helper(changes_value: &Value) -> Result<Vec<StyleChange>, String>{
+  let mut changes = Vec::new();
+  for change_value in array_helper(changes_value)? {
+    let change_obj = object_helper(change_value)?;
+    let id = |key: &str| change_obj.get(key).and_then(|v| v.as_u64()).map(|v| v as u32);
+    let target = match (id("id"), id("from_id"), id("to_id")) {
+      (Some(node_id), _, _) => StyleTarget::Node(node_id),
+      (None, Some(from_id), Some(to_id)) => StyleTarget::Edge(from_id, to_id),
+      _ => {
+        println!("Style change {} has no target, ignoring it", change_value);
+        continue;
+      }
+    };
+    let style = change_obj.get("style").map(style_helper).unwrap_or_default();
+    changes.push(StyleChange { target, style });
+  }
+  Ok(changes)
+}
+
+//the style of every node and edge that has one after step diffs of a listener
+pub fn styles_at(root: &Root, listener_id: usize, step: usize)
+  -> (HashMap<u32, Style>, HashMap<(u32, u32), Style>){
+  let mut node_styles: HashMap<u32, Style> = root.node_styles.iter()
+    .enumerate()
+    .filter(|(_, style)| **style != Style::default())
+    .map(|(id, style)| (id as u32, *style))
+    .collect();
+  let mut edge_styles = root.edge_styles.clone();
+
+  for diff in root.diffs[listener_id].iter().take(step) {
+    for change in &diff.styles_changed {
+      match change.target {
+        StyleTarget::Node(id) => {
+          node_styles.insert(id, change.style);
+        }
+        StyleTarget::Edge(from_id, to_id) => {
+          edge_styles.insert((from_id, to_id), change.style);
+        }
       }
     }
-    
-    let mut labels_changed = Vec::new();
-    if let Some(labels_changed_value) = obj.get("labels_changed") {
-      for node_value in labels_changed_value.as_array().unwrap() {
-        let node_obj = node_value.as_object().unwrap();
-        labels_changed.push(Node {
-          id: node_obj.get("id").unwrap().as_u64().unwrap() as u32,
-          label: node_obj.get("label").unwrap().as_str().unwrap().to_string(),
-        });
+  }
+  (node_styles, edge_styles)
+}
+
+pub fn node_info_helper(node_value: &Value) -> Result<Vec<String>, String>{
+  let array_unwrapped = array_helper(node_value)?;
+  let mut new_array = Vec::with_capacity(array_unwrapped.len());
+  new_array.resize(array_unwrapped.len(),String::default());
+  for node_obj in array_unwrapped{
+    let node_map = object_helper(node_obj)?;
+    let id_index = id_helper(node_map, "id")? as usize;
+    if id_index >= new_array.len() {
+      return Err(format!("node id {} is past the end of the nodes", id_index));
+    }
+    new_array[id_index] = text_helper(node_map, "label")?;
+  }
+  Ok(new_array)
+}
+
+pub fn diff_info_helper(diff_value: &Value) -> Result<Vec<DiffInfo>, String>{
+  array_helper(diff_value)?.iter().map(diff_helper).collect()
+}
+
+fn edges_helper(obj: &Map<String, Value>, key: &str) -> Result<Vec<Edge>, String>{
+  let mut edges = Vec::new();
+  if let Some(edges_value) = obj.get(key) {
+    for edge_value in array_helper(edges_value)? {
+      let edge_obj = object_helper(edge_value)?;
+      edges.push(Edge {
+        from_id: id_helper(edge_obj, "from_id")?,
+        to_id: id_helper(edge_obj, "to_id")?,
+        weight: optional_text_helper(edge_obj, "weight")?,
+      });
+    }
+  }
+  Ok(edges)
+}
+
+fn nodes_helper(obj: &Map<String, Value>, key: &str) -> Result<Vec<Node>, String>{
+  let mut nodes = Vec::new();
+  if let Some(nodes_value) = obj.get(key) {
+    for node_value in array_helper(nodes_value)? {
+      let node_obj = object_helper(node_value)?;
+      nodes.push(Node {
+        id: id_helper(node_obj, "id")?,
+        label: optional_text_helper(node_obj, "label")?,
+      });
+    }
+  }
+  Ok(nodes)
+}
+
+//one entry of a listener's diff array, or a diff that was streamed in on its own
+pub fn diff_helper(diff_obj: &Value) -> Result<DiffInfo, String>{
+  let obj = object_helper(diff_obj)?;
+
+  //both take "old" and "new", or the "old===new" string of older traces under "label"
+  let mut weights_changed = Vec::new();
+  if let Some(weights_changed_value) = obj.get("weights_changed") {
+    for edge_value in array_helper(weights_changed_value)? {
+      weights_changed.push(WeightChange::from_value(edge_value)
+        .ok_or(format!("{} is not a weight change", shorten(edge_value)))?);
+    }
+  }
+
+  let mut labels_changed = Vec::new();
+  if let Some(labels_changed_value) = obj.get("labels_changed") {
+    for node_value in array_helper(labels_changed_value)? {
+      labels_changed.push(LabelChange::from_value(node_value)
+        .ok_or(format!("{} is not a label change", shorten(node_value)))?);
+    }
+  }
+
+  let styles_changed = match obj.get("styles_changed") {
+    Some(styles_changed_value) => style_change_helper(styles_changed_value)?,
+    None => Vec::new(),
+  };
+
+  Ok(DiffInfo {
+    added_edges: edges_helper(obj, "added_edges")?,
+    removed_edges: edges_helper(obj, "removed_edges")?,
+    added_nodes: nodes_helper(obj, "added_nodes")?,
+    removed_nodes: nodes_helper(obj, "removed_nodes")?,
+    weights_changed,
+    labels_changed,
+    styles_changed,
+    note: optional_text_helper(obj, "note")?,
+    root_id: id_helper(obj, "root_id")?,
+    seq: obj.get("seq").and_then(|v| v.as_u64()),
+    timestamp: obj.get("timestamp").and_then(|v| v.as_f64()),
+  })
+}
+
+//how the diffs of every listener are put in one order
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TimelineOrder{
+  Seq,
+  Timestamp,
+  Interleaved,
+}
+
+//how many diffs a trace has and how many of them carry a seq or a timestamp
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct TimelineCount{
+  pub diffs: usize,
+  pub seqs: usize,
+  pub timestamps: usize,
+}
+
+impl TimelineCount{
+  pub fn of<'a>(diffs: impl Iterator<Item = &'a DiffInfo>) -> Self{
+    let mut count = TimelineCount::default();
+    for diff in diffs {
+      count.add(diff);
+    }
+    count
+  }
+
+  pub fn add(&mut self, diff: &DiffInfo){
+    self.diffs += 1;
+    self.seqs += diff.seq.is_some() as usize;
+    self.timestamps += diff.timestamp.is_some() as usize;
+  }
+
+  pub fn remove(&mut self, diff: &DiffInfo){
+    self.diffs -= 1;
+    self.seqs -= diff.seq.is_some() as usize;
+    self.timestamps -= diff.timestamp.is_some() as usize;
+  }
+
+  //seq when every diff has one, timestamps when every diff has one and step by step when none
+  //has either. a seq and a timestamp say nothing about each other, so anything in between is refused
+  pub fn order(&self) -> Result<TimelineOrder, String>{
+    if self.seqs == self.diffs {
+      Ok(TimelineOrder::Seq)
+    }
+    else if self.timestamps == self.diffs {
+      Ok(TimelineOrder::Timestamp)
+    }
+    else if self.seqs == 0 && self.timestamps == 0 {
+      Ok(TimelineOrder::Interleaved)
+    }
+    else{
+      Err(format!("{} of {} diffs have a seq and {} a timestamp, they cannot be put in one order", self.seqs, self.diffs, self.timestamps))
+    }
+  }
+}
+
+//a listener's diffs come one after another in the order, one going back would move a cursor backwards
+pub fn keeps_order(order: TimelineOrder, previous: &DiffInfo, next: &DiffInfo) -> Result<(), String>{
+  let forward = match order {
+    TimelineOrder::Seq => previous.seq <= next.seq,
+    TimelineOrder::Timestamp => previous.timestamp <= next.timestamp,
+    TimelineOrder::Interleaved => true,
+  };
+  if forward {
+    return Ok(());
+  }
+  match order {
+    TimelineOrder::Seq => Err(format!("seq {} comes after seq {}", next.seq.unwrap(), previous.seq.unwrap())),
+    _ => Err(format!("timestamp {} comes after timestamp {}", next.timestamp.unwrap(), previous.timestamp.unwrap())),
+  }
+}
+
+//the order of the whole trace, with every listener's diffs checked against it
+pub fn timeline_order(listeners: &[Vec<DiffInfo>]) -> Result<TimelineOrder, String>{
+  let order = TimelineCount::of(listeners.iter().flatten()).order()?;
+  for (listener_id, diffs) in listeners.iter().enumerate() {
+    for (step, pair) in diffs.windows(2).enumerate() {
+      keeps_order(order, &pair[0], &pair[1])
+        .map_err(|message| format!("diff {} of listener {}: {}", step + 2, listener_id + 1, message))?;
+    }
+  }
+  Ok(order)
+}
+
+//(listener id, diff index) pairs in program order
+pub fn global_timeline(root: &Root) -> Vec<(usize, usize)>{
+  let mut timeline: Vec<(usize, usize)> = Vec::new();
+  for (listener_id, diffs) in root.diffs.iter().enumerate(){
+    for diff_index in 0..diffs.len(){
+      timeline.push((listener_id, diff_index));
+    }
+  }
+
+  let diff = |&(listener_id, diff_index): &(usize, usize)| &root.diffs[listener_id][diff_index];
+  match timeline_order(&root.diffs) {
+    Ok(TimelineOrder::Seq) => timeline.sort_by_key(|entry| diff(entry).seq),
+    Ok(TimelineOrder::Timestamp) => {
+      timeline.sort_by(|a, b| diff(a).timestamp.unwrap().total_cmp(&diff(b).timestamp.unwrap()));
+    }
+    order => {
+      if root.diffs.len() > 1 {
+        println!("Trace has no global order, interleaving listeners");
+      }
+      if let Err(message) = order {
+        println!("{}", message);
       }
+      timeline.sort_by_key(|&(listener_id, diff_index)| (diff_index, listener_id));
     }
-    
-    let note = obj.get("note").unwrap().as_str().unwrap().to_string();
-    let root_id = obj.get("root_id").unwrap().as_u64().unwrap() as u32;
-    
-    diff_infos.push(DiffInfo {
-      added_edges,
-      removed_edges,
-      added_nodes,
-      removed_nodes,
-      weights_changed,
-      labels_changed,
-      note,
-      root_id,
-    });
-  }
-  
-  diff_infos
-}
-
-pub fn base_info_helper(base_value: &Value) -> BaseInfo {
+  }
+  timeline
+}
+
+pub fn base_info_helper(base_value: &Value) -> Result<BaseInfo, String> {
   let mut new_base_info = BaseInfo::default();
-  let unwrapped_diffobj = base_value.as_object().unwrap();
-  unwrapped_diffobj.iter()
-    .for_each(|(key, value)|{
-      let id_or_text:Option<u32> = key.parse().ok();
-    match id_or_text{
-        Some(from_id) => {
-          new_base_info.edges.insert(from_id, 
-            base_info_edge_former(value));
-        }
-        None => {
-          match key.as_str() {
-            "root" => {
-              new_base_info.root_id = unwrapped_diffobj.get("root").unwrap().as_u64().unwrap() as u32;
-            }
-            "note" => {
-              new_base_info.note = unwrapped_diffobj.get("note").unwrap().as_str().unwrap().to_string();
-            }
-            _ => {
-              panic!("unexpected key");
-            }
+  let unwrapped_diffobj = object_helper(base_value)?;
+  for (key, value) in unwrapped_diffobj {
+    match key.parse::<u32>() {
+      Ok(from_id) => {
+        new_base_info.edges.insert(from_id, base_info_edge_former(value)?);
+      }
+      Err(_) => match key.as_str() {
+        "root" => new_base_info.root_id = id_helper(unwrapped_diffobj, "root")?,
+        "note" => new_base_info.note = text_helper(unwrapped_diffobj, "note")?,
+        "labels" => {
+          for (id, label) in object_helper(value)? {
+            let id: u32 = id.parse().map_err(|_| format!("{:?} is not a node id", id))?;
+            let label = label.as_str().ok_or(format!("the label of {} is not a string", id))?;
+            new_base_info.labels.insert(id, label.to_string());
           }
         }
+        _ => return Err(format!("unexpected key {:?} in a base", key)),
+      },
+    }
+  }
+  Ok(new_base_info)
+}
+
+pub fn base_info_edge_former(base_value: &Value) -> Result<HashMap<u32, String>, String>{
+  let mut edge_connections: HashMap<u32, String> = HashMap::default();
+  for objs in array_helper(base_value)? {
+    for (to_id_str, weight_wrapped) in object_helper(objs)? {
+      let to_id: u32 = to_id_str.parse().map_err(|_| format!("{:?} is not a node id", to_id_str))?;
+      let weight = weight_wrapped.as_str().ok_or(format!("the weight to {} is not a string", to_id))?;
+      edge_connections.insert(to_id, weight.to_string());
+    }
+  }
+  Ok(edge_connections)
+}
+
+impl BaseInfo{
+  //the label of every node as the base shows it
+  pub fn node_labels(&self, registered: &[String]) -> Vec<String>{
+    let mut labels = registered.to_vec();
+    for (id, label) in &self.labels {
+      if let Some(shown) = labels.get_mut(*id as usize) {
+        shown.clone_from(label);
       }
+    }
+    labels
+  }
 
-    });
-  new_base_info
+  //every node the base names has to be registered
+  pub fn check_ids(&self, known: usize) -> Result<(), String>{
+    let unknown = std::iter::once(self.root_id)
+      .chain(self.edges.iter().flat_map(|(from_id, connections)| std::iter::once(*from_id).chain(connections.keys().copied())))
+      .chain(self.labels.keys().copied())
+      .find(|id| *id as usize >= known);
+    match unknown {
+      Some(id) => Err(format!("node {} was never registered", id)),
+      None => Ok(()),
+    }
+  }
 }
 
-pub fn base_info_edge_former(base_value: &Value) -> HashMap<u32, String>{
-  let mut edge_connections: HashMap<u32, String> = HashMap::default();
-  let to_array = base_value.as_array().unwrap();
-  for objs in to_array {
-    let unwrapped_obj = objs.as_object().unwrap();
-    for (to_id_str, weight_wrapped) in unwrapped_obj{
-      let to_id:u32 = to_id_str.parse().unwrap();
-      let weight = String::from(weight_wrapped.as_str().unwrap());
-      edge_connections.insert(to_id, weight);
+impl DiffInfo{
+  //every node the diff names has to be registered, added ones too
+  pub fn check_ids(&self, known: usize) -> Result<(), String>{
+    let unknown = std::iter::once(self.root_id)
+      .chain(self.added_nodes.iter().chain(&self.removed_nodes).map(|node| node.id))
+      .chain(self.added_edges.iter().chain(&self.removed_edges).flat_map(|edge| [edge.from_id, edge.to_id]))
+      .chain(self.labels_changed.iter().map(|change| change.id))
+      .chain(self.weights_changed.iter().flat_map(|change| [change.from_id, change.to_id]))
+      .find(|id| *id as usize >= known);
+    match unknown {
+      Some(id) => Err(format!("node {} was never registered", id)),
+      None => Ok(()),
     }
   }
-  edge_connections
 }
 
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  type Order<'a> = &'a [(Option<u64>, Option<f64>)]; //seq and timestamp of each diff
+
+  fn root_with(orders: &[Order]) -> Root{
+    let mut root = Root::new(orders.len() as u8);
+    for (listener_id, diffs) in orders.iter().enumerate() {
+      for &(seq, timestamp) in diffs.iter() {
+        root.push_diff(listener_id, DiffInfo { seq, timestamp, ..DiffInfo::default() });
+      }
+    }
+    root
+  }
+
+  #[test]
+  fn timeline_follows_seq_when_every_diff_has_one(){
+    //the timestamps would put listener 1 first
+    let root = root_with(&[
+      &[(Some(2), Some(5.0)), (Some(4), None)],
+      &[(Some(1), None), (Some(3), Some(1.0))],
+    ]);
+    assert_eq!(global_timeline(&root), vec![(1, 0), (0, 0), (1, 1), (0, 1)]);
+  }
 
+  #[test]
+  fn timeline_follows_timestamps_when_a_seq_is_missing(){
+    let root = root_with(&[
+      &[(Some(1), Some(0.5)), (None, Some(3.0))],
+      &[(Some(2), Some(0.25)), (Some(3), Some(2.0))],
+    ]);
+    assert_eq!(global_timeline(&root), vec![(1, 0), (0, 0), (1, 1), (0, 1)]);
+  }
 
+  #[test]
+  fn timeline_interleaves_without_either(){
+    let root = root_with(&[&[(None, None), (None, None)], &[(None, None)]]);
+    assert_eq!(timeline_order(&root.diffs), Ok(TimelineOrder::Interleaved));
+    assert_eq!(global_timeline(&root), vec![(0, 0), (1, 0), (0, 1)]);
+  }
 
+  #[test]
+  fn mixed_orders_are_refused(){
+    let mixed: [&[Order]; 3] = [
+      &[&[(Some(1), None)], &[(None, Some(2.0))]],
+      &[&[(Some(1), None), (None, None)]],
+      &[&[(None, Some(1.0))], &[(None, None)]],
+    ];
+    for orders in mixed {
+      let root = root_with(orders);
+      assert!(timeline_order(&root.diffs).is_err(), "{:?} was ordered", orders);
+      assert!(root.check().is_err());
+    }
+  }
+
+  #[test]
+  fn a_listener_going_back_is_refused(){
+    let backwards: [&[Order]; 2] = [
+      &[&[(Some(1), None), (Some(3), None)], &[(Some(4), None), (Some(2), None)]],
+      &[&[(Some(2), Some(1.0)), (None, Some(0.5))]],
+    ];
+    for orders in backwards {
+      assert!(timeline_order(&root_with(orders).diffs).is_err(), "{:?} was ordered", orders);
+    }
+    //the order that is not used may go back
+    assert_eq!(timeline_order(&root_with(&[&[(Some(1), Some(2.0)), (Some(2), Some(1.0))]]).diffs), Ok(TimelineOrder::Seq));
+  }
+}
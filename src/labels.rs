@@ -0,0 +1,93 @@
+use crate::hashgrid::HashGrid;
+use crate::tree::{CS, Point};
+use std::collections::HashMap;
+
+const NODE: u8 = 0;
+const LABEL: u8 = 1;
+const LABEL_MARGIN: f32 = 2.0; //space kept around a placed label
+const DISTANCE_COST: f32 = 1.0; //per candidate skipped, so ties keep the label by the middle of its edge
+
+type Rectangle = ((f32, f32), (f32, f32)); //center, dimensions
+
+//nodes and the labels placed so far, labels are placed one at a time around both
+pub struct LabelPlacer {
+  grid: HashGrid<(u8, usize)>,
+  boxes: HashMap<(u8, usize), Rectangle>,
+  placed: usize,
+}
+
+impl LabelPlacer {
+  //nodes by their center and outline
+  pub fn new(nodes: &HashMap<usize, ((f32, f32), CS)>, cell: f32) -> Self {
+    let mut placer = LabelPlacer {
+      grid: HashGrid::new(cell.max(1.0) as u16),
+      boxes: HashMap::new(),
+      placed: 0,
+    };
+    let mut ids: Vec<&usize> = nodes.keys().collect();
+    ids.sort();
+    for &id in ids {
+      let (center, outline) = nodes[&id];
+      if let Some((half_width, half_height)) = outline.half_size() {
+        placer.occupy((NODE, id), (center, (2.0 * half_width, 2.0 * half_height)));
+      }
+    }
+    placer
+  }
+
+  fn occupy(&mut self, key: (u8, usize), rectangle: Rectangle) {
+    self.grid.insert_rectangle(rectangle.0, rectangle.1, &key);
+    self.boxes.insert(key, rectangle);
+  }
+
+  //picks the candidate overlapping the least and keeps its spot, returns its index
+  pub fn place(&mut self, candidates: &[(Point, f32)], size: (f32, f32)) -> usize {
+    let mut best = (0, f32::MAX, ((0.0, 0.0), (0.0, 0.0)));
+    for (index, &(center, angle)) in candidates.iter().enumerate() {
+      let rectangle = ((center.x, center.y), rotated_bounds(size, angle));
+      let mut cost = index as f32 * DISTANCE_COST;
+      for key in self.grid.query_rectangle(rectangle.0, rectangle.1) {
+        cost += overlap(rectangle, self.boxes[&key]);
+      }
+      if cost < best.1 {
+        best = (index, cost, rectangle);
+      }
+    }
+    if !candidates.is_empty() {
+      let (center, dimensions) = best.2;
+      self.occupy((LABEL, self.placed), (center, (dimensions.0 + LABEL_MARGIN, dimensions.1 + LABEL_MARGIN)));
+      self.placed += 1;
+    }
+    best.0
+  }
+}
+
+//axis aligned box around a rotated one
+fn rotated_bounds(size: (f32, f32), angle: f32) -> (f32, f32) {
+  let (sin, cos) = (angle.sin().abs(), angle.cos().abs());
+  (size.0 * cos + size.1 * sin, size.0 * sin + size.1 * cos)
+}
+
+fn overlap(a: Rectangle, b: Rectangle) -> f32 {
+  let along = |a_center: f32, a_size: f32, b_center: f32, b_size: f32| {
+    ((a_center + a_size / 2.0).min(b_center + b_size / 2.0) - (a_center - a_size / 2.0).max(b_center - b_size / 2.0))
+      .max(0.0)
+  };
+  along(a.0.0, a.1.0, b.0.0, b.1.0) * along(a.0.1, a.1.1, b.0.1, b.1.1)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  //the first candidate is off a plain node's box but on the rectangle drawn there
+  #[test]
+  fn labels_keep_off_the_whole_outline() {
+    let nodes = HashMap::from([(0, ((0.0, 0.0), CS::Rectangle(120.0, 20.0)))]);
+    let candidates = [(Point::new(80.0, 0.0), 0.0), (Point::new(80.0, 60.0), 0.0)];
+    assert_eq!(LabelPlacer::new(&nodes, 24.0).place(&candidates, (30.0, 12.0)), 1);
+
+    let circle = HashMap::from([(0, ((0.0, 0.0), CS::Circle(30.0)))]);
+    assert_eq!(LabelPlacer::new(&circle, 24.0).place(&candidates, (30.0, 12.0)), 0);
+  }
+}
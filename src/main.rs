@@ -1,23 +1,36 @@
+mod binary;
 mod c_side;
+mod dot;
 mod dsas;
+mod events;
+mod golden;
 mod graph_draw;
 mod hashgrid;
+mod inspector;
 mod json_deserialize;
+mod labels;
+mod picking;
+mod raster;
+mod render;
+mod routing;
+mod stream;
+mod theme;
+#[cfg(test)]
+mod trace_check;
 mod tree;
+mod watch;
 
-use crate::hashgrid::HashGrid;
 use glfw::{
-    Action, Context, GlfwReceiver, Key, OpenGlProfileHint, Window, WindowEvent, WindowHint,
+    Action, Context, GlfwReceiver, Key, MouseButton, OpenGlProfileHint, WindowEvent, WindowHint,
     WindowMode, fail_on_errors,
 };
 use graph_draw::*;
-use json_deserialize::{Root, deserialize_json};
-use std::{f32::consts::PI, ffi::CString};
+use json_deserialize::Root;
+use std::{ffi::CString, time::Duration};
 use tree::*;
 
 pub static mut CAMERA_SHIFT: (f32, f32) = (0.0, 0.0);
 pub static mut DIMENSIONS: (f32, f32) = (1920.0, 1080.0);
-pub static mut STABLE_HAPPENED: bool = false;
 
 static mut LEFT: bool = false;
 static mut RIGHT: bool = false;
@@ -34,23 +47,133 @@ static mut NUM6: bool = false;
 static mut NUM7: bool = false;
 static mut NUM8: bool = false;
 static mut NUM9: bool = false;
+static mut TAB: bool = false;
+static mut SPLIT: bool = false;
+static mut PAGE: bool = false;
+static mut THEME: bool = false;
+static mut GHOSTS: bool = false;
+static mut ROUTING: bool = false;
+static mut CURSOR: (f32, f32) = (0.0, 0.0);
+static mut CLICK: bool = false;
+static mut HELD: bool = false;
+static mut SCROLL: f32 = 0.0;
+static mut GLOBAL_FORWARD: bool = false;
+static mut GLOBAL_BACKWARD: bool = false;
+static mut FOLLOW: bool = false;
 
 const SHIFT_AMOUNT: f32 = 8.0;
+const ZOOM_STEP: f32 = 1.1; //per notch of the scroll wheel
+const DEFAULT_TRACE: &str = "/home/alp/Desktop/code_files/c++/works/json_converter/ds.txt";
+const WAIT_FRAME: Duration = Duration::from_millis(16); //while no trace has arrived yet
 
-pub fn resize_camera(width: f32, height: f32, cam_horizontal: f32, cam_vertical: f32) {
+//draws everything once per tile, clipped to the tile and seen through its camera
+pub fn render_tile(tile: &Tile, focused: bool) {
+    let ((left, top), (right, bottom)) = tile.viewport;
+    let (width, height) = tile.dimensions();
     unsafe {
-        CAMERA_SHIFT = (
-            CAMERA_SHIFT.0 + cam_horizontal,
-            CAMERA_SHIFT.1 + cam_vertical,
-        );
-        DIMENSIONS = (width, height);
-        gl::Viewport(0, 0, DIMENSIONS.0 as i32, DIMENSIONS.1 as i32);
-        c_side::set_uniform_matrix(DIMENSIONS.0, DIMENSIONS.1, CAMERA_SHIFT.0, CAMERA_SHIFT.1);
-        c_side::sprite_uniform_matrix(DIMENSIONS.0, DIMENSIONS.1, CAMERA_SHIFT.0, CAMERA_SHIFT.1);
+        // gl counts rows from the bottom of the window
+        let gl_bottom = DIMENSIONS.1 as i32 - bottom;
+        gl::Viewport(left, gl_bottom, right - left, bottom - top);
+        gl::Scissor(left, gl_bottom, right - left, bottom - top);
+        if focused {
+            let (r, g, b) = theme::current().focused_background;
+            gl::ClearColor(r, g, b, 0.5);
+            gl::Clear(gl::COLOR_BUFFER_BIT);
+        }
+        //a zoomed in camera sees less of the world
+        let (width, height) = (width / tile.zoom, height / tile.zoom);
+        c_side::set_uniform_matrix(width, height, tile.camera.0, tile.camera.1);
+        c_side::sprite_uniform_matrix(width, height, tile.camera.0, tile.camera.1);
+        c_side::render_text();
+        c_side::draw_sprites();
     }
 }
 
 fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    if args.get(1).map(String::as_str) == Some("--golden") {
+        std::process::exit(golden::run_golden_command(&args[2..]));
+    }
+    if args.get(1).map(String::as_str) == Some("--screenshot") {
+        std::process::exit(raster::run_screenshot_command(&args[2..]));
+    }
+    if args.get(1).map(String::as_str) == Some("--convert") {
+        std::process::exit(events::run_convert_command(&args[2..]));
+    }
+    if args.get(1).map(String::as_str) == Some("--binary-bench") {
+        std::process::exit(binary::run_bench_command(&args[2..]));
+    }
+    if args.get(1).map(String::as_str) == Some("--hashgrid-bench") {
+        std::process::exit(hashgrid::run_bench_command(&args[2..]));
+    }
+    let mut trace_paths: Vec<&str> = Vec::new();
+    let mut listen = None;
+    let mut watch = false;
+    let mut edge_routing = routing::EdgeRouting::Straight;
+    let mut arg_index = 1;
+    while arg_index < args.len() {
+        if args[arg_index] == "--theme" {
+            let name = args.get(arg_index + 1).map(String::as_str).unwrap_or("");
+            if !theme::set_theme(name) {
+                println!("Unknown theme {:?}, pick one of {:?}", name, theme::theme_names());
+                std::process::exit(2);
+            }
+            arg_index += 2;
+            continue;
+        }
+        if args[arg_index] == "--routing" {
+            let name = args.get(arg_index + 1).map(String::as_str).unwrap_or("");
+            match routing::EdgeRouting::from_name(name) {
+                Some(routing) => edge_routing = routing,
+                None => {
+                    println!(
+                        "Unknown routing {:?}, pick one of {:?}",
+                        name,
+                        routing::routing_names()
+                    );
+                    std::process::exit(2);
+                }
+            }
+            arg_index += 2;
+            continue;
+        }
+        if args[arg_index] == "--listen" {
+            let text = args.get(arg_index + 1).map(String::as_str).unwrap_or("");
+            match stream::Address::parse(text) {
+                Ok(address) => listen = Some(address),
+                Err(e) => {
+                    println!("Cannot listen on {}", e);
+                    std::process::exit(2);
+                }
+            }
+            arg_index += 2;
+            continue;
+        }
+        if args[arg_index] == "--watch" {
+            watch = true;
+            arg_index += 1;
+            continue;
+        }
+        trace_paths.push(args[arg_index].as_str());
+        arg_index += 1;
+    }
+    //several dot files are one trace with a step for each graph
+    if trace_paths.len() > 1 && (watch || !trace_paths.iter().all(|path| dot::is_dot_path(path))) {
+        println!("Only dot files can be shown one after another, and not with --watch");
+        std::process::exit(2);
+    }
+    let trace_path = trace_paths.first().copied();
+    let mut trace_stream = listen.map(|address| {
+        let shown = address.to_string();
+        match stream::TraceStream::bind(address) {
+            Ok(trace_stream) => trace_stream,
+            Err(e) => {
+                println!("Could not listen on {}: {}", shown, e);
+                std::process::exit(2);
+            }
+        }
+    });
+
     let mut glfw = glfw::init(fail_on_errors!()).unwrap();
 
     glfw.window_hint(WindowHint::ContextVersion(4, 5));
@@ -62,17 +185,51 @@ fn main() {
         .unwrap();
     window.make_current();
     window.set_key_polling(true);
+    window.set_cursor_pos_polling(true);
+    window.set_mouse_button_polling(true);
+    window.set_scroll_polling(true);
     window.set_size(1920, 1080);
     window.set_framebuffer_size_callback(callback_resize);
     gl::load_with(|s| window.get_proc_address(s));
 
-    let json_data: Root =
-        deserialize_json("/home/alp/Desktop/code_files/c++/works/json_converter/ds.txt")
-            .expect("Error");
-    let graph_drawer = GraphDrawBuilder::new()
-        .viewport((0, 0), (1920, 1080))
-        .root(&json_data)
-        .build();
+    let mut watcher = watch.then(|| watch::TraceWatcher::new(trace_path.unwrap_or(DEFAULT_TRACE)));
+    //a streamed trace may start empty and a watched one broken, both are shown once a listener has a base
+    let mut json_data: Root = match (trace_path, &trace_stream, &watcher) {
+        (_, _, Some(watcher)) => watch::load_trace(watcher.path()).unwrap_or_else(|e| {
+            println!("Could not load {}: {}", watcher.path(), e);
+            Root::new(0)
+        }),
+        (None, Some(_), None) => Root::new(0),
+        (_, _, None) if trace_paths.len() > 1 => dot::read_dot(&trace_paths).expect("Error"),
+        (path, _, None) => events::read_trace(path.unwrap_or(DEFAULT_TRACE)).expect("Error"),
+    };
+    if let Some(trace_stream) = trace_stream.as_ref() {
+        println!("Listening for a trace on {}", trace_stream.address());
+    }
+    if json_data.total_listeners == 0 {
+        while json_data.total_listeners == 0 && !window.should_close() {
+            if let Some(trace_stream) = trace_stream.as_mut() {
+                for event in trace_stream.poll() {
+                    if let Err(e) = events::apply_event(&mut json_data, event) {
+                        println!("Skipping a trace event: {}", e);
+                    }
+                }
+            }
+            if let Some(watcher) = watcher.as_mut()
+                && watcher.poll()
+            {
+                match watch::load_trace(watcher.path()) {
+                    Ok(root) => json_data = root,
+                    Err(e) => println!("Could not load {}: {}", watcher.path(), e),
+                }
+            }
+            window.swap_buffers();
+            glfw.poll_events();
+            handle_window_event(&events, &mut window);
+            std::thread::sleep(WAIT_FRAME);
+        }
+    }
+    let total_listeners = json_data.total_listeners as usize;
 
     unsafe {
         c_side::initialize_render();
@@ -84,44 +241,140 @@ fn main() {
 
     unsafe {
         c_side::new_sprite_renderer();
-        let path = "/usr/share/fonts/TTF/CaskaydiaCoveNerdFontMono-Regular.ttf";
-        c_side::initialize_font_renderer(CString::new(path).expect("Error cstr").as_ptr());
+        c_side::initialize_font_renderer(CString::new(render::FONT_PATH).expect("Error cstr").as_ptr());
         c_side::load_all_text_vbo();
     }
 
     let mut graph_draw = GraphDrawBuilder::new()
         .viewport((0, 0), (1920, 1080))
-        .root(&json_data)
+        .owned_root(json_data)
         .listener_id(0)
+        .edge_routing(edge_routing)
         .build()
         .expect("Error building graph drawer");
 
-    for i in 0..(json_data.total_listeners as usize) {
+    for i in 0..total_listeners {
         println!("Adding graph for listener id {}", i);
         graph_draw.add_new_graph(i);
     }
 
+    let mut window_size = unsafe { DIMENSIONS };
     while !window.should_close() {
         window.swap_buffers();
         glfw.poll_events();
-        if !unsafe { STABLE_HAPPENED } {
-            unsafe {
-                STABLE_HAPPENED = graph_draw.simulation_step();
+        if let Some(trace_stream) = trace_stream.as_mut() {
+            let arrived = trace_stream.poll();
+            if !arrived.is_empty() {
+                graph_draw.apply_events(arrived);
+            }
+        }
+        if let Some(watcher) = watcher.as_mut()
+            && watcher.poll()
+        {
+            match watch::load_trace(watcher.path()) {
+                Ok(root) => {
+                    println!("Reloaded {}", watcher.path());
+                    graph_draw.clear_notice();
+                    graph_draw.replace_root(root);
+                }
+                Err(e) => {
+                    println!("Could not reload {}: {}", watcher.path(), e);
+                    graph_draw.show_notice(vec![format!("could not reload {}", watcher.path()), e]);
+                }
             }
         }
+        graph_draw.simulation_step();
         handle_window_event(&events, &mut window);
+        graph_draw.hover(unsafe { CURSOR });
+        if unsafe { CLICK } {
+            graph_draw.inspect(unsafe { CURSOR });
+            graph_draw.start_drag(unsafe { CURSOR });
+            unsafe {
+                CLICK = false;
+            }
+        }
+        if unsafe { HELD } {
+            graph_draw.drag(unsafe { CURSOR });
+        } else {
+            graph_draw.end_drag();
+        }
+        if unsafe { SCROLL } != 0.0 {
+            graph_draw.zoom_at(unsafe { CURSOR }, ZOOM_STEP.powf(unsafe { SCROLL }));
+            unsafe {
+                SCROLL = 0.0;
+            }
+        }
+        if unsafe { DIMENSIONS } != window_size {
+            window_size = unsafe { DIMENSIONS };
+            graph_draw.resize_viewport((0, 0), (window_size.0 as i32, window_size.1 as i32));
+        }
         // Update animations if enabled
         if unsafe { LEFT } {
-            left_pressed();
+            left_pressed(&mut graph_draw);
         }
         if unsafe { RIGHT } {
-            right_pressed();
+            right_pressed(&mut graph_draw);
         }
         if unsafe { UP } {
-            up_pressed();
+            up_pressed(&mut graph_draw);
         }
         if unsafe { DOWN } {
-            down_pressed();
+            down_pressed(&mut graph_draw);
+        }
+        if unsafe { TAB } {
+            graph_draw.focus_next();
+            unsafe {
+                TAB = false;
+            }
+        }
+        if unsafe { SPLIT } {
+            graph_draw.toggle_split();
+            unsafe {
+                SPLIT = false;
+            }
+        }
+        if unsafe { PAGE } {
+            graph_draw.next_page();
+            unsafe {
+                PAGE = false;
+            }
+        }
+        if unsafe { THEME } {
+            println!("Theme: {}", theme::next_theme().name);
+            graph_draw.refresh_colors();
+            unsafe {
+                THEME = false;
+            }
+        }
+        if unsafe { GHOSTS } {
+            println!("Ghosts: {:?}", graph_draw.cycle_ghost_mode());
+            unsafe {
+                GHOSTS = false;
+            }
+        }
+        if unsafe { ROUTING } {
+            println!("Edge routing: {}", graph_draw.cycle_edge_routing().name());
+            unsafe {
+                ROUTING = false;
+            }
+        }
+        if unsafe { GLOBAL_FORWARD } {
+            graph_draw.global_forward();
+            unsafe {
+                GLOBAL_FORWARD = false;
+            }
+        }
+        if unsafe { GLOBAL_BACKWARD } {
+            graph_draw.global_backward();
+            unsafe {
+                GLOBAL_BACKWARD = false;
+            }
+        }
+        if unsafe { FOLLOW } {
+            graph_draw.follow_latest();
+            unsafe {
+                FOLLOW = false;
+            }
         }
 
         if unsafe { ENTER } {
@@ -192,10 +445,19 @@ fn main() {
         }
 
         unsafe {
-            gl::ClearColor(0.0, 0.0, 0.0, 0.5);
+            CAMERA_SHIFT = graph_draw.focused_tile().camera;
+            gl::Viewport(0, 0, DIMENSIONS.0 as i32, DIMENSIONS.1 as i32);
+            let (r, g, b) = theme::current().background;
+            gl::ClearColor(r, g, b, 0.5);
             gl::Clear(gl::COLOR_BUFFER_BIT);
-            c_side::render_text();
-            c_side::draw_sprites();
+            gl::Enable(gl::SCISSOR_TEST);
+        }
+        for (index, tile) in graph_draw.tiles().iter().enumerate() {
+            let focused = graph_draw.is_split() && index == graph_draw.focused_index();
+            render_tile(tile, focused);
+        }
+        unsafe {
+            gl::Disable(gl::SCISSOR_TEST);
         }
     }
 }
@@ -212,6 +474,19 @@ fn callback_resize(_: &mut glfw::Window, width: i32, height: i32) {
 fn handle_window_event(events: &GlfwReceiver<(f64, WindowEvent)>, window: &mut glfw::PWindow) {
     for (_, event) in glfw::flush_messages(events) {
         match event {
+            glfw::WindowEvent::CursorPos(x, y) => unsafe {
+                CURSOR = (x as f32, y as f32);
+            },
+            glfw::WindowEvent::MouseButton(MouseButton::Button1, Action::Press, _) => unsafe {
+                CLICK = true;
+                HELD = true;
+            },
+            glfw::WindowEvent::MouseButton(MouseButton::Button1, Action::Release, _) => unsafe {
+                HELD = false;
+            },
+            glfw::WindowEvent::Scroll(_, y) => unsafe {
+                SCROLL += y as f32;
+            },
             glfw::WindowEvent::Key(Key::Escape, _, Action::Press, _) => {
                 window.set_should_close(true)
             }
@@ -305,52 +580,77 @@ fn handle_window_event(events: &GlfwReceiver<(f64, WindowEvent)>, window: &mut g
             glfw::WindowEvent::Key(Key::Num9, _, Action::Release, _) => unsafe {
                 NUM9 = false;
             },
+            glfw::WindowEvent::Key(Key::Tab, _, Action::Press, _) => unsafe {
+                TAB = true;
+            },
+            glfw::WindowEvent::Key(Key::Tab, _, Action::Release, _) => unsafe {
+                TAB = false;
+            },
+            glfw::WindowEvent::Key(Key::S, _, Action::Press, _) => unsafe {
+                SPLIT = true;
+            },
+            glfw::WindowEvent::Key(Key::S, _, Action::Release, _) => unsafe {
+                SPLIT = false;
+            },
+            glfw::WindowEvent::Key(Key::P, _, Action::Press, _) => unsafe {
+                PAGE = true;
+            },
+            glfw::WindowEvent::Key(Key::P, _, Action::Release, _) => unsafe {
+                PAGE = false;
+            },
+            glfw::WindowEvent::Key(Key::T, _, Action::Press, _) => unsafe {
+                THEME = true;
+            },
+            glfw::WindowEvent::Key(Key::T, _, Action::Release, _) => unsafe {
+                THEME = false;
+            },
+            glfw::WindowEvent::Key(Key::G, _, Action::Press, _) => unsafe {
+                GHOSTS = true;
+            },
+            glfw::WindowEvent::Key(Key::G, _, Action::Release, _) => unsafe {
+                GHOSTS = false;
+            },
+            glfw::WindowEvent::Key(Key::R, _, Action::Press, _) => unsafe {
+                ROUTING = true;
+            },
+            glfw::WindowEvent::Key(Key::R, _, Action::Release, _) => unsafe {
+                ROUTING = false;
+            },
+            glfw::WindowEvent::Key(Key::RightBracket, _, Action::Press, _) => unsafe {
+                GLOBAL_FORWARD = true;
+            },
+            glfw::WindowEvent::Key(Key::RightBracket, _, Action::Release, _) => unsafe {
+                GLOBAL_FORWARD = false;
+            },
+            glfw::WindowEvent::Key(Key::LeftBracket, _, Action::Press, _) => unsafe {
+                GLOBAL_BACKWARD = true;
+            },
+            glfw::WindowEvent::Key(Key::LeftBracket, _, Action::Release, _) => unsafe {
+                GLOBAL_BACKWARD = false;
+            },
+            glfw::WindowEvent::Key(Key::F, _, Action::Press, _) => unsafe {
+                FOLLOW = true;
+            },
+            glfw::WindowEvent::Key(Key::F, _, Action::Release, _) => unsafe {
+                FOLLOW = false;
+            },
             _ => {}
         }
     }
 }
 
-pub fn left_pressed() {
-    unsafe {
-        resize_camera(DIMENSIONS.0, DIMENSIONS.1, -SHIFT_AMOUNT, 0.0);
-    }
-}
-
-pub fn right_pressed() {
-    unsafe {
-        resize_camera(DIMENSIONS.0, DIMENSIONS.1, SHIFT_AMOUNT, 0.0);
-    }
+pub fn left_pressed(graph_draw: &mut GraphDraw) {
+    graph_draw.pan_focused(-SHIFT_AMOUNT, 0.0);
 }
 
-pub fn up_pressed() {
-    unsafe {
-        resize_camera(DIMENSIONS.0, DIMENSIONS.1, 0.0, SHIFT_AMOUNT);
-    }
+pub fn right_pressed(graph_draw: &mut GraphDraw) {
+    graph_draw.pan_focused(SHIFT_AMOUNT, 0.0);
 }
 
-pub fn down_pressed() {
-    unsafe {
-        resize_camera(DIMENSIONS.0, DIMENSIONS.1, 0.0, -SHIFT_AMOUNT);
-    }
+pub fn up_pressed(graph_draw: &mut GraphDraw) {
+    graph_draw.pan_focused(0.0, SHIFT_AMOUNT);
 }
-// Simple smoke test for HashGrid
-fn run_hashgrid_smoke() {
-    use std::collections::HashSet;
-    println!("Running HashGrid smoke test...");
-    let screen = ((0.0f32, 0.0f32), (100.0f32, 100.0f32));
-    let mut hg: crate::hashgrid::HashGrid<u32> = HashGrid::new(screen, 10);
-
-    println!("Initial grid:\n{:#?}", hg);
-
-    // Insert rectangle centered at (15,15) with size 10x10 and key 1
-    let res = hg.insert_rectangle((15.0, 15.0), (10.0, 10.0), &1u32);
-    println!("After insert result: {:?}\n{:#?}", res, hg);
-
-    // Try moving the rectangle to (45,45)
-    let res_move = hg.move_rectangle((15.0, 15.0), (10.0, 10.0), (45.0, 45.0));
-    println!("After move result: {:?}\n{:#?}", res_move, hg);
 
-    // Remove rectangle at new location
-    let res_rem = hg.remove_rectangle((45.0, 45.0), (10.0, 10.0), &1u32);
-    println!("After remove result: {:?}\n{:#?}", res_rem, hg);
+pub fn down_pressed(graph_draw: &mut GraphDraw) {
+    graph_draw.pan_focused(0.0, -SHIFT_AMOUNT);
 }
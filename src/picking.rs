@@ -0,0 +1,86 @@
+use crate::hashgrid::HashGrid;
+use crate::tree::Point;
+use std::collections::HashSet;
+
+const NODE: u8 = 0;
+const EDGE: u8 = 1;
+pub const EDGE_REACH: f32 = 6.0; //how far from an edge a click still picks it
+
+type Key = (u8, usize, usize); //kind, node id or edge ends
+type Rectangle = ((f32, f32), (f32, f32)); //center, dimensions
+
+//what the cursor can land on
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Picked {
+  Node(usize),
+  Edge(usize, usize),
+}
+
+//bounding boxes of one listener's nodes and edges in world space, kept up to date as the layout moves them.
+//only narrows down what could be under a point, the exact test is left to the caller
+#[derive(Debug)]
+pub struct PickIndex {
+  grid: HashGrid<Key>,
+}
+
+impl PickIndex {
+  pub fn new(cell: u16) -> Self {
+    PickIndex {
+      grid: HashGrid::new(cell),
+    }
+  }
+
+  pub fn update_node(&mut self, id: usize, center: (f32, f32), half_size: (f32, f32)) {
+    self.update((NODE, id, id), (center, (2.0 * half_size.0, 2.0 * half_size.1)));
+  }
+
+  pub fn update_edge(&mut self, from_id: usize, to_id: usize, path: &[Point]) {
+    if path.is_empty() {
+      return;
+    }
+    let mut top_left = (f32::MAX, f32::MAX);
+    let mut bottom_right = (f32::MIN, f32::MIN);
+    for point in path {
+      top_left = (top_left.0.min(point.x), top_left.1.min(point.y));
+      bottom_right = (bottom_right.0.max(point.x), bottom_right.1.max(point.y));
+    }
+    let center = ((top_left.0 + bottom_right.0) / 2.0, (top_left.1 + bottom_right.1) / 2.0);
+    let dimensions = (
+      bottom_right.0 - top_left.0 + 2.0 * EDGE_REACH,
+      bottom_right.1 - top_left.1 + 2.0 * EDGE_REACH,
+    );
+    self.update((EDGE, from_id, to_id), (center, dimensions));
+  }
+
+  //boxes that did not move stay where they are
+  fn update(&mut self, key: Key, rectangle: Rectangle) {
+    if self.grid.rectangle(&key) == Some(rectangle) {
+      return;
+    }
+    self.grid.remove(&key);
+    self.grid.insert_rectangle(rectangle.0, rectangle.1, &key);
+  }
+
+  //forgets whatever is no longer in the graph
+  pub fn retain(&mut self, nodes: &HashSet<usize>, edges: &HashSet<(usize, usize)>) {
+    let gone: Vec<Key> = self.grid.items()
+      .filter(|&&(kind, first, second)| match kind {
+        NODE => !nodes.contains(&first),
+        _ => !edges.contains(&(first, second)),
+      })
+      .copied()
+      .collect();
+    for key in gone {
+      self.grid.remove(&key);
+    }
+  }
+
+  //everything whose box holds the point, nodes first and in id order
+  pub fn at(&self, point: (f32, f32)) -> Vec<Picked> {
+    let mut found: Vec<Key> = self.grid.query_range(point, (0.0, 0.0)).into_iter().collect();
+    found.sort();
+    found.into_iter()
+      .map(|(kind, first, second)| if kind == NODE { Picked::Node(first) } else { Picked::Edge(first, second) })
+      .collect()
+  }
+}
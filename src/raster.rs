@@ -0,0 +1,672 @@
+use crate::golden::replay_recording;
+use crate::routing::{self, EdgeRouting};
+use crate::events::read_trace;
+use crate::render::{Recording, Rgb, ShapeKind, Sprite, TextItem};
+use crate::theme;
+use ab_glyph::{point, Font, FontVec, PxScale, ScaleFont};
+use std::f32::consts::PI;
+use std::fs::File;
+use std::io::{self, Write};
+
+const SCREENSHOT_SIZE: (usize, usize) = (1920, 1080);
+const LAYOUT_ITERATIONS: usize = 2000; //upper bound, the layout usually settles long before
+const FONT_SIZE: f32 = 100.0; //text is measured at this size and then scaled to its box
+//screenshots look the same on every machine unless --font asks for another
+const BUNDLED_FONT: &[u8] = include_bytes!("../assets/fonts/DejaVuSansMono.ttf");
+
+//RGBA image the software renderer draws into, (0, 0) is the top left pixel
+pub struct Canvas {
+  pub width: usize,
+  pub height: usize,
+  pixels: Vec<[f32; 4]>,
+}
+
+impl Canvas {
+  pub fn new(width: usize, height: usize, background: Rgb) -> Self {
+    Canvas {
+      width,
+      height,
+      pixels: vec![[background.0, background.1, background.2, 1.0]; width * height],
+    }
+  }
+
+  //source over blending of one pixel with the given coverage
+  fn blend(&mut self, x: i32, y: i32, color: Rgb, coverage: f32) {
+    if x < 0 || y < 0 || x as usize >= self.width || y as usize >= self.height || coverage <= 0.0 {
+      return;
+    }
+    let alpha = coverage.min(1.0);
+    let pixel = &mut self.pixels[y as usize * self.width + x as usize];
+    pixel[0] = color.0 * alpha + pixel[0] * (1.0 - alpha);
+    pixel[1] = color.1 * alpha + pixel[1] * (1.0 - alpha);
+    pixel[2] = color.2 * alpha + pixel[2] * (1.0 - alpha);
+    pixel[3] = alpha + pixel[3] * (1.0 - alpha);
+  }
+
+  //fills the pixels of a box with coverage from a signed distance, negative is inside
+  fn fill_sdf(&mut self, min: (f32, f32), max: (f32, f32), color: Rgb, sdf: impl Fn(f32, f32) -> f32) {
+    let x_start = (min.0.floor() as i32 - 1).max(0);
+    let y_start = (min.1.floor() as i32 - 1).max(0);
+    let x_end = (max.0.ceil() as i32 + 1).min(self.width as i32 - 1);
+    let y_end = (max.1.ceil() as i32 + 1).min(self.height as i32 - 1);
+    for y in y_start..=y_end {
+      for x in x_start..=x_end {
+        let distance = sdf(x as f32 + 0.5, y as f32 + 0.5);
+        self.blend(x, y, color, (0.5 - distance).clamp(0.0, 1.0));
+      }
+    }
+  }
+
+  pub fn to_rgba8(&self) -> Vec<u8> {
+    self.pixels.iter()
+      .flat_map(|pixel| pixel.map(|channel| (channel.clamp(0.0, 1.0) * 255.0).round() as u8))
+      .collect()
+  }
+
+  pub fn save_png(&self, path: &str) -> io::Result<()> {
+    let mut file = File::create(path)?;
+    file.write_all(&encode_png(self.width as u32, self.height as u32, &self.to_rgba8()))
+  }
+}
+
+fn box_sdf(px: f32, py: f32, half_width: f32, half_height: f32) -> f32 {
+  let dx = px.abs() - half_width;
+  let dy = py.abs() - half_height;
+  let outside = (dx.max(0.0).powi(2) + dy.max(0.0).powi(2)).sqrt();
+  outside + dx.max(dy).min(0.0)
+}
+
+//world direction of an angle, angles follow the y reversed convention of tree.rs
+fn direction(angle: f32) -> (f32, f32) {
+  (angle.cos(), -angle.sin())
+}
+
+//draws the sprites and texts of a recording seen from camera (world position of the top left pixel)
+pub fn rasterize(recording: &Recording, camera: (f32, f32), size: (usize, usize), font: &FontVec) -> Canvas {
+  let mut canvas = Canvas::new(size.0, size.1, theme::current().background);
+
+  let mut sprites: Vec<_> = recording.sprites.iter().collect();
+  sprites.sort_by_key(|(id, _)| **id);
+  for (_, sprite) in sprites {
+    draw_sprite(&mut canvas, sprite, camera);
+  }
+
+  let mut texts: Vec<_> = recording.texts.iter().collect();
+  texts.sort_by_key(|(id, _)| **id);
+  for (_, text) in texts {
+    draw_text(&mut canvas, text, camera, recording.text_color, font);
+  }
+  canvas
+}
+
+pub fn draw_sprite(canvas: &mut Canvas, sprite: &Sprite, camera: (f32, f32)) {
+  let center = (sprite.x - camera.0, sprite.y - camera.1);
+  match sprite.kind {
+    ShapeKind::Circle => {
+      let radius = sprite.width;
+      let half_ring = sprite.thickness.min(radius) / 2.0;
+      let ring_center = radius - half_ring;
+      canvas.fill_sdf((center.0 - radius, center.1 - radius), (center.0 + radius, center.1 + radius),
+        sprite.color, |x, y| {
+          let distance = ((x - center.0).powi(2) + (y - center.1).powi(2)).sqrt();
+          (distance - ring_center).abs() - half_ring
+        });
+    }
+    ShapeKind::Square | ShapeKind::Rectangle => {
+      let half = (sprite.width, sprite.height);
+      let thickness = sprite.thickness.min(half.0).min(half.1);
+      canvas.fill_sdf((center.0 - half.0, center.1 - half.1), (center.0 + half.0, center.1 + half.1),
+        sprite.color, |x, y| {
+          let outer = box_sdf(x - center.0, y - center.1, half.0, half.1);
+          let inner = box_sdf(x - center.0, y - center.1, half.0 - thickness, half.1 - thickness);
+          outer.max(-inner)
+        });
+    }
+    ShapeKind::Line => {
+      let (dx, dy) = direction(sprite.thickness);
+      let half = (sprite.width, sprite.height / 2.0);
+      let reach = half.0 + half.1;
+      canvas.fill_sdf((center.0 - reach, center.1 - reach), (center.0 + reach, center.1 + reach),
+        sprite.color, |x, y| {
+          let local = (x - center.0, y - center.1);
+          let along = local.0 * dx + local.1 * dy;
+          let across = -local.0 * dy + local.1 * dx;
+          box_sdf(along, across, half.0, half.1.max(0.5))
+        });
+    }
+    ShapeKind::Triangle => {
+      //the arrow head is stored rotated a quarter turn from the line it sits on
+      let (dx, dy) = direction(sprite.thickness - PI / 2.0);
+      let (nx, ny) = (-dy, dx);
+      let half_height = sprite.height / 2.0;
+      let half_width = sprite.width / 2.0;
+      let tip = (center.0 + dx * half_height, center.1 + dy * half_height);
+      let left = (center.0 - dx * half_height + nx * half_width, center.1 - dy * half_height + ny * half_width);
+      let right = (center.0 - dx * half_height - nx * half_width, center.1 - dy * half_height - ny * half_width);
+      let corners = [tip, left, right];
+      let reach = half_width.max(half_height);
+      canvas.fill_sdf((center.0 - reach, center.1 - reach), (center.0 + reach, center.1 + reach),
+        sprite.color, |x, y| convex_sdf(&corners, x, y));
+    }
+  }
+}
+
+//distance to the edges of a convex polygon, exact inside and good enough outside for anti aliasing
+fn convex_sdf(corners: &[(f32, f32)], x: f32, y: f32) -> f32 {
+  let mut orientation = 0.0;
+  for i in 0..corners.len() {
+    let a = corners[i];
+    let b = corners[(i + 1) % corners.len()];
+    orientation += (b.0 - a.0) * (b.1 + a.1);
+  }
+  let sign = if orientation > 0.0 { 1.0 } else { -1.0 };
+  let mut distance = f32::MIN;
+  for i in 0..corners.len() {
+    let a = corners[i];
+    let b = corners[(i + 1) % corners.len()];
+    let length = ((b.0 - a.0).powi(2) + (b.1 - a.1).powi(2)).sqrt().max(f32::EPSILON);
+    let edge_distance = sign * ((b.0 - a.0) * (y - a.1) - (b.1 - a.1) * (x - a.0)) / length;
+    distance = distance.max(edge_distance);
+  }
+  distance
+}
+
+pub fn bundled_font() -> FontVec {
+  FontVec::try_from_vec(BUNDLED_FONT.to_vec()).expect("Error reading the bundled font")
+}
+
+pub fn load_font(path: &str) -> Result<FontVec, String> {
+  let bytes = std::fs::read(path).map_err(|e| format!("{}: {}", path, e))?;
+  FontVec::try_from_vec(bytes).map_err(|e| format!("{}: {}", path, e))
+}
+
+//text is scaled to fit its box like create_text_centered does, drawn unrotated into a coverage
+//block and sampled from there so rotated weights stay smooth
+pub fn draw_text(canvas: &mut Canvas, text: &TextItem, camera: (f32, f32), color: Rgb, font: &FontVec) {
+  let lines: Vec<&str> = text.text.lines().collect();
+  if text.max_size.0 <= 0 || text.max_size.1 <= 0 {
+    return;
+  }
+  let measured = font.as_scaled(PxScale::from(FONT_SIZE));
+  let line_width = |line: &str| line.chars().map(|character| measured.h_advance(font.glyph_id(character))).sum::<f32>();
+  let longest = lines.iter().map(|line| line_width(line)).fold(0.0, f32::max);
+  if longest <= 0.0 {
+    return;
+  }
+  let line_advance = measured.height() + measured.line_gap();
+  let block_height = lines.len() as f32 * line_advance - measured.line_gap();
+  let ratio = (text.max_size.0 as f32 / longest).min(text.max_size.1 as f32 / block_height);
+  let scaled = font.as_scaled(PxScale::from(FONT_SIZE * ratio));
+
+  //every line is centered in the block
+  let block = ((longest * ratio).ceil() as usize + 2, (block_height * ratio).ceil() as usize + 2);
+  let mut coverage = vec![0.0f32; block.0 * block.1];
+  for (index, line) in lines.iter().enumerate() {
+    let mut x = 1.0 + (longest - line_width(line)) * ratio / 2.0;
+    let baseline = 1.0 + index as f32 * line_advance * ratio + scaled.ascent();
+    for character in line.chars() {
+      let glyph_id = font.glyph_id(character);
+      let glyph = glyph_id.with_scale_and_position(scaled.scale(), point(x, baseline));
+      x += scaled.h_advance(glyph_id);
+      let Some(outlined) = font.outline_glyph(glyph) else { continue };
+      let bounds = outlined.px_bounds();
+      outlined.draw(|gx, gy, value| {
+        let (bx, by) = (bounds.min.x as i32 + gx as i32, bounds.min.y as i32 + gy as i32);
+        if bx >= 0 && by >= 0 && (bx as usize) < block.0 && (by as usize) < block.1 {
+          let cell = &mut coverage[by as usize * block.0 + bx as usize];
+          *cell = (*cell + value).min(1.0);
+        }
+      });
+    }
+  }
+  let sample = |x: f32, y: f32| -> f32 {
+    let (x0, y0) = (x.floor(), y.floor());
+    let at = |x: f32, y: f32| {
+      if x < 0.0 || y < 0.0 || x as usize >= block.0 || y as usize >= block.1 { 0.0 } else { coverage[y as usize * block.0 + x as usize] }
+    };
+    let (fx, fy) = (x - x0, y - y0);
+    (at(x0, y0) * (1.0 - fx) + at(x0 + 1.0, y0) * fx) * (1.0 - fy) + (at(x0, y0 + 1.0) * (1.0 - fx) + at(x0 + 1.0, y0 + 1.0) * fx) * fy
+  };
+
+  let center = (text.center.0 as f32 - camera.0, text.center.1 as f32 - camera.1);
+  let (dx, dy) = direction(text.rotation);
+  let half = (block.0 as f32 / 2.0, block.1 as f32 / 2.0);
+  let reach = (half.0 * half.0 + half.1 * half.1).sqrt();
+  let x_start = ((center.0 - reach).floor() as i32).max(0);
+  let y_start = ((center.1 - reach).floor() as i32).max(0);
+  let x_end = ((center.0 + reach).ceil() as i32).min(canvas.width as i32 - 1);
+  let y_end = ((center.1 + reach).ceil() as i32).min(canvas.height as i32 - 1);
+  for y in y_start..=y_end {
+    for x in x_start..=x_end {
+      let (px, py) = (x as f32 + 0.5 - center.0, y as f32 + 0.5 - center.1);
+      //back into the unrotated block, whose pixel centers sit at half steps
+      let along = px * dx + py * dy + half.0 - 0.5;
+      let across = -px * dy + py * dx + half.1 - 0.5;
+      canvas.blend(x, y, color, sample(along, across));
+    }
+  }
+}
+
+/* ---- png encoding ---- */
+
+fn crc32(bytes: &[u8]) -> u32 {
+  let mut crc = 0xFFFF_FFFFu32;
+  for &byte in bytes {
+    crc ^= byte as u32;
+    for _ in 0..8 {
+      crc = if crc & 1 != 0 { (crc >> 1) ^ 0xEDB8_8320 } else { crc >> 1 };
+    }
+  }
+  !crc
+}
+
+fn adler32(bytes: &[u8]) -> u32 {
+  let (mut a, mut b) = (1u32, 0u32);
+  for &byte in bytes {
+    a = (a + byte as u32) % 65521;
+    b = (b + a) % 65521;
+  }
+  (b << 16) | a
+}
+
+struct BitWriter {
+  bytes: Vec<u8>,
+  current: u32,
+  filled: u32,
+}
+
+impl BitWriter {
+  fn write_bits(&mut self, value: u32, count: u32) {
+    for i in 0..count {
+      self.current |= ((value >> i) & 1) << self.filled;
+      self.filled += 1;
+      if self.filled == 8 {
+        self.bytes.push(self.current as u8);
+        self.current = 0;
+        self.filled = 0;
+      }
+    }
+  }
+
+  //huffman codes go out most significant bit first
+  fn write_code(&mut self, code: u32, length: u32) {
+    for i in (0..length).rev() {
+      self.write_bits((code >> i) & 1, 1);
+    }
+  }
+
+  fn finish(mut self) -> Vec<u8> {
+    if self.filled > 0 {
+      self.bytes.push(self.current as u8);
+    }
+    self.bytes
+  }
+}
+
+const LENGTH_BASES: [u32; 29] = [3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67,
+  83, 99, 115, 131, 163, 195, 227, 258];
+const LENGTH_EXTRA: [u32; 29] = [0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5,
+  5, 5, 0];
+
+fn write_literal_length(writer: &mut BitWriter, symbol: u32) {
+  match symbol {
+    0..=143 => writer.write_code(0x30 + symbol, 8),
+    144..=255 => writer.write_code(0x190 + symbol - 144, 9),
+    256..=279 => writer.write_code(symbol - 256, 7),
+    _ => writer.write_code(0xC0 + symbol - 280, 8),
+  }
+}
+
+//single fixed huffman block that only looks back one pixel, which is all flat backgrounds need
+fn deflate(data: &[u8]) -> Vec<u8> {
+  const DISTANCE: usize = 4;
+  let mut writer = BitWriter { bytes: Vec::new(), current: 0, filled: 0 };
+  writer.write_bits(1, 1); //final block
+  writer.write_bits(1, 2); //fixed huffman codes
+
+  let mut i = 0;
+  while i < data.len() {
+    let mut run = 0;
+    if i >= DISTANCE {
+      while run < 258 && i + run < data.len() && data[i + run] == data[i + run - DISTANCE] {
+        run += 1;
+      }
+    }
+    if run >= 3 {
+      let code = LENGTH_BASES.iter().rposition(|&base| base as usize <= run).unwrap();
+      write_literal_length(&mut writer, 257 + code as u32);
+      writer.write_bits(run as u32 - LENGTH_BASES[code], LENGTH_EXTRA[code]);
+      writer.write_code(DISTANCE as u32 - 1, 5);
+      i += run;
+    } else {
+      write_literal_length(&mut writer, data[i] as u32);
+      i += 1;
+    }
+  }
+  write_literal_length(&mut writer, 256);
+  writer.finish()
+}
+
+fn png_chunk(out: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+  out.extend_from_slice(&(data.len() as u32).to_be_bytes());
+  let mut checked = Vec::with_capacity(data.len() + 4);
+  checked.extend_from_slice(kind);
+  checked.extend_from_slice(data);
+  out.extend_from_slice(&checked);
+  out.extend_from_slice(&crc32(&checked).to_be_bytes());
+}
+
+pub fn encode_png(width: u32, height: u32, rgba: &[u8]) -> Vec<u8> {
+  let stride = width as usize * 4;
+  let mut raw = Vec::with_capacity((stride + 1) * height as usize);
+  for row in rgba.chunks(stride) {
+    raw.push(0); //no filter
+    raw.extend_from_slice(row);
+  }
+
+  let mut zlib = vec![0x78, 0x01];
+  zlib.extend(deflate(&raw));
+  zlib.extend_from_slice(&adler32(&raw).to_be_bytes());
+
+  let mut header = Vec::with_capacity(13);
+  header.extend_from_slice(&width.to_be_bytes());
+  header.extend_from_slice(&height.to_be_bytes());
+  header.extend_from_slice(&[8, 6, 0, 0, 0]); //8 bit rgba, no interlace
+
+  let mut png = vec![0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A];
+  png_chunk(&mut png, b"IHDR", &header);
+  png_chunk(&mut png, b"IDAT", &zlib);
+  png_chunk(&mut png, b"IEND", &[]);
+  png
+}
+
+pub fn option_value<'a>(args: &'a [String], name: &str) -> Option<&'a str> {
+  args.iter().position(|arg| arg == name).and_then(|i| args.get(i + 1)).map(String::as_str)
+}
+
+//--screenshot <trace> <listener> <step> <png> [--size WxH] [--layout iterations] [--theme name] [--routing name] [--font ttf]
+pub fn run_screenshot_command(args: &[String]) -> i32 {
+  if args.len() < 4 {
+    println!("usage: --screenshot <trace> <listener> <step> <png> [--size WxH] [--layout iterations] [--theme name] [--routing name] [--font ttf]");
+    return 2;
+  }
+  let font = match option_value(args, "--font").map(load_font) {
+    None => bundled_font(),
+    Some(Ok(font)) => font,
+    Some(Err(e)) => {
+      println!("Could not read the font {}", e);
+      return 2;
+    }
+  };
+  if let Some(name) = option_value(args, "--theme")
+    && !theme::set_theme(name)
+  {
+    println!("Unknown theme {:?}, pick one of {:?}", name, theme::theme_names());
+    return 2;
+  }
+  let routing = match option_value(args, "--routing") {
+    None => EdgeRouting::Straight,
+    Some(name) => match EdgeRouting::from_name(name) {
+      Some(routing) => routing,
+      None => {
+        println!("Unknown routing {:?}, pick one of {:?}", name, routing::routing_names());
+        return 2;
+      }
+    },
+  };
+  let root = match read_trace(&args[0]) {
+    Ok(root) => root,
+    Err(e) => {
+      println!("Could not read {}: {}", args[0], e);
+      return 2;
+    }
+  };
+  let (Ok(listener_id), Ok(step)) = (args[1].parse::<usize>(), args[2].parse::<usize>()) else {
+    println!("listener and step must be numbers");
+    return 2;
+  };
+  let size = match option_value(args, "--size") {
+    None => SCREENSHOT_SIZE,
+    Some(text) => match text.split_once('x').map(|(w, h)| (w.parse::<usize>(), h.parse::<usize>())) {
+      Some((Ok(width), Ok(height))) if width > 0 && height > 0 => (width, height),
+      _ => {
+        println!("--size must look like 1920x1080");
+        return 2;
+      }
+    },
+  };
+  let iterations = match option_value(args, "--layout").map(str::parse::<usize>) {
+    None => LAYOUT_ITERATIONS,
+    Some(Ok(iterations)) => iterations,
+    Some(Err(_)) => {
+      println!("--layout must be a number");
+      return 2;
+    }
+  };
+
+  let (recording, camera) = replay_recording(&root, listener_id, step, iterations, routing);
+  //keep the layout centered when the picture is not the size of the window
+  let camera = (
+    camera.0 + (SCREENSHOT_SIZE.0 as f32 - size.0 as f32) / 2.0,
+    camera.1 + (SCREENSHOT_SIZE.1 as f32 - size.1 as f32) / 2.0,
+  );
+  let canvas = rasterize(&recording.borrow(), camera, size, &font);
+  match canvas.save_png(&args[3]) {
+    Ok(()) => {
+      println!("wrote {} ({}x{})", args[3], size.0, size.1);
+      0
+    }
+    Err(e) => {
+      println!("Could not write {}: {}", args[3], e);
+      1
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  const LENGTH_CODES: usize = 29;
+  const DISTANCE_BASES: [u32; 30] = [1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025,
+    1537, 2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577];
+
+  struct BitReader<'a> {
+    bytes: &'a [u8],
+    at: usize, //in bits
+  }
+
+  impl BitReader<'_> {
+    fn bits(&mut self, count: u32) -> u32 {
+      let mut value = 0;
+      for i in 0..count {
+        let bit = (self.bytes[self.at / 8] >> (self.at % 8)) & 1;
+        value |= (bit as u32) << i;
+        self.at += 1;
+      }
+      value
+    }
+
+    fn code(&mut self, length: u32) -> u32 {
+      (0..length).fold(0, |code, _| (code << 1) | self.bits(1))
+    }
+
+    //the fixed literal/length code, 7 to 9 bits long
+    fn literal_length(&mut self) -> u32 {
+      let code = self.code(7);
+      if code < 24 {
+        return 256 + code;
+      }
+      let code = (code << 1) | self.bits(1);
+      match code {
+        0x30..=0xBF => code - 0x30,
+        0xC0..=0xC7 => 280 + code - 0xC0,
+        _ => 144 + ((code << 1) | self.bits(1)) - 0x190,
+      }
+    }
+  }
+
+  //enough of inflate for what deflate writes, one final block with the fixed codes
+  fn inflate(bytes: &[u8]) -> Vec<u8> {
+    let mut reader = BitReader { bytes, at: 0 };
+    assert_eq!(reader.bits(1), 1, "not the final block");
+    assert_eq!(reader.bits(2), 1, "not the fixed codes");
+    let mut out: Vec<u8> = Vec::new();
+    loop {
+      let symbol = reader.literal_length();
+      match symbol {
+        0..=255 => out.push(symbol as u8),
+        256 => break,
+        _ => {
+          let code = symbol as usize - 257;
+          assert!(code < LENGTH_CODES, "length code {}", symbol);
+          let length = LENGTH_BASES[code] + reader.bits(LENGTH_EXTRA[code]);
+          let distance_code = reader.code(5) as usize;
+          let extra = (distance_code as u32 / 2).saturating_sub(1);
+          let distance = (DISTANCE_BASES[distance_code] + reader.bits(extra)) as usize;
+          for _ in 0..length {
+            out.push(out[out.len() - distance]);
+          }
+        }
+      }
+    }
+    out
+  }
+
+  //checks every chunk's crc and the zlib checksum, returns the size and the rgba pixels
+  fn decode_png(png: &[u8]) -> (u32, u32, Vec<u8>) {
+    assert_eq!(&png[..8], &[0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A]);
+    let mut chunks = Vec::new();
+    let mut at = 8;
+    while at < png.len() {
+      let length = u32::from_be_bytes(png[at..at + 4].try_into().unwrap()) as usize;
+      let checked = &png[at + 4..at + 8 + length];
+      let crc = u32::from_be_bytes(png[at + 8 + length..at + 12 + length].try_into().unwrap());
+      assert_eq!(crc32(checked), crc, "crc of {:?}", String::from_utf8_lossy(&checked[..4]));
+      chunks.push((&checked[..4], &checked[4..]));
+      at += 12 + length;
+    }
+    let kinds: Vec<&[u8]> = chunks.iter().map(|(kind, _)| *kind).collect();
+    assert_eq!(kinds, [b"IHDR".as_slice(), b"IDAT", b"IEND"]);
+
+    let header = chunks[0].1;
+    let width = u32::from_be_bytes(header[0..4].try_into().unwrap());
+    let height = u32::from_be_bytes(header[4..8].try_into().unwrap());
+    assert_eq!(&header[8..], &[8, 6, 0, 0, 0]);
+
+    let zlib = chunks[1].1;
+    assert_eq!((zlib[0] as u16 * 256 + zlib[1] as u16) % 31, 0, "bad zlib header");
+    let raw = inflate(&zlib[2..zlib.len() - 4]);
+    assert_eq!(adler32(&raw).to_be_bytes(), zlib[zlib.len() - 4..]);
+    let stride = width as usize * 4 + 1;
+    assert_eq!(raw.len(), stride * height as usize);
+    let mut rgba = Vec::new();
+    for row in raw.chunks(stride) {
+      assert_eq!(row[0], 0, "filtered row");
+      rgba.extend_from_slice(&row[1..]);
+    }
+    (width, height, rgba)
+  }
+
+  #[test]
+  fn checksums_match_known_values() {
+    assert_eq!(crc32(b""), 0);
+    assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+    assert_eq!(adler32(b""), 1);
+    assert_eq!(adler32(b"Wikipedia"), 0x11E6_0398);
+  }
+
+  #[test]
+  fn pngs_decode_back_to_their_pixels() {
+    let flat = vec![40u8; 7 * 5 * 4];
+    let noise: Vec<u8> = (0..13 * 11 * 4u32).map(|i| (i.wrapping_mul(2_654_435_761) >> 13) as u8).collect();
+    //runs longer than a single length code reaches, and every byte value as a literal
+    let long_runs: Vec<u8> = (0..300 * 2 * 4u32).map(|i| if i < 1200 { 7 } else { (i % 256) as u8 }).collect();
+    for (width, height, rgba) in [(7, 5, flat), (13, 11, noise), (300, 2, long_runs), (1, 1, vec![1, 2, 3, 4])] {
+      let png = encode_png(width, height, &rgba);
+      assert_eq!(decode_png(&png), (width, height, rgba));
+    }
+  }
+
+  fn pixel(canvas: &Canvas, x: usize, y: usize) -> [u8; 4] {
+    let rgba = canvas.to_rgba8();
+    let at = (y * canvas.width + x) * 4;
+    rgba[at..at + 4].try_into().unwrap()
+  }
+
+  const RED: Rgb = (1.0, 0.0, 0.0);
+  const BACKGROUND: [u8; 4] = [0, 0, 0, 255];
+  const FILLED: [u8; 4] = [255, 0, 0, 255];
+
+  #[test]
+  fn outlines_are_drawn_and_the_inside_is_left_alone() {
+    let mut canvas = Canvas::new(100, 100, (0.0, 0.0, 0.0));
+    let sprite = |kind, x, y, width, height, thickness| Sprite { kind, x, y, width, height, thickness, color: RED };
+    draw_sprite(&mut canvas, &sprite(ShapeKind::Circle, 30.0, 30.0, 20.0, 20.0, 4.0), (0.0, 0.0));
+    draw_sprite(&mut canvas, &sprite(ShapeKind::Rectangle, 75.0, 75.0, 15.0, 10.0, 3.0), (0.0, 0.0));
+    assert_eq!(pixel(&canvas, 30, 30), BACKGROUND);
+    assert_eq!(pixel(&canvas, 30, 11), FILLED);
+    assert_eq!(pixel(&canvas, 48, 30), FILLED);
+    assert_eq!(pixel(&canvas, 30, 55), BACKGROUND);
+    assert_eq!(pixel(&canvas, 75, 75), BACKGROUND);
+    assert_eq!(pixel(&canvas, 61, 75), FILLED);
+    assert_eq!(pixel(&canvas, 75, 66), FILLED);
+    assert_eq!(pixel(&canvas, 75, 88), BACKGROUND);
+
+    //the camera moves everything the other way
+    let mut moved = Canvas::new(100, 100, (0.0, 0.0, 0.0));
+    draw_sprite(&mut moved, &sprite(ShapeKind::Circle, 40.0, 50.0, 20.0, 20.0, 4.0), (10.0, 20.0));
+    assert_eq!(pixel(&moved, 30, 11), FILLED);
+  }
+
+  #[test]
+  fn lines_follow_their_angle() {
+    let mut canvas = Canvas::new(100, 100, (0.0, 0.0, 0.0));
+    //angles go counterclockwise with y pointing down, a quarter turn is straight up
+    let line = Sprite { kind: ShapeKind::Line, x: 50.0, y: 50.0, width: 40.0, height: 4.0, thickness: PI / 2.0, color: RED };
+    draw_sprite(&mut canvas, &line, (0.0, 0.0));
+    for y in [12, 50, 88] {
+      assert_eq!(pixel(&canvas, 50, y), FILLED, "y {}", y);
+    }
+    assert_eq!(pixel(&canvas, 60, 50), BACKGROUND);
+    assert_eq!(pixel(&canvas, 50, 95), BACKGROUND);
+  }
+
+  #[test]
+  fn text_stays_in_its_box_in_the_recorded_color() {
+    let mut recording = Recording::default();
+    recording.text_color = (0.0, 1.0, 0.0);
+    let text = TextItem { text: String::from("42"), center: (50, 40), max_size: (40, 20), rotation: 0.0 };
+    recording.texts.insert(0, text);
+    let canvas = rasterize(&recording, (0.0, 0.0), (100, 80), &bundled_font());
+    let mut inked = Vec::new();
+    for (at, rgba) in canvas.to_rgba8().chunks(4).enumerate() {
+      assert_eq!((rgba[0], rgba[2]), (0, 0));
+      if rgba[1] > 0 {
+        inked.push((at % 100, at / 100));
+      }
+    }
+    assert!(inked.len() > 40, "only {} pixels of text", inked.len());
+    assert!(inked.iter().all(|&(x, y)| (29..=71).contains(&x) && (29..=51).contains(&y)), "text left its box");
+  }
+
+  #[test]
+  fn screenshots_are_written_with_the_bundled_font() {
+    let path = std::env::temp_dir().join(format!("parse_listen_screenshot_{}.png", std::process::id()));
+    let path = path.to_string_lossy().into_owned();
+    let args: Vec<String> = ["traces/bst.json", "0", "2", &path, "--size", "320x200", "--layout", "0"]
+      .iter()
+      .map(|arg| arg.to_string())
+      .collect();
+    let status = run_screenshot_command(&args);
+    let png = std::fs::read(&path);
+    let _ = std::fs::remove_file(&path);
+    assert_eq!(status, 0);
+    let (width, height, rgba) = decode_png(&png.unwrap());
+    assert_eq!((width, height), (320, 200));
+    assert!(rgba.chunks(4).any(|pixel| pixel[..3] != rgba[..3]), "nothing was drawn");
+
+    let missing = [&args[..4], &[String::from("--font"), String::from("/nonexistent.ttf")]].concat();
+    assert_eq!(run_screenshot_command(&missing), 2);
+  }
+}
@@ -0,0 +1,364 @@
+use crate::c_side;
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::ffi::CString;
+use std::rc::Rc;
+
+pub type Handle = u32;
+pub type Rgb = (f32, f32, f32);
+
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+pub enum ShapeKind {
+  Circle,
+  Square,
+  Rectangle,
+  Line,
+  Triangle,
+}
+
+//same layout the c++ side keeps, lines and triangles store their angle in thickness
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Sprite {
+  pub kind: ShapeKind,
+  pub x: f32,
+  pub y: f32,
+  pub width: f32,
+  pub height: f32,
+  pub thickness: f32,
+  pub color: Rgb,
+}
+
+//what override_sprite changes, the kind stays the one the sprite was made with
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct SpriteFrame {
+  pub x: f32,
+  pub y: f32,
+  pub width: f32,
+  pub height: f32,
+  pub thickness: f32,
+  pub color: Rgb,
+}
+
+impl Sprite {
+  pub fn frame(&self) -> SpriteFrame {
+    SpriteFrame { x: self.x, y: self.y, width: self.width, height: self.height, thickness: self.thickness, color: self.color }
+  }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TextItem {
+  pub text: String,
+  pub center: (i32, i32),
+  pub max_size: (i32, i32),
+  pub rotation: f32,
+}
+
+//everything tree::Node and tree::Line need to put shapes and text on screen
+pub trait RenderBackend {
+  fn new_circle(&mut self, x: f32, y: f32, radius: f32, thickness: f32, color: Rgb) -> Handle;
+  fn new_square(&mut self, x: f32, y: f32, width: f32, thickness: f32, color: Rgb) -> Handle;
+  fn new_rectangle(&mut self, x: f32, y: f32, width: f32, height: f32, thickness: f32, color: Rgb) -> Handle;
+  fn new_line(&mut self, x: f32, y: f32, width: f32, height: f32, angle: f32, color: Rgb) -> Handle;
+  fn new_triangle(&mut self, x: f32, y: f32, width: f32, height: f32, angle: f32, color: Rgb) -> Handle;
+
+  fn override_sprite(&mut self, id: Handle, frame: SpriteFrame);
+  fn move_sprite(&mut self, id: Handle, x: f32, y: f32);
+  fn scale_sprite(&mut self, id: Handle, width: f32, height: f32, thickness: f32);
+  fn remove_sprite(&mut self, id: Handle);
+  fn sprite(&self, id: Handle) -> Sprite;
+
+  fn create_text_centered(&mut self, text: &str, center_x: i32, center_y: i32,
+    max_width: i32, max_height: i32, rotation: f32) -> Handle;
+  fn move_text(&mut self, id: Handle, center_x: i32, center_y: i32);
+  fn rotate_text(&mut self, id: Handle, angle: f32);
+  fn remove_text(&mut self, id: Handle);
+  fn text(&self, id: Handle) -> String;
+  //every text is drawn in one color, the one set last
+  fn color_text(&mut self, color: Rgb);
+  //text changes are batched until this is called
+  fn flush_text(&mut self);
+}
+
+thread_local! {
+  static BACKEND: RefCell<Box<dyn RenderBackend>> = RefCell::new(Box::new(GlBackend::default()));
+}
+
+//swaps the backend used by every drawing call, returns the previous one
+pub fn set_backend(backend: Box<dyn RenderBackend>) -> Box<dyn RenderBackend> {
+  BACKEND.with(|cell| std::mem::replace(&mut *cell.borrow_mut(), backend))
+}
+
+pub fn with_backend<R>(f: impl FnOnce(&mut dyn RenderBackend) -> R) -> R {
+  BACKEND.with(|cell| f(cell.borrow_mut().as_mut()))
+}
+
+//what the c++ font renderer draws every text with
+pub const FONT_PATH: &str = "/usr/share/fonts/TTF/CaskaydiaCoveNerdFontMono-Regular.ttf";
+
+//the OpenGL renderer living on the c++ side
+#[derive(Default)]
+pub struct GlBackend {
+  kinds: HashMap<Handle, ShapeKind>,
+  texts: HashMap<Handle, String>,
+}
+
+impl GlBackend {
+  fn track(&mut self, id: Handle, kind: ShapeKind) -> Handle {
+    self.kinds.insert(id, kind);
+    id
+  }
+}
+
+impl RenderBackend for GlBackend {
+  fn new_circle(&mut self, x: f32, y: f32, radius: f32, thickness: f32, color: Rgb) -> Handle {
+    let id = unsafe { c_side::new_circle(x, y, radius, thickness, color.0, color.1, color.2) };
+    self.track(id, ShapeKind::Circle)
+  }
+
+  fn new_square(&mut self, x: f32, y: f32, width: f32, thickness: f32, color: Rgb) -> Handle {
+    let id = unsafe { c_side::new_square(x, y, width, thickness, color.0, color.1, color.2) };
+    self.track(id, ShapeKind::Square)
+  }
+
+  fn new_rectangle(&mut self, x: f32, y: f32, width: f32, height: f32, thickness: f32, color: Rgb) -> Handle {
+    let id = unsafe { c_side::new_rectangle(x, y, width, height, thickness, color.0, color.1, color.2) };
+    self.track(id, ShapeKind::Rectangle)
+  }
+
+  fn new_line(&mut self, x: f32, y: f32, width: f32, height: f32, angle: f32, color: Rgb) -> Handle {
+    let id = unsafe { c_side::new_line(x, y, width, height, angle, color.0, color.1, color.2) };
+    self.track(id, ShapeKind::Line)
+  }
+
+  fn new_triangle(&mut self, x: f32, y: f32, width: f32, height: f32, angle: f32, color: Rgb) -> Handle {
+    let id = unsafe { c_side::new_triangle(x, y, width, height, angle, color.0, color.1, color.2) };
+    self.track(id, ShapeKind::Triangle)
+  }
+
+  fn override_sprite(&mut self, id: Handle, frame: SpriteFrame) {
+    let SpriteFrame { x, y, width, height, thickness, color } = frame;
+    unsafe { c_side::override_sprite(id, x, y, width, height, thickness, color.0, color.1, color.2) };
+  }
+
+  fn move_sprite(&mut self, id: Handle, x: f32, y: f32) {
+    unsafe { c_side::move_sprite(id, x, y) };
+  }
+
+  fn scale_sprite(&mut self, id: Handle, width: f32, height: f32, thickness: f32) {
+    unsafe { c_side::scale_sprite(id, width, height, thickness) };
+  }
+
+  fn remove_sprite(&mut self, id: Handle) {
+    self.kinds.remove(&id);
+    unsafe { c_side::remove_sprite(id) };
+  }
+
+  fn sprite(&self, id: Handle) -> Sprite {
+    let sprite_obj = unsafe { *c_side::get_sprite(id) };
+    Sprite {
+      kind: *self.kinds.get(&id).expect("Unknown sprite"),
+      x: sprite_obj.x,
+      y: sprite_obj.y,
+      width: sprite_obj.width,
+      height: sprite_obj.height,
+      thickness: sprite_obj.thickness,
+      color: (sprite_obj.color[0], sprite_obj.color[1], sprite_obj.color[2]),
+    }
+  }
+
+  fn create_text_centered(&mut self, text: &str, center_x: i32, center_y: i32,
+    max_width: i32, max_height: i32, rotation: f32) -> Handle {
+    let send_text = CString::new(text).expect("Error cstr");
+    let id = unsafe {
+      c_side::create_text_centered(send_text.as_ptr(), center_x, center_y, max_width, max_height, rotation)
+    };
+    self.texts.insert(id, text.to_string());
+    id
+  }
+
+  fn move_text(&mut self, id: Handle, center_x: i32, center_y: i32) {
+    unsafe { c_side::move_text(id, center_x, center_y) };
+  }
+
+  fn rotate_text(&mut self, id: Handle, angle: f32) {
+    unsafe { c_side::rotate_text(id, angle) };
+  }
+
+  fn remove_text(&mut self, id: Handle) {
+    self.texts.remove(&id);
+    unsafe { c_side::remove_text(id) };
+  }
+
+  fn text(&self, id: Handle) -> String {
+    self.texts.get(&id).cloned().unwrap_or_default()
+  }
+
+  fn color_text(&mut self, color: Rgb) {
+    unsafe { c_side::color_text(color.0, color.1, color.2) };
+  }
+
+  fn flush_text(&mut self) {
+    unsafe { c_side::load_all_text_vbo() };
+  }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum DrawCall {
+  NewSprite { id: Handle, sprite: Sprite },
+  OverrideSprite { id: Handle, sprite: Sprite },
+  MoveSprite { id: Handle, x: f32, y: f32 },
+  ScaleSprite { id: Handle, width: f32, height: f32, thickness: f32 },
+  RemoveSprite { id: Handle },
+  NewText { id: Handle, text: TextItem },
+  MoveText { id: Handle, center: (i32, i32) },
+  RotateText { id: Handle, angle: f32 },
+  RemoveText { id: Handle },
+  ColorText { color: Rgb },
+}
+
+//keeps every live shape and text in memory instead of drawing them,
+//calls holds the order they were issued in
+#[derive(Debug, Default)]
+pub struct Recording {
+  pub sprites: HashMap<Handle, Sprite>,
+  pub texts: HashMap<Handle, TextItem>,
+  pub text_color: Rgb,
+  pub calls: Vec<DrawCall>,
+  next_sprite: Handle,
+  next_text: Handle,
+}
+
+//the backend is moved into the thread local, the shared recording stays readable
+#[derive(Default)]
+pub struct RecordingBackend {
+  recording: Rc<RefCell<Recording>>,
+}
+
+impl RecordingBackend {
+  pub fn new() -> Self {
+    RecordingBackend::default()
+  }
+
+  pub fn recording(&self) -> Rc<RefCell<Recording>> {
+    Rc::clone(&self.recording)
+  }
+
+  fn add_sprite(&mut self, sprite: Sprite) -> Handle {
+    let mut recording = self.recording.borrow_mut();
+    let id = recording.next_sprite;
+    recording.next_sprite += 1;
+    recording.sprites.insert(id, sprite);
+    recording.calls.push(DrawCall::NewSprite { id, sprite });
+    id
+  }
+
+  fn sprite_mut(&mut self, id: Handle, call: DrawCall, f: impl FnOnce(&mut Sprite)) {
+    let mut recording = self.recording.borrow_mut();
+    f(recording.sprites.get_mut(&id).expect("Unknown sprite"));
+    recording.calls.push(call);
+  }
+
+  fn text_mut(&mut self, id: Handle, call: DrawCall, f: impl FnOnce(&mut TextItem)) {
+    let mut recording = self.recording.borrow_mut();
+    f(recording.texts.get_mut(&id).expect("Unknown text"));
+    recording.calls.push(call);
+  }
+}
+
+impl RenderBackend for RecordingBackend {
+  fn new_circle(&mut self, x: f32, y: f32, radius: f32, thickness: f32, color: Rgb) -> Handle {
+    self.add_sprite(Sprite { kind: ShapeKind::Circle, x, y, width: radius, height: radius, thickness, color })
+  }
+
+  fn new_square(&mut self, x: f32, y: f32, width: f32, thickness: f32, color: Rgb) -> Handle {
+    self.add_sprite(Sprite { kind: ShapeKind::Square, x, y, width, height: width, thickness, color })
+  }
+
+  fn new_rectangle(&mut self, x: f32, y: f32, width: f32, height: f32, thickness: f32, color: Rgb) -> Handle {
+    self.add_sprite(Sprite { kind: ShapeKind::Rectangle, x, y, width, height, thickness, color })
+  }
+
+  fn new_line(&mut self, x: f32, y: f32, width: f32, height: f32, angle: f32, color: Rgb) -> Handle {
+    self.add_sprite(Sprite { kind: ShapeKind::Line, x, y, width, height, thickness: angle, color })
+  }
+
+  fn new_triangle(&mut self, x: f32, y: f32, width: f32, height: f32, angle: f32, color: Rgb) -> Handle {
+    self.add_sprite(Sprite { kind: ShapeKind::Triangle, x, y, width, height, thickness: angle, color })
+  }
+
+  fn override_sprite(&mut self, id: Handle, frame: SpriteFrame) {
+    let SpriteFrame { x, y, width, height, thickness, color } = frame;
+    let kind = self.sprite(id).kind;
+    let sprite = Sprite { kind, x, y, width, height, thickness, color };
+    self.sprite_mut(id, DrawCall::OverrideSprite { id, sprite }, |old| *old = sprite);
+  }
+
+  fn move_sprite(&mut self, id: Handle, x: f32, y: f32) {
+    self.sprite_mut(id, DrawCall::MoveSprite { id, x, y }, |sprite| {
+      sprite.x = x;
+      sprite.y = y;
+    });
+  }
+
+  fn scale_sprite(&mut self, id: Handle, width: f32, height: f32, thickness: f32) {
+    self.sprite_mut(id, DrawCall::ScaleSprite { id, width, height, thickness }, |sprite| {
+      sprite.width = width;
+      sprite.height = height;
+      sprite.thickness = thickness;
+    });
+  }
+
+  fn remove_sprite(&mut self, id: Handle) {
+    let mut recording = self.recording.borrow_mut();
+    recording.sprites.remove(&id);
+    recording.calls.push(DrawCall::RemoveSprite { id });
+  }
+
+  fn sprite(&self, id: Handle) -> Sprite {
+    *self.recording.borrow().sprites.get(&id).expect("Unknown sprite")
+  }
+
+  fn create_text_centered(&mut self, text: &str, center_x: i32, center_y: i32,
+    max_width: i32, max_height: i32, rotation: f32) -> Handle {
+    let mut recording = self.recording.borrow_mut();
+    let id = recording.next_text;
+    recording.next_text += 1;
+    let item = TextItem {
+      text: text.to_string(),
+      center: (center_x, center_y),
+      max_size: (max_width, max_height),
+      rotation,
+    };
+    recording.texts.insert(id, item.clone());
+    recording.calls.push(DrawCall::NewText { id, text: item });
+    id
+  }
+
+  fn move_text(&mut self, id: Handle, center_x: i32, center_y: i32) {
+    let center = (center_x, center_y);
+    self.text_mut(id, DrawCall::MoveText { id, center }, |text| text.center = center);
+  }
+
+  fn rotate_text(&mut self, id: Handle, angle: f32) {
+    self.text_mut(id, DrawCall::RotateText { id, angle }, |text| text.rotation = angle);
+  }
+
+  fn remove_text(&mut self, id: Handle) {
+    let mut recording = self.recording.borrow_mut();
+    recording.texts.remove(&id);
+    recording.calls.push(DrawCall::RemoveText { id });
+  }
+
+  fn text(&self, id: Handle) -> String {
+    self.recording.borrow().texts.get(&id).map(|text| text.text.clone()).unwrap_or_default()
+  }
+
+  fn color_text(&mut self, color: Rgb) {
+    let mut recording = self.recording.borrow_mut();
+    recording.text_color = color;
+    recording.calls.push(DrawCall::ColorText { color });
+  }
+
+  fn flush_text(&mut self) {}
+}
@@ -0,0 +1,294 @@
+use crate::hashgrid::HashGrid;
+use crate::tree::{CS, Point};
+use std::collections::HashMap;
+
+//how edges that would cross other nodes are drawn
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum EdgeRouting {
+  Straight, //through whatever is in the way
+  Curved,   //bows around it
+  Orthogonal,
+}
+
+pub const ROUTINGS: [EdgeRouting; 3] = [EdgeRouting::Straight, EdgeRouting::Curved, EdgeRouting::Orthogonal];
+
+impl EdgeRouting {
+  pub fn name(self) -> &'static str {
+    match self {
+      EdgeRouting::Straight => "straight",
+      EdgeRouting::Curved => "curved",
+      EdgeRouting::Orthogonal => "orthogonal",
+    }
+  }
+
+  pub fn from_name(name: &str) -> Option<Self> {
+    ROUTINGS.iter().copied().find(|routing| routing.name() == name)
+  }
+
+  pub fn next(self) -> Self {
+    let index = ROUTINGS.iter().position(|&routing| routing == self).unwrap();
+    ROUTINGS[(index + 1) % ROUTINGS.len()]
+  }
+}
+
+pub fn routing_names() -> Vec<&'static str> {
+  ROUTINGS.iter().map(|routing| routing.name()).collect()
+}
+
+const CLEARANCE: f32 = 8.0; //space kept between a routed edge and a node it goes around
+const CURVE_SEGMENTS: usize = 12;
+const DETOURS: [f32; 3] = [1.5, 3.0, 4.5]; //how far a detour goes out, in node radii
+
+type Outline = ((f32, f32), CS); //center and the shape drawn around it
+
+//node outlines of one graph grown by the clearance, rebuilt whenever nodes move
+pub struct Obstacles {
+  grid: HashGrid<usize>,
+  nodes: HashMap<usize, Outline>,
+  radius: f32, //of a plain node, detours are measured in it
+}
+
+impl Obstacles {
+  pub fn new(nodes: HashMap<usize, Outline>, radius: f32) -> Self {
+    let mut grid = HashGrid::new((2.0 * (radius + CLEARANCE)).ceil() as u16);
+    let mut ids: Vec<&usize> = nodes.keys().collect();
+    ids.sort();
+    for &id in ids {
+      let (center, outline) = nodes[&id];
+      if let Some((half_width, half_height)) = outline.half_size() {
+        grid.insert_rectangle(center, (2.0 * (half_width + CLEARANCE), 2.0 * (half_height + CLEARANCE)), &id);
+      }
+    }
+    Obstacles { grid, nodes, radius }
+  }
+
+  //nodes other than the two ends a path passes through
+  fn blockers(&self, path: &[Point], ends: (usize, usize)) -> usize {
+    let mut blocking = Vec::new();
+    for pair in path.windows(2) {
+      for (id, _) in self.grid.query_segment((pair[0].x, pair[0].y), (pair[1].x, pair[1].y)) {
+        if id == ends.0 || id == ends.1 || blocking.contains(&id) {
+          continue;
+        }
+        //the grown box of a square or rectangle is its outline, a circle sits inside its box
+        let (center, outline) = self.nodes[&id];
+        if let CS::Circle(radius) = outline
+          && distance_to_segment(center, pair[0], pair[1]) >= radius + CLEARANCE
+        {
+          continue;
+        }
+        blocking.push(id);
+      }
+    }
+    blocking.len()
+  }
+
+  //None when the straight edge is already clear, or no detour is any better
+  pub fn route(&self, from_id: usize, to_id: usize, routing: EdgeRouting) -> Option<Vec<Point>> {
+    if routing == EdgeRouting::Straight || from_id == to_id {
+      return None;
+    }
+    let (from, to) = (self.nodes[&from_id], self.nodes[&to_id]);
+    let straight = self.clip(vec![Point::new(from.0.0, from.0.1), Point::new(to.0.0, to.0.1)], from, to)?;
+    let mut fewest = self.blockers(&straight, (from_id, to_id));
+    if fewest == 0 {
+      return None;
+    }
+    let mut best = None;
+    for candidate in candidates(from.0, to.0, self.radius, routing) {
+      let Some(path) = self.clip(candidate, from, to) else {
+        continue;
+      };
+      let blocking = self.blockers(&path, (from_id, to_id));
+      if blocking < fewest {
+        fewest = blocking;
+        best = Some(path);
+        if blocking == 0 {
+          break;
+        }
+      }
+    }
+    best
+  }
+
+  //cuts a center to center path at the two node borders
+  fn clip(&self, mut path: Vec<Point>, from: Outline, to: Outline) -> Option<Vec<Point>> {
+    path = clip_start(path, from)?;
+    path.reverse();
+    path = clip_start(path, to)?;
+    path.reverse();
+    Some(path)
+  }
+}
+
+fn candidates(from: (f32, f32), to: (f32, f32), radius: f32, routing: EdgeRouting) -> Vec<Vec<Point>> {
+  let start = Point::new(from.0, from.1);
+  let end = Point::new(to.0, to.1);
+  let mut paths = Vec::new();
+  match routing {
+    EdgeRouting::Straight => {}
+    //a quadratic curve reaches half way to its control point
+    EdgeRouting::Curved => {
+      let length = ((to.0 - from.0).powi(2) + (to.1 - from.1).powi(2)).sqrt().max(f32::EPSILON);
+      let normal = (-(to.1 - from.1) / length, (to.0 - from.0) / length);
+      let middle = ((from.0 + to.0) / 2.0, (from.1 + to.1) / 2.0);
+      for detour in DETOURS {
+        for side in [1.0, -1.0] {
+          let reach = 2.0 * side * detour * radius;
+          let control = Point::new(middle.0 + normal.0 * reach, middle.1 + normal.1 * reach);
+          paths.push((0..=CURVE_SEGMENTS)
+            .map(|i| {
+              let t = i as f32 / CURVE_SEGMENTS as f32;
+              let (a, b, c) = ((1.0 - t) * (1.0 - t), 2.0 * (1.0 - t) * t, t * t);
+              Point::new(a * start.x + b * control.x + c * end.x, a * start.y + b * control.y + c * end.y)
+            })
+            .collect());
+        }
+      }
+    }
+    EdgeRouting::Orthogonal => {
+      let middle = ((from.0 + to.0) / 2.0, (from.1 + to.1) / 2.0);
+      paths.push(vec![start, Point::new(to.0, from.1), end]);
+      paths.push(vec![start, Point::new(from.0, to.1), end]);
+      paths.push(vec![start, Point::new(middle.0, from.1), Point::new(middle.0, to.1), end]);
+      paths.push(vec![start, Point::new(from.0, middle.1), Point::new(to.0, middle.1), end]);
+      //around everything in between, over or under and left or right of both ends
+      for detour in DETOURS {
+        let out = 2.0 * detour * radius;
+        for y in [from.1.min(to.1) - out, from.1.max(to.1) + out] {
+          paths.push(vec![start, Point::new(from.0, y), Point::new(to.0, y), end]);
+        }
+        for x in [from.0.min(to.0) - out, from.0.max(to.0) + out] {
+          paths.push(vec![start, Point::new(x, from.1), Point::new(x, to.1), end]);
+        }
+      }
+    }
+  }
+  paths
+}
+
+//drops the part of the path inside the outline, None when all of it is inside.
+//inside is measured with the border straight edges end at
+fn clip_start(path: Vec<Point>, (center, outline): Outline) -> Option<Vec<Point>> {
+  let inside = |point: &Point| {
+    let (dx, dy) = (point.x - center.0, point.y - center.1);
+    (dx * dx + dy * dy).sqrt() < outline.border_distance((-dy).atan2(dx))
+  };
+  let leaving = path.iter().position(|point| !inside(point))?;
+  if leaving == 0 {
+    return Some(path);
+  }
+  let (a, b) = (path[leaving - 1], path[leaving]);
+  let (dx, dy) = (b.x - a.x, b.y - a.y);
+  let t = match outline {
+    //the first side of the box a + t(b - a) goes through
+    CS::Square(_) | CS::Rectangle(..) => {
+      let (half_width, half_height) = outline.half_size().unwrap();
+      let side = |start: f32, along: f32, middle: f32, half: f32| {
+        if along == 0.0 { f32::MAX } else { (middle + half.copysign(along) - start) / along }
+      };
+      side(a.x, dx, center.0, half_width).min(side(a.y, dy, center.1, half_height))
+    }
+    //|a + t(b - a) - center| = radius, the larger root is where the path leaves
+    _ => {
+      let radius = outline.border_distance(0.0);
+      let (fx, fy) = (a.x - center.0, a.y - center.1);
+      let qa = dx * dx + dy * dy;
+      let qb = 2.0 * (fx * dx + fy * dy);
+      let qc = fx * fx + fy * fy - radius * radius;
+      (-qb + (qb * qb - 4.0 * qa * qc).max(0.0).sqrt()) / (2.0 * qa)
+    }
+  };
+  let mut clipped = vec![Point::new(a.x + dx * t, a.y + dy * t)];
+  clipped.extend_from_slice(&path[leaving..]);
+  Some(clipped)
+}
+
+fn distance_to_segment(point: (f32, f32), a: Point, b: Point) -> f32 {
+  let (dx, dy) = (b.x - a.x, b.y - a.y);
+  let length = dx * dx + dy * dy;
+  let t = if length > 0.0 {
+    (((point.0 - a.x) * dx + (point.1 - a.y) * dy) / length).clamp(0.0, 1.0)
+  } else {
+    0.0
+  };
+  ((a.x + dx * t - point.0).powi(2) + (a.y + dy * t - point.1).powi(2)).sqrt()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  const RADIUS: f32 = 30.0;
+
+  fn circle(x: f32, y: f32) -> Outline {
+    ((x, y), CS::Circle(RADIUS))
+  }
+
+  fn on_border(point: Point, (center, outline): Outline) -> bool {
+    let (dx, dy) = (point.x - center.0, point.y - center.1);
+    ((dx * dx + dy * dy).sqrt() - outline.border_distance((-dy).atan2(dx))).abs() < 0.01
+  }
+
+  #[test]
+  fn clear_edges_stay_straight() {
+    let obstacles = Obstacles::new(HashMap::from([(0, circle(0.0, 0.0)), (1, circle(400.0, 0.0)), (2, circle(200.0, 100.0))]), RADIUS);
+    for routing in ROUTINGS {
+      assert_eq!(obstacles.route(0, 1, routing), None, "{}", routing.name());
+    }
+  }
+
+  #[test]
+  fn routes_go_around_what_is_in_the_way() {
+    let nodes = HashMap::from([(0, circle(0.0, 0.0)), (1, circle(400.0, 0.0)), (2, circle(200.0, 0.0))]);
+    let obstacles = Obstacles::new(nodes.clone(), RADIUS);
+    assert_eq!(obstacles.route(0, 1, EdgeRouting::Straight), None);
+    for routing in [EdgeRouting::Curved, EdgeRouting::Orthogonal] {
+      let path = obstacles.route(0, 1, routing).unwrap_or_else(|| panic!("{} is not routed", routing.name()));
+      assert_eq!(obstacles.blockers(&path, (0, 1)), 0, "{} still goes through", routing.name());
+      assert!(on_border(path[0], nodes[&0]) && on_border(path[path.len() - 1], nodes[&1]), "{} {:?}", routing.name(), path);
+    }
+  }
+
+  //a plain node where the rectangle is would leave the edge alone
+  #[test]
+  fn wide_nodes_block_by_their_outline() {
+    let nodes = HashMap::from([(0, circle(0.0, -200.0)), (1, circle(0.0, 200.0)), (2, ((120.0, 0.0), CS::Rectangle(140.0, 20.0)))]);
+    let obstacles = Obstacles::new(nodes, RADIUS);
+    let path = obstacles.route(0, 1, EdgeRouting::Orthogonal).expect("the edge goes through the rectangle");
+    assert_eq!(obstacles.blockers(&path, (0, 1)), 0);
+
+    let circles = Obstacles::new(HashMap::from([(0, circle(0.0, -200.0)), (1, circle(0.0, 200.0)), (2, circle(120.0, 0.0))]), RADIUS);
+    assert_eq!(circles.route(0, 1, EdgeRouting::Orthogonal), None);
+  }
+
+  #[test]
+  fn paths_are_cut_where_straight_edges_end() {
+    let outlines = [circle(0.0, 0.0), ((0.0, 0.0), CS::Square(25.0)), ((0.0, 0.0), CS::Rectangle(60.0, 20.0))];
+    for outline in outlines {
+      for end in [Point::new(300.0, 0.0), Point::new(-120.0, 250.0), Point::new(10.0, -300.0)] {
+        let path = clip_start(vec![Point::new(0.0, 0.0), Point::new(5.0, 2.0), end], outline).unwrap();
+        assert!(on_border(path[0], outline), "{:?} towards {:?} is cut at {:?}", outline.1, end, path[0]);
+        assert_eq!(path[path.len() - 1], end);
+      }
+      assert_eq!(clip_start(vec![Point::new(0.0, 0.0), Point::new(3.0, 3.0)], outline), None);
+    }
+    let outside = vec![Point::new(100.0, 0.0), Point::new(200.0, 0.0)];
+    assert_eq!(clip_start(outside.clone(), circle(0.0, 0.0)), Some(outside));
+  }
+
+  #[test]
+  fn candidates_run_from_one_center_to_the_other() {
+    let (from, to) = ((0.0, 0.0), (300.0, 120.0));
+    assert!(candidates(from, to, RADIUS, EdgeRouting::Straight).is_empty());
+    let curves = candidates(from, to, RADIUS, EdgeRouting::Curved);
+    assert_eq!(curves.len(), 2 * DETOURS.len());
+    let orthogonal = candidates(from, to, RADIUS, EdgeRouting::Orthogonal);
+    for path in curves.iter().chain(&orthogonal) {
+      assert_eq!((path[0], path[path.len() - 1]), (Point::new(from.0, from.1), Point::new(to.0, to.1)));
+    }
+    for path in &orthogonal {
+      assert!(path.windows(2).all(|pair| pair[0].x == pair[1].x || pair[0].y == pair[1].y), "{:?} is not orthogonal", path);
+    }
+  }
+}
@@ -0,0 +1,259 @@
+use crate::events::{TraceEvent, parse_event};
+use std::fmt;
+use std::io::{self, ErrorKind, Read};
+use std::net::{SocketAddr, TcpListener};
+use std::os::unix::fs::FileTypeExt;
+use std::os::unix::net::UnixListener;
+use std::path::PathBuf;
+
+const READ_CHUNK: usize = 64 * 1024;
+const POLL_BYTES: usize = 4 * 1024 * 1024; //read per client per frame, the rest waits for the next one
+const MAX_LINE: usize = 16 * 1024 * 1024; //a client sending more than this without a newline is dropped
+
+//where to listen, "unix:<path>", "<port>" or "<host>:<port>" on this machine
+#[derive(Debug, Clone)]
+pub enum Address {
+  Unix(PathBuf),
+  Tcp(SocketAddr),
+}
+
+impl Address {
+  pub fn parse(text: &str) -> Result<Address, String> {
+    if let Some(path) = text.strip_prefix("unix:") {
+      return Ok(Address::Unix(PathBuf::from(path)));
+    }
+    let address = match text.parse::<u16>() {
+      Ok(port) => SocketAddr::from(([127, 0, 0, 1], port)),
+      Err(_) => text.parse::<SocketAddr>()
+        .map_err(|_| format!("{:?} is not unix:<path>, <port> or <host>:<port>", text))?,
+    };
+    //the stream is not authenticated, so it never leaves the machine
+    if !address.ip().is_loopback() {
+      return Err(format!("{} is not a local address", address));
+    }
+    Ok(Address::Tcp(address))
+  }
+}
+
+impl fmt::Display for Address {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Address::Unix(path) => write!(f, "unix:{}", path.display()),
+      Address::Tcp(address) => write!(f, "{}", address),
+    }
+  }
+}
+
+enum Listener {
+  Unix(UnixListener),
+  Tcp(TcpListener),
+}
+
+struct Client {
+  stream: Box<dyn Read>,
+  pending: Vec<u8>, //bytes of a line that has not ended yet
+  open: bool,
+}
+
+//accepts traced programs on a socket and turns what they send into events, never blocks
+pub struct TraceStream {
+  address: Address,
+  listener: Listener,
+  clients: Vec<Client>,
+}
+
+impl TraceStream {
+  pub fn bind(address: Address) -> io::Result<Self> {
+    let listener = match &address {
+      Address::Unix(path) => {
+        //a socket an earlier run left behind would make bind fail, anything else is not ours to delete
+        if let Ok(metadata) = std::fs::metadata(path)
+          && metadata.file_type().is_socket()
+        {
+          std::fs::remove_file(path)?;
+        }
+        let listener = UnixListener::bind(path)?;
+        listener.set_nonblocking(true)?;
+        Listener::Unix(listener)
+      }
+      Address::Tcp(socket_address) => {
+        let listener = TcpListener::bind(socket_address)?;
+        listener.set_nonblocking(true)?;
+        Listener::Tcp(listener)
+      }
+    };
+    Ok(TraceStream { address, listener, clients: Vec::new() })
+  }
+
+  pub fn address(&self) -> &Address {
+    &self.address
+  }
+
+  //events that arrived since the last call, in the order each client sent them
+  pub fn poll(&mut self) -> Vec<TraceEvent> {
+    self.accept();
+    let mut events = Vec::new();
+    for client in self.clients.iter_mut() {
+      client.read_into(&mut events);
+    }
+    self.clients.retain(|client| client.open);
+    events
+  }
+
+  fn accept(&mut self) {
+    loop {
+      let accepted: io::Result<Box<dyn Read>> = match &self.listener {
+        Listener::Unix(listener) => listener.accept().and_then(|(stream, _)| {
+          stream.set_nonblocking(true)?;
+          Ok(Box::new(stream) as Box<dyn Read>)
+        }),
+        Listener::Tcp(listener) => listener.accept().and_then(|(stream, _)| {
+          stream.set_nonblocking(true)?;
+          Ok(Box::new(stream) as Box<dyn Read>)
+        }),
+      };
+      match accepted {
+        Ok(stream) => {
+          println!("Trace connected on {}", self.address);
+          self.clients.push(Client { stream, pending: Vec::new(), open: true });
+        }
+        Err(e) if e.kind() == ErrorKind::WouldBlock => break,
+        Err(e) => {
+          println!("Could not accept a trace connection: {}", e);
+          break;
+        }
+      }
+    }
+  }
+}
+
+impl Drop for TraceStream {
+  fn drop(&mut self) {
+    if let Address::Unix(path) = &self.address {
+      let _ = std::fs::remove_file(path);
+    }
+  }
+}
+
+impl Client {
+  fn read_into(&mut self, events: &mut Vec<TraceEvent>) {
+    let mut chunk = vec![0u8; READ_CHUNK];
+    let mut read = 0;
+    while read < POLL_BYTES {
+      match self.stream.read(&mut chunk) {
+        Ok(0) => {
+          self.open = false;
+          break;
+        }
+        Ok(count) => {
+          self.pending.extend_from_slice(&chunk[..count]);
+          read += count;
+        }
+        Err(e) if e.kind() == ErrorKind::WouldBlock => break,
+        Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+        Err(e) => {
+          println!("Trace connection failed: {}", e);
+          self.open = false;
+          break;
+        }
+      }
+    }
+
+    let mut start = 0;
+    while let Some(length) = self.pending[start..].iter().position(|byte| *byte == b'\n') {
+      parse_line(&self.pending[start..start + length], events);
+      start += length + 1;
+    }
+    self.pending.drain(..start);
+    if self.pending.len() > MAX_LINE {
+      println!("Trace line longer than {} bytes, dropping the connection", MAX_LINE);
+      self.pending = Vec::new();
+      self.open = false;
+      return;
+    }
+    if !self.open {
+      //the last line does not need a newline
+      let rest = std::mem::take(&mut self.pending);
+      parse_line(&rest, events);
+      println!("Trace disconnected");
+    }
+  }
+}
+
+fn parse_line(line: &[u8], events: &mut Vec<TraceEvent>) {
+  let text = String::from_utf8_lossy(line);
+  let text = text.trim();
+  if text.is_empty() {
+    return;
+  }
+  match parse_event(text) {
+    Ok(event) => events.push(event),
+    Err(message) => {
+      let shown: String = text.chars().take(80).collect();
+      println!("Skipping trace line {:?}: {}", shown, message);
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  //hands out its bytes a few at a time, then would block
+  struct Trickle {
+    bytes: Vec<u8>,
+    at: usize,
+    step: usize,
+  }
+
+  impl Read for Trickle {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+      if self.at == self.bytes.len() {
+        return Err(ErrorKind::WouldBlock.into());
+      }
+      let count = self.step.min(buf.len()).min(self.bytes.len() - self.at);
+      buf[..count].copy_from_slice(&self.bytes[self.at..self.at + count]);
+      self.at += count;
+      Ok(count)
+    }
+  }
+
+  //a line that never ends
+  struct Endless;
+
+  impl Read for Endless {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+      buf.fill(b' ');
+      Ok(buf.len())
+    }
+  }
+
+  fn client(stream: impl Read + 'static) -> Client {
+    Client { stream: Box::new(stream), pending: Vec::new(), open: true }
+  }
+
+  #[test]
+  fn lines_split_across_reads_come_out_whole() {
+    let line = r#"{"type": "node", "id": 0, "label": "a"}"#;
+    let bytes = format!("{}\n\n{}\n{}", line, line, &line[..10]).into_bytes();
+    let mut client = client(Trickle { bytes, at: 0, step: 7 });
+    let mut events = Vec::new();
+    client.read_into(&mut events);
+    assert_eq!(events.len(), 2);
+    assert!(client.open);
+    assert_eq!(client.pending, &line.as_bytes()[..10]);
+  }
+
+  #[test]
+  fn a_line_without_end_drops_the_client() {
+    let mut client = client(Endless);
+    let mut events = Vec::new();
+    for _ in 0..=MAX_LINE / POLL_BYTES {
+      assert!(client.open);
+      client.read_into(&mut events);
+    }
+    assert!(!client.open);
+    assert!(client.pending.is_empty());
+    assert!(events.is_empty());
+  }
+}
@@ -0,0 +1,99 @@
+use crate::render::Rgb;
+use std::cell::Cell;
+
+pub struct Theme {
+  pub name: &'static str,
+  pub background: Rgb,
+  pub focused_background: Rgb,//behind the focused tile in split mode
+  pub node: Rgb,
+  pub edge: Rgb,
+  pub text: Rgb,
+  pub root: Rgb,
+  //highlights for what the last step did, one per diff category
+  pub added: Rgb,
+  pub removed: Rgb,
+  pub changed: Rgb,//labels
+  pub added_edge: Rgb,
+  pub removed_edge: Rgb,
+  pub weight_changed: Rgb,
+}
+
+pub const DARK: Theme = Theme {
+  name: "dark",
+  background: (0.0, 0.0, 0.0),
+  focused_background: (0.08, 0.08, 0.08),
+  node: (1.0, 1.0, 1.0),
+  edge: (1.0, 1.0, 1.0),
+  text: (1.0, 1.0, 1.0),
+  root: (0.5, 0.0, 1.0),
+  added: (0.2, 0.85, 0.3),
+  removed: (1.0, 0.2, 0.2),
+  changed: (1.0, 0.9, 0.1),
+  added_edge: (0.2, 0.8, 0.9),
+  removed_edge: (0.9, 0.3, 0.7),
+  weight_changed: (1.0, 0.6, 0.1),
+};
+
+//for projectors in bright rooms
+pub const LIGHT: Theme = Theme {
+  name: "light",
+  background: (0.96, 0.96, 0.94),
+  focused_background: (0.89, 0.89, 0.86),
+  node: (0.12, 0.12, 0.12),
+  edge: (0.35, 0.35, 0.35),
+  text: (0.08, 0.08, 0.08),
+  root: (0.45, 0.1, 0.8),
+  added: (0.1, 0.6, 0.2),
+  removed: (0.85, 0.1, 0.1),
+  changed: (0.75, 0.6, 0.0),
+  added_edge: (0.0, 0.5, 0.65),
+  removed_edge: (0.7, 0.1, 0.5),
+  weight_changed: (0.85, 0.4, 0.0),
+};
+
+//okabe ito colors, they stay apart for the common kinds of color blindness
+pub const HIGH_CONTRAST: Theme = Theme {
+  name: "high-contrast",
+  background: (0.0, 0.0, 0.0),
+  focused_background: (0.12, 0.12, 0.12),
+  node: (1.0, 1.0, 1.0),
+  edge: (1.0, 1.0, 1.0),
+  text: (1.0, 1.0, 1.0),
+  root: (0.0, 0.45, 0.7),
+  added: (0.0, 0.62, 0.45),
+  removed: (0.84, 0.37, 0.0),
+  changed: (0.94, 0.89, 0.26),
+  added_edge: (0.34, 0.71, 0.91),
+  removed_edge: (0.8, 0.47, 0.65),
+  weight_changed: (0.9, 0.62, 0.0),
+};
+
+pub const THEMES: [Theme; 3] = [DARK, LIGHT, HIGH_CONTRAST];
+
+thread_local! {
+  static CURRENT: Cell<usize> = const { Cell::new(0) };
+}
+
+pub fn current() -> &'static Theme {
+  &THEMES[CURRENT.with(Cell::get)]
+}
+
+//false when there is no theme with that name
+pub fn set_theme(name: &str) -> bool {
+  match THEMES.iter().position(|theme| theme.name == name) {
+    Some(index) => {
+      CURRENT.with(|current| current.set(index));
+      true
+    }
+    None => false,
+  }
+}
+
+pub fn next_theme() -> &'static Theme {
+  CURRENT.with(|current| current.set((current.get() + 1) % THEMES.len()));
+  current()
+}
+
+pub fn theme_names() -> Vec<&'static str> {
+  THEMES.iter().map(|theme| theme.name).collect()
+}
@@ -0,0 +1,448 @@
+use crate::events::{read_trace, root_value};
+use crate::json_deserialize::{Root, global_timeline};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::cell::RefCell;
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::rc::{Rc, Weak};
+use trace::{Listener, Next, Trace, TraceId, TraceNode};
+
+const CHECK_SEED: u64 = 0x11573;
+const BUCKETS: usize = 5;
+
+#[derive(TraceNode)]
+struct BstNode {
+  id: TraceId,
+  #[trace(label)]
+  value: i32,
+  #[trace(weight = "left")]
+  left: Option<Box<BstNode>>,
+  #[trace(weight = "right")]
+  right: Option<Box<BstNode>>,
+}
+
+fn bst_insert(node: &mut Option<Box<BstNode>>, value: i32) {
+  match node {
+    None => *node = Some(Box::new(BstNode { id: TraceId::new(), value, left: None, right: None })),
+    Some(inner) if value < inner.value => bst_insert(&mut inner.left, value),
+    Some(inner) if value > inner.value => bst_insert(&mut inner.right, value),
+    Some(_) => {}
+  }
+}
+
+//a node with two children takes its successor's value, so the label changes and the successor goes
+fn bst_remove(node: &mut Option<Box<BstNode>>, value: i32) {
+  let Some(inner) = node else {
+    return;
+  };
+  if value < inner.value {
+    return bst_remove(&mut inner.left, value);
+  }
+  if value > inner.value {
+    return bst_remove(&mut inner.right, value);
+  }
+  match (inner.left.take(), inner.right.take()) {
+    (None, None) => *node = None,
+    (Some(child), None) | (None, Some(child)) => *node = Some(child),
+    (Some(left), Some(right)) => {
+      inner.left = Some(left);
+      inner.right = Some(right);
+      let mut successor = inner.right.as_ref().unwrap();
+      while let Some(left) = successor.left.as_ref() {
+        successor = left;
+      }
+      inner.value = successor.value;
+      let successor_value = successor.value;
+      bst_remove(&mut inner.right, successor_value);
+    }
+  }
+}
+
+//a chained hash table, the table points at the first entry of every bucket with the bucket as the weight.
+//an entry's label is made of two fields, so it implements TraceNode by hand. the === in it is
+//where older traces split a changed label, it has to come through whole
+struct Entry {
+  id: TraceId,
+  key: u32,
+  value: u32,
+  next: Option<Box<Entry>>,
+}
+
+#[derive(TraceNode)]
+struct Table {
+  id: TraceId,
+  #[trace(label)]
+  name: &'static str,
+  #[trace(weight = "{index}")]
+  buckets: Vec<Option<Box<Entry>>>,
+}
+
+impl TraceNode for Entry {
+  fn trace_id(&self) -> TraceId {
+    self.id
+  }
+
+  fn nexts<'a>(&'a self, visit: &mut dyn FnMut(Next<'a>, String)) {
+    if let Some(next) = &self.next {
+      visit(Next::Node(next.as_ref()), String::from("next"));
+    }
+  }
+
+  fn stringify(&self) -> String {
+    format!("{}==={}", self.key, self.value)
+  }
+}
+
+impl Table {
+  fn put(&mut self, key: u32, value: u32) {
+    let mut slot = &mut self.buckets[key as usize % BUCKETS];
+    while let Some(entry) = slot {
+      if entry.key == key {
+        entry.value = value;
+        return;
+      }
+      slot = &mut slot.as_mut().unwrap().next;
+    }
+    *slot = Some(Box::new(Entry { id: TraceId::new(), key, value, next: None }));
+  }
+
+  fn remove(&mut self, key: u32) {
+    let mut slot = &mut self.buckets[key as usize % BUCKETS];
+    while slot.as_ref().is_some_and(|entry| entry.key != key) {
+      slot = &mut slot.as_mut().unwrap().next;
+    }
+    if let Some(entry) = slot.take() {
+      *slot = entry.next;
+    }
+  }
+}
+
+//a directed graph of shared vertices. the graph owns them, edges are weak so a removed vertex
+//takes its incoming edges with it, and the weight of an edge is its position in the list
+#[derive(TraceNode)]
+struct Vertex {
+  id: TraceId,
+  #[trace(label)]
+  name: u32,
+  #[trace(weight = "{index}")]
+  out: Vec<Weak<RefCell<Vertex>>>,
+}
+
+#[derive(TraceNode)]
+struct Graph {
+  id: TraceId,
+  #[trace(label)]
+  name: &'static str,
+  #[trace(weight = "{index}")]
+  vertices: Vec<Rc<RefCell<Vertex>>>,
+}
+
+impl Graph {
+  //returns what it did, for the note
+  fn change(&mut self, rng: &mut StdRng, next_name: &mut u32) -> String {
+    let count = self.vertices.len();
+    match rng.random_range(0..5) {
+      4 if count > 0 => {
+        let mut vertex = self.vertices[rng.random_range(0..count)].borrow_mut();
+        *next_name += 1;
+        let note = format!("rename {} to {}", vertex.name, next_name);
+        vertex.name = *next_name;
+        note
+      }
+      0 if count > 0 => {
+        let removed = self.vertices.remove(rng.random_range(0..count));
+        let name = removed.borrow().name;
+        format!("remove {}", name)
+      }
+      1 if count > 1 => {
+        let from = &self.vertices[rng.random_range(0..count)];
+        let mut from = from.borrow_mut();
+        from.out.retain(|to| to.strong_count() > 0);
+        if from.out.is_empty() {
+          return format!("disconnect nothing from {}", from.name);
+        }
+        let index = rng.random_range(0..from.out.len());
+        let to = from.out.remove(index);
+        format!("disconnect {} from {}", to.upgrade().unwrap().borrow().name, from.name)
+      }
+      2 | 3 if count > 1 => {
+        let from = rng.random_range(0..count);
+        let to = (from + rng.random_range(1..count)) % count;
+        let (from, to) = (&self.vertices[from], &self.vertices[to]);
+        if from.borrow().out.iter().any(|out| out.ptr_eq(&Rc::downgrade(to))) {
+          return String::from("connect nothing");
+        }
+        from.borrow_mut().out.push(Rc::downgrade(to));
+        format!("connect {} to {}", from.borrow().name, to.borrow().name)
+      }
+      _ => {
+        *next_name += 1;
+        self.vertices.push(Rc::new(RefCell::new(Vertex { id: TraceId::new(), name: *next_name, out: Vec::new() })));
+        format!("add {}", next_name)
+      }
+    }
+  }
+}
+
+//labels and labelled edges, which is all a step shows once ids are left out.
+//the structures here never hold two nodes with the same label
+type Shape = (BTreeSet<String>, BTreeSet<(String, String, String)>);
+
+fn walk(root: Option<&dyn TraceNode>) -> Shape {
+  let mut shape = Shape::default();
+  if let Some(root) = root {
+    walk_from(root, &mut shape, &mut HashSet::from([root.trace_id()]));
+  }
+  shape
+}
+
+fn walk_from(node: &dyn TraceNode, shape: &mut Shape, seen: &mut HashSet<TraceId>) {
+  shape.0.insert(node.stringify());
+  node.nexts(&mut |next, weight| {
+    let mut follow = |next: &dyn TraceNode| {
+      shape.1.insert((node.stringify(), weight.clone(), next.stringify()));
+      if seen.insert(next.trace_id()) {
+        walk_from(next, shape, seen);
+      }
+    };
+    match next {
+      Next::Node(next) => follow(next),
+      Next::Shared(next) => next.with_node(&mut follow),
+    }
+  });
+}
+
+fn listen<N: TraceNode>(listener: &mut Listener, root: Option<&N>, note: &str, expected: &mut Vec<Shape>) {
+  listener.graph_listen(root, note);
+  //nothing is recorded before the structure has a node
+  if root.is_none() && expected.is_empty() {
+    return;
+  }
+  expected.push(walk(root.map(|root| root as &dyn TraceNode)));
+}
+
+//applies a listener's base and diffs the way the viewer does and checks every step against
+//what the structure looked like, and that no diff adds what is there or removes what is not
+fn replay(root: &Root, listener_id: usize, expected: &[Shape], failures: &mut Vec<String>) {
+  let mut labels: HashMap<u32, String> = HashMap::new();
+  let mut edges: HashMap<(u32, u32), String> = HashMap::new();
+  let base = &root.bases[listener_id];
+  let base_labels = base.node_labels(&root.nodes);
+  for (from_id, connections) in &base.edges {
+    labels.insert(*from_id, base_labels[*from_id as usize].clone());
+    for (to_id, weight) in connections {
+      edges.insert((*from_id, *to_id), weight.clone());
+    }
+  }
+  let shape = |labels: &HashMap<u32, String>, edges: &HashMap<(u32, u32), String>| -> Shape {
+    (
+      labels.values().cloned().collect(),
+      edges.iter().map(|((from_id, to_id), weight)| (labels[from_id].clone(), weight.clone(), labels[to_id].clone())).collect(),
+    )
+  };
+  if root.diffs[listener_id].len() + 1 != expected.len() {
+    failures.push(format!("listener {} has {} diffs for {} steps", listener_id, root.diffs[listener_id].len(), expected.len()));
+    return;
+  }
+  if shape(&labels, &edges) != expected[0] {
+    failures.push(format!("listener {} base differs", listener_id));
+  }
+
+  for (step, diff) in root.diffs[listener_id].iter().enumerate() {
+    let mut wrong = Vec::new();
+    for edge in &diff.removed_edges {
+      if edges.remove(&(edge.from_id, edge.to_id)).is_none() {
+        wrong.push(format!("removes missing edge {} -> {}", edge.from_id, edge.to_id));
+      }
+    }
+    for node in &diff.removed_nodes {
+      if labels.remove(&node.id).is_none() {
+        wrong.push(format!("removes missing node {}", node.id));
+      }
+    }
+    for node in &diff.added_nodes {
+      if labels.insert(node.id, node.label.clone()).is_some() {
+        wrong.push(format!("adds node {} twice", node.id));
+      }
+    }
+    for edge in &diff.added_edges {
+      if edges.insert((edge.from_id, edge.to_id), edge.weight.clone()).is_some() {
+        wrong.push(format!("adds edge {} -> {} twice", edge.from_id, edge.to_id));
+      }
+    }
+    //the old text has to be what was shown
+    for change in &diff.labels_changed {
+      if labels.insert(change.id, change.new.clone()).as_ref() != Some(&change.old) {
+        wrong.push(format!("relabels node {} from something it was not", change.id));
+      }
+    }
+    for change in &diff.weights_changed {
+      if edges.insert((change.from_id, change.to_id), change.new.clone()).as_ref() != Some(&change.old) {
+        wrong.push(format!("reweighs edge {} -> {} from something it was not", change.from_id, change.to_id));
+      }
+    }
+    if edges.keys().any(|(from_id, to_id)| !labels.contains_key(from_id) || !labels.contains_key(to_id)) {
+      wrong.push(String::from("leaves an edge without its nodes"));
+    } else if shape(&labels, &edges) != expected[step + 1] {
+      wrong.push(String::from("shows something else than the structure"));
+    }
+    if !wrong.is_empty() {
+      failures.push(format!("listener {} step {} ({}): {}", listener_id, step + 1, diff.note, wrong.join(", ")));
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  const OPERATIONS: usize = 400;
+
+  //a tree, a hash table and a graph changed at random through the trace crate, the saved and the
+  //streamed trace read back, with what every listener's structure looked like at each step
+  fn record(name: &str) -> ([Root; 2], [Vec<Shape>; 3]) {
+    let directory = std::env::temp_dir();
+    let lines_path = directory.join(format!("parse_listen_{}_{}.ndjson", name, std::process::id()));
+    let json_path = directory.join(format!("parse_listen_{}_{}.json", name, std::process::id()));
+    let (lines_path, json_path) = (lines_path.to_string_lossy(), json_path.to_string_lossy());
+    let trace = Trace::create(lines_path.as_ref()).unwrap();
+
+    let mut rng = StdRng::seed_from_u64(CHECK_SEED);
+    let mut tree: Option<Box<BstNode>> = None;
+    let mut table = Table { id: TraceId::new(), name: "table", buckets: (0..BUCKETS).map(|_| None).collect() };
+    let mut graph = Graph { id: TraceId::new(), name: "graph", vertices: Vec::new() };
+    let mut next_name = 0;
+    let mut tree_listener = trace.listener();
+    let mut table_listener = trace.listener();
+    let mut graph_listener = trace.listener();
+    let mut expected: [Vec<Shape>; 3] = [Vec::new(), Vec::new(), Vec::new()];
+    listen(&mut tree_listener, tree.as_deref(), "empty", &mut expected[0]);
+    listen(&mut table_listener, Some(&table), "empty", &mut expected[1]);
+    listen(&mut graph_listener, Some(&graph), "empty", &mut expected[2]);
+    for _ in 0..OPERATIONS {
+      let key = rng.random_range(0..40);
+      match rng.random_range(0..7) {
+        0 | 1 => {
+          bst_insert(&mut tree, key);
+          listen(&mut tree_listener, tree.as_deref(), &format!("insert {}", key), &mut expected[0]);
+        }
+        2 => {
+          bst_remove(&mut tree, key);
+          listen(&mut tree_listener, tree.as_deref(), &format!("remove {}", key), &mut expected[0]);
+        }
+        3 => {
+          table.put(key as u32, rng.random_range(0..10));
+          listen(&mut table_listener, Some(&table), &format!("put {}", key), &mut expected[1]);
+        }
+        4 => {
+          table.remove(key as u32);
+          listen(&mut table_listener, Some(&table), &format!("remove {}", key), &mut expected[1]);
+        }
+        _ => {
+          let note = graph.change(&mut rng, &mut next_name);
+          listen(&mut graph_listener, Some(&graph), &note, &mut expected[2]);
+        }
+      }
+    }
+    trace.finish().unwrap();
+    trace.save(json_path.as_ref()).unwrap();
+
+    let read = [read_trace(&json_path), read_trace(&lines_path)];
+    let _ = std::fs::remove_file(lines_path.as_ref());
+    let _ = std::fs::remove_file(json_path.as_ref());
+    (read.map(|root| root.unwrap()), expected)
+  }
+
+  fn replays(name: &str, listener_id: usize) {
+    let (roots, expected) = record(name);
+    assert!(expected[listener_id].len() > OPERATIONS / 10, "too few steps to check");
+    let mut failures = Vec::new();
+    for root in &roots {
+      replay(root, listener_id, &expected[listener_id], &mut failures);
+    }
+    assert!(failures.is_empty(), "{} failures:\n  {}", failures.len(), failures.join("\n  "));
+  }
+
+  #[test]
+  fn bst_replays() {
+    replays("bst", 0);
+  }
+
+  #[test]
+  fn hash_table_replays() {
+    replays("hash_table", 1);
+  }
+
+  #[test]
+  fn graph_replays() {
+    replays("graph", 2);
+  }
+
+  #[test]
+  fn saved_and_streamed_traces_are_the_same() {
+    let ([from_json, from_lines], expected) = record("saved_and_streamed");
+    assert_eq!(root_value(&from_json), root_value(&from_lines));
+    let steps: usize = expected.iter().map(|shapes| shapes.len() - 1).sum();
+    assert_eq!(global_timeline(&from_json).len(), steps);
+  }
+
+  //some of the graph's vertices, held by a second structure with a listener of its own
+  #[derive(TraceNode)]
+  struct Picked {
+    id: TraceId,
+    #[trace(label)]
+    name: &'static str,
+    #[trace(weight = "{index}")]
+    vertices: Vec<Rc<RefCell<Vertex>>>,
+  }
+
+  //the picked listener starts late, so the vertices it shares were registered by the graph's
+  //listener and renamed since
+  #[test]
+  fn listeners_sharing_nodes_replay() {
+    let directory = std::env::temp_dir();
+    let lines_path = directory.join(format!("parse_listen_shared_{}.ndjson", std::process::id()));
+    let json_path = directory.join(format!("parse_listen_shared_{}.json", std::process::id()));
+    let (lines_path, json_path) = (lines_path.to_string_lossy(), json_path.to_string_lossy());
+    let trace = Trace::create(lines_path.as_ref()).unwrap();
+
+    let mut rng = StdRng::seed_from_u64(CHECK_SEED);
+    let mut graph = Graph { id: TraceId::new(), name: "graph", vertices: Vec::new() };
+    let mut picked = Picked { id: TraceId::new(), name: "picked", vertices: Vec::new() };
+    let mut next_name = 0;
+    let mut graph_listener = trace.listener();
+    let mut picked_listener = trace.listener();
+    let mut expected: [Vec<Shape>; 2] = [Vec::new(), Vec::new()];
+    for operation in 0..600 {
+      let note = if rng.random_bool(0.2) && !graph.vertices.is_empty() {
+        //edges are keyed by their ends, so a vertex is picked at most once
+        let count = rng.random_range(1..4).min(graph.vertices.len());
+        picked.vertices = rand::seq::index::sample(&mut rng, graph.vertices.len(), count)
+          .into_iter()
+          .map(|index| Rc::clone(&graph.vertices[index]))
+          .collect();
+        String::from("pick")
+      } else {
+        graph.change(&mut rng, &mut next_name)
+      };
+      listen(&mut graph_listener, Some(&graph), &note, &mut expected[0]);
+      if operation >= 200 {
+        listen(&mut picked_listener, Some(&picked), &note, &mut expected[1]);
+      }
+    }
+    trace.finish().unwrap();
+    trace.save(json_path.as_ref()).unwrap();
+
+    let mut failures = Vec::new();
+    for path in [&json_path, &lines_path] {
+      let root = read_trace(path).unwrap();
+      assert!(!root.bases[1].labels.is_empty(), "no shared vertex was renamed before the second base");
+      for (listener_id, shapes) in expected.iter().enumerate() {
+        replay(&root, listener_id, shapes, &mut failures);
+      }
+    }
+    let _ = std::fs::remove_file(lines_path.as_ref());
+    let _ = std::fs::remove_file(json_path.as_ref());
+    assert!(failures.is_empty(), "{} failures:\n  {}", failures.len(), failures.join("\n  "));
+  }
+}
@@ -1,9 +1,7 @@
-use gl::DebugMessageInsert;
-
-use crate::c_side::{self, load_all_text_vbo};
-use crate::c_side::get_text;
-use crate::CString;
-use core::ffi::{c_char, c_float, c_int, c_uint, c_void};
+use crate::json_deserialize::{ShapeHint, Style};
+use crate::render::{Handle, RenderBackend, Rgb, SpriteFrame, with_backend};
+use crate::theme;
+use core::ffi::{c_float, c_int};
 use std::f32::consts::PI;
 //u32 is the radius, distance here.
 macro_rules! average_point {
@@ -48,12 +46,6 @@ macro_rules! rotate_around {
   };
 }
 
-const DEFAULT_R: c_float = 1.0;
-const DEFAULT_G: c_float = 1.0;
-const DEFAULT_B: c_float = 1.0;
-const HIGHLIGHT_R: c_float = 1.0;
-const HIGHLIGHT_G: c_float = 0.0;
-const HIGHLIGHT_B: c_float = 0.0;
 const DEFAULT_THICKNESS: c_float = 5.0;
 const LINE_HEIGHT: c_float = 2.0;
 const TRIANGLE_WIDTH_RATIO_LINE_HEIGHT: c_float = 5.0 * LINE_HEIGHT; 
@@ -62,9 +54,29 @@ const LINE_STOE_RATIO: c_float = 0.3;
 const CENTERING_RATIO: c_float = 0.9;
 const WEIGHT_SIZE: c_int = 18;
 const WBOTTOM_DISTANCE:c_int = 10;
+const CURVE_SEGMENTS: usize = 10;
+const WEIGHT_FRACTIONS: [c_float; 5] = [0.5, 0.35, 0.65, 0.2, 0.8];//along the line, tried in order
+const NEAR_VERTICAL: c_float = PI / 3.0;
+const GLYPH_ASPECT: c_float = 0.7;//width of a character for its height
+const CIRCLE_LABEL_CHARS: usize = 4;//longer labels make the node a rectangle
+const LABEL_LINE_CHARS: usize = 16;
+const LABEL_MAX_LINES: usize = 4;
+const LABEL_TEXT_SIZE: c_float = 14.0;
+const LABEL_LINE_SPACING: c_float = 1.15;
+const ELLIPSIS: &str = "...";
+const TOOLTIP_LINE_CHARS: usize = 48;
+const TOOLTIP_MAX_LINES: usize = 24;
+const TOOLTIP_PADDING: c_float = 8.0;
+const TOOLTIP_GAP: c_float = 10.0;//between the tooltip and the node
+const PANEL_LINE_CHARS: usize = 40;
+const PANEL_MAX_LINES: usize = 40;
+const BEND_RATIO: c_float = 0.15;//how far a bent line bows out, relative to its length
+const LOOP_SPREAD: c_float = 0.45;//angle between the top of the node and a foot of the loop
+const LOOP_REACH: c_float = 2.2;//radii from a foot to its control point
+const LOOP_ARROW_AT: c_float = 0.8;
 
 #[allow(dead_code)]
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, PartialEq)]
 pub struct Point{
   pub x: c_float,
   pub y: c_float,
@@ -78,7 +90,7 @@ pub struct IntPoint{
 }
 
 #[allow(dead_code)]
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum CS{
   Circle(c_float),//radius
   Square(c_float),//width
@@ -86,9 +98,38 @@ pub enum CS{
   Removed,
 }
 
+impl CS{
+  //half the width and height of the box around the outline
+  pub fn half_size(self) -> Option<(c_float, c_float)>{
+    match self {
+      CS::Circle(radius) => Some((radius, radius)),
+      CS::Square(half) => Some((half, half)),
+      CS::Rectangle(half_width, half_height) => Some((half_width, half_height)),
+      CS::Removed => None,
+    }
+  }
+
+  //from the center to the outline, towards an angle
+  pub fn border_distance(self, angle: c_float) -> c_float{
+    match self {
+      CS::Circle(radius) => radius,
+      CS::Square(half) => half / angle.cos().abs().max(angle.sin().abs()),
+      CS::Rectangle(half_width, half_height) => (half_width / angle.cos().abs()).min(half_height / angle.sin().abs()),
+      CS::Removed => 0.0,
+    }
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LineShape{
+  Straight,
+  Bent,//bows to its right, both halves of an antiparallel pair use it so they separate
+  Loop(c_float),//self-loop on top of a node of this radius, start and end are its center
+}
+
 #[derive(Debug)]
 pub enum LineState{
-  StartToEnd (c_uint),
+  StartToEnd (Handle),
   Nodirection,
   Novisual,
   Removed,
@@ -100,7 +141,6 @@ const BLUE: (c_float, c_float, c_float) = (0.0, 0.0, 1.0);
 const YELLOW: (c_float, c_float, c_float) = (1.0, 1.0, 0.0);
 const ORANGE: (c_float, c_float, c_float) = (1.0, 0.5, 0.0);
 const PURPLE: (c_float, c_float, c_float) = (0.5, 0.0, 1.0);
-const DEFAULT_COLOR: (c_float, c_float, c_float) = (DEFAULT_R, DEFAULT_G, DEFAULT_B);
 
 #[derive(Debug, Copy, Clone)]
 pub enum NodeColor {
@@ -111,6 +151,14 @@ pub enum NodeColor {
     Orange,
     Purple,
     Default,
+    Root,
+    Added,
+    Removed,
+    Changed,
+    AddedEdge,
+    RemovedEdge,
+    WeightChanged,
+    Rgb(c_float, c_float, c_float),
 }
 
 impl NodeColor {
@@ -122,7 +170,15 @@ impl NodeColor {
             NodeColor::Yellow => YELLOW,
             NodeColor::Orange => ORANGE,
             NodeColor::Purple => PURPLE,
-            NodeColor::Default => DEFAULT_COLOR,
+            NodeColor::Default => theme::current().node,
+            NodeColor::Root => theme::current().root,
+            NodeColor::Added => theme::current().added,
+            NodeColor::Removed => theme::current().removed,
+            NodeColor::Changed => theme::current().changed,
+            NodeColor::AddedEdge => theme::current().added_edge,
+            NodeColor::RemovedEdge => theme::current().removed_edge,
+            NodeColor::WeightChanged => theme::current().weight_changed,
+            NodeColor::Rgb(r, g, b) => (*r, *g, *b),
         }
     }
 }
@@ -131,9 +187,15 @@ impl NodeColor {
 #[derive(Debug)]
 pub struct Node{
   distance: CS,
-  shape_id: c_uint,
-  text_id: c_uint,
-  weight_id: Option<c_uint>,
+  shape_id: Handle,
+  fill_id: Option<Handle>,
+  text_id: Handle,
+  weight_id: Option<Handle>,
+  default_shape: CS,//what the viewer asked for before the style
+  color: NodeColor,
+  style: Style,
+  highlight: Option<NodeColor>,//what the last step did to it, wins over everything else
+  label: String,//in full, what is drawn may be wrapped and cut short
 }
 //location is the location of the shape and scale also the scale of the shape so access that
 
@@ -141,10 +203,88 @@ pub struct Node{
 #[allow(dead_code)]
 pub struct Line{
   state: LineState,
-  line_id: c_uint,
-  weight_id: c_uint,
+  segment_ids: Vec<Handle>,//one straight sprite per piece of the path
+  weight_id: Handle,
+  shape: LineShape,
+  path: Vec<Point>,//what is drawn right now, from start to end
+  weight: String,
+  weight_width: c_float,//of the box the weight text is fitted in
   pub start: Point,
   pub end: Point,
+  thickness: c_float,
+  style: Style,
+  highlight: Option<NodeColor>,
+}
+
+//dashed, see through copy of something a step removed, left at its last position
+#[derive(Debug)]
+pub struct Ghost{
+  sprite_ids: Vec<Handle>,
+  color: NodeColor,
+  alpha: c_float,
+}
+
+const GHOST_THICKNESS_RATIO: c_float = 0.4;
+const GHOST_ALPHA: c_float = 0.6;
+const DASH_LENGTH: c_float = 8.0;
+const DASH_GAP: c_float = 6.0;
+
+impl Ghost {
+  fn new(color: NodeColor) -> Self {
+    Ghost{ sprite_ids: Vec::new(), color, alpha: GHOST_ALPHA }
+  }
+
+  //there is no alpha in the sprite renderer so see through means mixed with the background
+  fn rgb(&self) -> Rgb {
+    let color = self.color.to_rgb();
+    let background = theme::current().background;
+    (
+      color.0 * self.alpha + background.0 * (1.0 - self.alpha),
+      color.1 * self.alpha + background.1 * (1.0 - self.alpha),
+      color.2 * self.alpha + background.2 * (1.0 - self.alpha),
+    )
+  }
+
+  //a line between two points broken into dashes
+  fn dashed(&mut self, start: Point, end: Point, thickness: c_float){
+    let length = distance_between_points!(start, end);
+    let angle = angle_between_points!(start, end);
+    let direction = ((end.x - start.x) / length.max(f32::EPSILON), (end.y - start.y) / length.max(f32::EPSILON));
+    let rgb = self.rgb();
+    with_backend(|backend| {
+      let mut travelled = 0.0;
+      while travelled < length {
+        let dash = DASH_LENGTH.min(length - travelled);
+        let middle = travelled + dash / 2.0;
+        self.sprite_ids.push(backend.new_line(start.x + direction.0 * middle, start.y + direction.1 * middle,
+          dash / 2.0, thickness, angle, rgb));
+        travelled += DASH_LENGTH + DASH_GAP;
+      }
+    })
+  }
+
+  pub fn alpha(&self) -> c_float {
+    self.alpha
+  }
+
+  pub fn set_alpha(&mut self, alpha: c_float){
+    self.alpha = alpha;
+    let rgb = self.rgb();
+    with_backend(|backend| {
+      for &id in &self.sprite_ids {
+        let frame = SpriteFrame { color: rgb, ..backend.sprite(id).frame() };
+        backend.override_sprite(id, frame);
+      }
+    })
+  }
+
+  pub fn remove(&mut self){
+    with_backend(|backend| {
+      for id in self.sprite_ids.drain(..) {
+        backend.remove_sprite(id);
+      }
+    })
+  }
 }
 
 impl Point {
@@ -156,33 +296,41 @@ impl Point {
 impl Line{
 
     pub fn new(way: LineState, start: Point, end: Point, weight_str: &str) -> Self {
-      let angle = angle_between_points!(start, end);
-      let middle: Point = average_point!(start, end);
-      let distance = distance_between_points!(start, end) / 2.0;
-      let weight_center: Point = perpendicular_point!(middle,(WEIGHT_SIZE/2 + WBOTTOM_DISTANCE), angle);//perpendicular to line
-    unsafe {
+      Line::with_shape(way, LineShape::Straight, start, end, weight_str)
+    }
+
+    pub fn with_shape(way: LineState, shape: LineShape, start: Point, end: Point, weight_str: &str) -> Self {
+      let path = line_path(start, end, shape);
+      let (arrow_center, arrow_angle) = arrow_pose(&path, shape);
+      let (weight_center, weight_width, _) = weight_pose(&path, shape);
+    with_backend(|backend| {
       let new_line: Line;
       match way {
         LineState::StartToEnd(_) => {
           new_line = Line {
             state: LineState::StartToEnd(
-              c_side::new_triangle(
-                middle.x + (distance * LINE_STOE_RATIO * angle.cos()),
-                middle.y + (distance * LINE_STOE_RATIO * -angle.sin()),
+              backend.new_triangle(
+                arrow_center.x,
+                arrow_center.y,
                 TRIANGLE_WIDTH_RATIO_LINE_HEIGHT,
                 TRIANGLE_HIGHT,
-                angle + PI / 2.0,
-                DEFAULT_R,
-                DEFAULT_G,
-                DEFAULT_B
+                arrow_angle + PI / 2.0,
+                theme::current().edge,
               )
             ),
             start,
             end,
-            weight_id: c_side::create_text_centered(CString::new(weight_str).expect("Error cstr").as_ptr(),
+            weight_id: backend.create_text_centered(weight_str,
               weight_center.x as i32, weight_center.y as i32,
-              distance as i32, WEIGHT_SIZE, 0.0),
-            line_id: c_side::new_line(middle.x, middle.y, distance, LINE_HEIGHT, angle, DEFAULT_R, DEFAULT_G, DEFAULT_B),
+              weight_width as i32, WEIGHT_SIZE, 0.0),
+            segment_ids: new_segments(backend, &path, LINE_HEIGHT, theme::current().edge),
+            shape,
+            path: path.clone(),
+            weight: weight_str.to_string(),
+            weight_width,
+            thickness: LINE_HEIGHT,
+            style: Style::default(),
+            highlight: None,
           };
         }
         LineState::Nodirection => {
@@ -190,10 +338,17 @@ impl Line{
             state: LineState::Nodirection,
             start,
             end,
-            weight_id: c_side::create_text_centered(CString::new(weight_str).expect("Error cstr").as_ptr(),
+            weight_id: backend.create_text_centered(weight_str,
               weight_center.x as i32, weight_center.y as i32,
-              distance as i32, WEIGHT_SIZE, 0.0),
-            line_id: c_side::new_line(middle.x, middle.y, distance, LINE_HEIGHT, angle, DEFAULT_R, DEFAULT_G, DEFAULT_B),
+              weight_width as i32, WEIGHT_SIZE, 0.0),
+            segment_ids: new_segments(backend, &path, LINE_HEIGHT, theme::current().edge),
+            shape,
+            path: path.clone(),
+            weight: weight_str.to_string(),
+            weight_width,
+            thickness: LINE_HEIGHT,
+            style: Style::default(),
+            highlight: None,
           };
         }
         LineState::Novisual => {
@@ -201,8 +356,15 @@ impl Line{
                 state: LineState::Novisual,
                 start,
                 end,
-                line_id: 0,
+                segment_ids: Vec::new(),
                 weight_id: 0,
+                shape,
+                path: path.clone(),
+                weight: weight_str.to_string(),
+                weight_width,
+                thickness: LINE_HEIGHT,
+                style: Style::default(),
+                highlight: None,
             };
         }
         LineState::Removed => {
@@ -210,63 +372,160 @@ impl Line{
         }
       };
       if !matches!(way, LineState::Novisual) {
-        load_all_text_vbo();
+        backend.flush_text();
       }
       new_line
-    }
+    })
+  }
+
+  pub fn shape(&self) -> LineShape {
+    self.shape
+  }
+
+  pub fn is_visual(&self) -> bool {
+    matches!(self.state, LineState::StartToEnd(_) | LineState::Nodirection)
+  }
+
+  pub fn path(&self) -> &[Point] {
+    &self.path
+  }
+
+  //from a point to the closest part of the drawn path
+  pub fn distance_to(&self, x: c_float, y: c_float) -> c_float {
+    self.path.windows(2)
+      .map(|pair| {
+        let (dx, dy) = (pair[1].x - pair[0].x, pair[1].y - pair[0].y);
+        let length = dx * dx + dy * dy;
+        let t = if length > 0.0 {
+          (((x - pair[0].x) * dx + (y - pair[0].y) * dy) / length).clamp(0.0, 1.0)
+        } else {
+          0.0
+        };
+        ((pair[0].x + dx * t - x).powi(2) + (pair[0].y + dy * t - y).powi(2)).sqrt()
+      })
+      .fold(c_float::MAX, c_float::min)
   }
 
   //This does not change the weight text
   pub fn override_line(&mut self,start: Point, end: Point){
-    let angle = angle_between_points!(start, end);
-    let middle: Point = average_point!(start,end);
-    let distance = distance_between_points!(start,end) / 2.0;
+    self.draw_path(line_path(start, end, self.shape));
+  }
 
-    let weight_center: Point = perpendicular_point!(middle,(WEIGHT_SIZE/2 + WBOTTOM_DISTANCE), angle);//perpendicular to line
+  //draws along a path found by the edge router instead of the shape's own
+  pub fn route_line(&mut self, path: Vec<Point>){
+    self.draw_path(path);
+  }
+
+  fn draw_path(&mut self, path: Vec<Point>){
+    let (arrow_center, arrow_angle) = arrow_pose(&path, self.shape);
+    let (weight_center, _, weight_angle) = weight_pose(&path, self.shape);
+    let color = self.stroke();
+    self.start = path[0];
+    self.end = path[path.len() - 1];
 
-    unsafe{
+    with_backend(|backend| {
       match self.state {
         LineState::StartToEnd(id) => {
           // Overriding triangle sprite (arrow head)
-          c_side::override_sprite(id, middle.x + (distance*LINE_STOE_RATIO*f32::cos(angle)),
-          middle.y + (distance * LINE_STOE_RATIO*f32::sin(-angle)),
-          TRIANGLE_WIDTH_RATIO_LINE_HEIGHT, TRIANGLE_HIGHT, angle + PI/2.0, DEFAULT_R, DEFAULT_G, DEFAULT_B);
-          self.start = start;
-          self.end = end;
-          // Overriding line sprite
+          backend.override_sprite(id, SpriteFrame { x: arrow_center.x, y: arrow_center.y,
+            width: TRIANGLE_WIDTH_RATIO_LINE_HEIGHT, height: TRIANGLE_HIGHT, thickness: arrow_angle + PI/2.0, color });
         }
         LineState::Nodirection => {
-          self.start = start;
-          self.end = end;
-          // Overriding line sprite
         }
         LineState::Novisual => {
-            self.start = start;
-            self.end = end;
         }
         LineState::Removed => {
           panic!("Invalid");
         }
       }
       if !matches!(self.state, LineState::Novisual) {
-        c_side::override_sprite(self.line_id, middle.x, middle.y, distance, 
-          LINE_HEIGHT, angle, DEFAULT_R, DEFAULT_G, DEFAULT_B);
-        c_side::move_text(self.weight_id, weight_center.x as i32, weight_center.y as i32);
-        c_side::rotate_text(self.weight_id, angle);
-        c_side::load_all_text_vbo();
+        //routed paths and shapes differ in how many pieces they have
+        if self.segment_ids.len() != path.len() - 1 {
+          for id in self.segment_ids.drain(..) {
+            backend.remove_sprite(id);
+          }
+          self.segment_ids = new_segments(backend, &path, self.thickness, color);
+        }
+        for (i, &id) in self.segment_ids.iter().enumerate() {
+          let (middle, half_length, angle) = segment(path[i], path[i + 1]);
+          let frame = SpriteFrame { x: middle.x, y: middle.y, width: half_length, height: self.thickness, thickness: angle, color };
+          backend.override_sprite(id, frame);
+        }
+        backend.move_text(self.weight_id, weight_center.x as i32, weight_center.y as i32);
+        backend.rotate_text(self.weight_id, weight_angle);
+        backend.flush_text();
       }
+    });
+    self.path = path;
+  }
+
+  pub fn set_shape(&mut self, shape: LineShape, start: Point, end: Point){
+    if shape == self.shape {
+      return;
+    }
+    self.shape = shape;
+    self.override_line(start, end);
+  }
+
+  //stroke and thickness from the trace, redrawn in place
+  pub fn style_line(&mut self, style: Style){
+    if style == self.style {
+      return;
+    }
+    self.style = style;
+    self.thickness = style.thickness.unwrap_or(LINE_HEIGHT);
+    self.recolor();
+  }
+
+  fn stroke(&self) -> Rgb {
+    match self.highlight {
+      Some(highlight) => highlight.to_rgb(),
+      None => self.style.stroke.unwrap_or(theme::current().edge),
+    }
+  }
+
+  pub fn highlight_line(&mut self, highlight: Option<NodeColor>){
+    self.highlight = highlight;
+    self.recolor();
+  }
+
+  pub fn ghost(&self, color: NodeColor) -> Ghost {
+    let mut ghost = Ghost::new(color);
+    let arrow_id = match self.state {
+      LineState::StartToEnd(arrow_id) => Some(arrow_id),
+      LineState::Nodirection => None,
+      LineState::Novisual | LineState::Removed => return ghost,
+    };
+    for pair in self.path.windows(2) {
+      ghost.dashed(pair[0], pair[1], self.thickness);
+    }
+    if let Some(arrow_id) = arrow_id {
+      let rgb = ghost.rgb();
+      with_backend(|backend| {
+        let arrow = backend.sprite(arrow_id);
+        ghost.sprite_ids.push(backend.new_triangle(arrow.x, arrow.y, arrow.width, arrow.height,
+          arrow.thickness, rgb));
+      });
+    }
+    ghost
+  }
+
+  //redraws with the current theme
+  pub fn recolor(&mut self){
+    if self.is_visual() {
+      self.draw_path(self.path.clone());
     }
   }
 
   pub fn remove_line(&mut self){
-    unsafe{
+    with_backend(|backend| {
       if let LineState::Removed = self.state {
       panic!("Invalid op");
       }
       let is_visual = !matches!(self.state, LineState::Novisual);
       match self.state {
         LineState::StartToEnd(id) => {
-          c_side::remove_sprite(id);
+          backend.remove_sprite(id);
         }
         LineState::Nodirection => {
         }
@@ -276,30 +535,196 @@ impl Line{
       }
       self.state = LineState::Removed;
       if is_visual {
-        c_side::remove_sprite(self.line_id);
-        c_side::remove_text(self.weight_id);
-        c_side::load_all_text_vbo();
+        for id in self.segment_ids.drain(..) {
+          backend.remove_sprite(id);
+        }
+        backend.remove_text(self.weight_id);
+        backend.flush_text();
       }
-    }
+    })
   }
   pub fn weight_line(&mut self, weight_str: &str){
     match self.state {
       LineState::Removed => unreachable!(),
       _ => {
-        unsafe{
-          c_side::remove_text(self.weight_id);
-          let angle = angle_between_points!(self.start, self.end);
-          let middle: Point = average_point!(self.start,self.end);
-          let distance = distance_between_points!(self.start,self.end) / 2.0;
-          let weight_center: Point = perpendicular_point!(middle,(WEIGHT_SIZE/2 + WBOTTOM_DISTANCE), angle);//perpendicular to line
-          self.weight_id = c_side::create_text_centered(CString::new(weight_str).expect("Error cstr").as_ptr(),
+        let (weight_center, weight_width, _) = weight_pose(&self.path, self.shape);
+        self.weight = weight_str.to_string();
+        self.weight_width = weight_width;
+        with_backend(|backend| {
+          backend.remove_text(self.weight_id);
+          self.weight_id = backend.create_text_centered(weight_str,
             weight_center.x as i32, weight_center.y as i32,
-            distance as i32, WEIGHT_SIZE, 0.0);
-          c_side::load_all_text_vbo();
-        }
+            weight_width as i32, WEIGHT_SIZE, 0.0);
+          backend.flush_text();
+        })
+      }
+    }
+
+  }
+
+  pub fn weight(&self) -> &str {
+    &self.weight
+  }
+
+  //roughly what the weight text covers when it is not rotated
+  pub fn weight_size(&self) -> (c_float, c_float) {
+    let natural = self.weight.chars().count() as c_float * WEIGHT_SIZE as c_float * GLYPH_ASPECT;
+    (natural.min(self.weight_width.max(0.0)), WEIGHT_SIZE as c_float)
+  }
+
+  //where the weight could go, best first when nothing is in the way
+  //the text is turned to read left to right and lies flat next to steep edges
+  pub fn weight_candidates(&self) -> Vec<(Point, c_float)> {
+    let default = weight_pose(&self.path, self.shape);
+    if let LineShape::Loop(_) = self.shape {
+      return vec![(default.0, default.2)];
+    }
+    let (width, height) = self.weight_size();
+    let mut sides = [1.0, -1.0];
+    //the outside of the bow is clear of the reverse edge
+    if self.shape == LineShape::Bent {
+      sides = [-1.0, 1.0];
+    }
+    let mut candidates = Vec::new();
+    for fraction in WEIGHT_FRACTIONS {
+      let (point, angle) = point_along(&self.path, fraction);
+      let mut upright = if angle.abs() > PI / 2.0 { angle - PI.copysign(angle) } else { angle };
+      let mut offset = (WEIGHT_SIZE / 2 + WBOTTOM_DISTANCE) as c_float;
+      if upright.abs() > NEAR_VERTICAL {
+        upright = 0.0;
+        offset = width / 2.0 + WBOTTOM_DISTANCE as c_float;
+      }
+      let offset = offset.max(height / 2.0);
+      for side in sides {
+        candidates.push((perpendicular_point!(point, side * offset, angle), upright));
       }
     }
+    candidates
+  }
 
+  pub fn place_weight(&mut self, center: Point, angle: c_float){
+    if !self.is_visual() {
+      return;
+    }
+    with_backend(|backend| {
+      backend.move_text(self.weight_id, center.x as i32, center.y as i32);
+      backend.rotate_text(self.weight_id, angle);
+      backend.flush_text();
+    })
+  }
+}
+
+//middle, half length and angle of the sprite between two points
+fn segment(start: Point, end: Point) -> (Point, c_float, c_float) {
+  (average_point!(start, end), distance_between_points!(start, end) / 2.0, angle_between_points!(start, end))
+}
+
+fn new_segments(backend: &mut dyn RenderBackend, path: &[Point], thickness: c_float, color: Rgb) -> Vec<Handle> {
+  path.windows(2)
+    .map(|pair| {
+      let (middle, half_length, angle) = segment(pair[0], pair[1]);
+      backend.new_line(middle.x, middle.y, half_length, thickness, angle, color)
+    })
+    .collect()
+}
+
+//unit vector of an angle in the y reversed convention
+fn direction(angle: c_float) -> Point {
+  Point::new(angle.cos(), -angle.sin())
+}
+
+//the polyline a line is drawn with, the number of points only depends on the shape
+fn line_path(start: Point, end: Point, shape: LineShape) -> Vec<Point> {
+  match shape {
+    LineShape::Straight => vec![start, end],
+    LineShape::Bent => {
+      let length = distance_between_points!(start, end);
+      let middle = average_point!(start, end);
+      let right = direction(angle_between_points!(start, end) - PI / 2.0);
+      //a quadratic curve reaches half way to its control point
+      let control = Point::new(middle.x + right.x * length * BEND_RATIO * 2.0,
+        middle.y + right.y * length * BEND_RATIO * 2.0);
+      (0..=CURVE_SEGMENTS)
+        .map(|i| {
+          let t = i as c_float / CURVE_SEGMENTS as c_float;
+          let (a, b, c) = ((1.0 - t) * (1.0 - t), 2.0 * (1.0 - t) * t, t * t);
+          Point::new(a * start.x + b * control.x + c * end.x, a * start.y + b * control.y + c * end.y)
+        })
+        .collect()
+    }
+    LineShape::Loop(radius) => {
+      let center = start;
+      let at = |angle: c_float, distance: c_float, from: Point| {
+        let unit = direction(angle);
+        Point::new(from.x + unit.x * distance, from.y + unit.y * distance)
+      };
+      let p0 = at(PI / 2.0 + LOOP_SPREAD, radius, center);
+      let p3 = at(PI / 2.0 - LOOP_SPREAD, radius, center);
+      let p1 = at(PI / 2.0 + 2.0 * LOOP_SPREAD, radius * LOOP_REACH, p0);
+      let p2 = at(PI / 2.0 - 2.0 * LOOP_SPREAD, radius * LOOP_REACH, p3);
+      (0..=CURVE_SEGMENTS)
+        .map(|i| {
+          let t = i as c_float / CURVE_SEGMENTS as c_float;
+          let u = 1.0 - t;
+          let (a, b, c, d) = (u * u * u, 3.0 * u * u * t, 3.0 * u * t * t, t * t * t);
+          Point::new(a * p0.x + b * p1.x + c * p2.x + d * p3.x, a * p0.y + b * p1.y + c * p2.y + d * p3.y)
+        })
+        .collect()
+    }
+  }
+}
+
+//point at a fraction of the path's length and the angle of the path there
+fn point_along(path: &[Point], fraction: c_float) -> (Point, c_float) {
+  let total: c_float = path.windows(2).map(|pair| distance_between_points!(pair[0], pair[1])).sum();
+  let mut remaining = total * fraction;
+  let last = path.len() - 2;
+  for (i, pair) in path.windows(2).enumerate() {
+    let length = distance_between_points!(pair[0], pair[1]);
+    if remaining <= length || i == last {
+      let t = if length > 0.0 { (remaining / length).min(1.0) } else { 0.0 };
+      return (
+        Point::new(pair[0].x + (pair[1].x - pair[0].x) * t, pair[0].y + (pair[1].y - pair[0].y) * t),
+        angle_between_points!(pair[0], pair[1]),
+      );
+    }
+    remaining -= length;
+  }
+  (path[0], 0.0)
+}
+
+fn arrow_pose(path: &[Point], shape: LineShape) -> (Point, c_float) {
+  match shape {
+    LineShape::Loop(_) => point_along(path, LOOP_ARROW_AT),
+    _ => point_along(path, 0.5 + LINE_STOE_RATIO / 2.0),
+  }
+}
+
+//center, box width and rotation of the weight text
+fn weight_pose(path: &[Point], shape: LineShape) -> (Point, c_float, c_float) {
+  let first = path[0];
+  let last = path[path.len() - 1];
+  let chord_angle = angle_between_points!(first, last);
+  let half_chord = distance_between_points!(first, last) / 2.0;
+  let offset = (WEIGHT_SIZE / 2 + WBOTTOM_DISTANCE) as c_float;
+  match shape {
+    //a routed path is straight pieces, the weight goes by the middle one
+    LineShape::Straight => {
+      let (middle, angle) = point_along(path, 0.5);
+      (perpendicular_point!(middle, offset, angle), half_chord, angle)
+    }
+    //outside of the bow so the two weights of a pair do not meet
+    LineShape::Bent => {
+      let (apex, _) = point_along(path, 0.5);
+      let right = direction(chord_angle - PI / 2.0);
+      //the reverse edge runs the other way, keep both weights upright
+      let upright = if chord_angle.abs() > PI / 2.0 { chord_angle - PI.copysign(chord_angle) } else { chord_angle };
+      (Point::new(apex.x + right.x * offset, apex.y + right.y * offset), half_chord, upright)
+    }
+    LineShape::Loop(radius) => {
+      let (apex, _) = point_along(path, 0.5);
+      (Point::new(apex.x, apex.y - offset), 2.0 * radius, 0.0)
+    }
   }
 }
 
@@ -307,227 +732,620 @@ impl Line{
 
 impl Node {
   pub fn new(shape_distance: CS, text: &str, center_x: c_float, center_y: c_float, color: NodeColor) -> Self {
-    let mut bounding_width: c_float;
-    let mut bounding_height: c_float;
-    let (r, g, b) = color.to_rgb();
-    let send_text = CString::new(text).expect("Error");
+    Node::styled(shape_distance, text, center_x, center_y, color, Style::default())
+  }
 
+  //style hints win over the shape and color the viewer picked
+  pub fn styled(default_shape: CS, text: &str, center_x: c_float, center_y: c_float, color: NodeColor,
+    style: Style) -> Self {
+    let lines = wrap_label(text, LABEL_LINE_CHARS, LABEL_MAX_LINES);
+    let shown = lines.join("\n");
+    let shape_distance = fitted_shape(default_shape, &style, &lines);
+    let thickness = style.thickness.unwrap_or(DEFAULT_THICKNESS);
+    let rgb = style.stroke.unwrap_or(color.to_rgb());
+
+    with_backend(|backend| {
+    //a fill is the same shape with an outline that reaches the center, made first so it is drawn below
+    let fill_id = style.fill.map(|fill| match shape_distance {
+      CS::Circle(radius) => backend.new_circle(center_x, center_y, radius, radius, fill),
+      CS::Square(edge_length) => backend.new_square(center_x, center_y, edge_length, edge_length, fill),
+      CS::Rectangle(width, height) => backend.new_rectangle(center_x, center_y, width, height,
+        width.min(height), fill),
+      CS::Removed => panic!("Invalid"),
+    });
+    let (bounding_width, bounding_height) = text_box(shape_distance, thickness);
     let node = match shape_distance {
       CS::Circle(radius) => {
-        bounding_width = 3.0 * radius * f32::cos(PI / 4.0);
-        bounding_width -= 3.0 * DEFAULT_THICKNESS;
-        bounding_width *= CENTERING_RATIO;
-        bounding_height = bounding_width;
-
-        unsafe {
-          Node {
-            distance: shape_distance,
-            text_id: c_side::create_text_centered(
-              send_text.as_ptr(),
-              center_x as i32,
-              center_y as i32,
-              bounding_width as i32,
-              bounding_height as i32,
-              0.0,
-            ),
-            shape_id: c_side::new_circle(center_x, center_y, radius, DEFAULT_THICKNESS, r, g, b),
-            weight_id: None,
-          }
+        Node {
+          distance: shape_distance,
+          text_id: backend.create_text_centered(
+            &shown,
+            center_x as i32,
+            center_y as i32,
+            bounding_width as i32,
+            bounding_height as i32,
+            0.0,
+          ),
+          shape_id: backend.new_circle(center_x, center_y, radius, thickness, rgb),
+          fill_id,
+          weight_id: None,
+          default_shape,
+          color,
+          style,
+          highlight: None,
+          label: text.to_string(),
         }
       }
       CS::Square(edge_length) => {
-        bounding_width = 3.0 * (edge_length - DEFAULT_THICKNESS);
-        bounding_width *= CENTERING_RATIO;
-        bounding_height = bounding_width;
-
-        unsafe {
-          Node {
-            distance: shape_distance,
-            text_id: c_side::create_text_centered(
-              send_text.as_ptr(),
-              center_x as i32,
-              center_y as i32,
-              bounding_width as i32,
-              bounding_height as i32,
-              0.0,
-            ),
-            shape_id: c_side::new_square(center_x, center_y, edge_length, DEFAULT_THICKNESS, r, g, b),
-            weight_id: None,
-          }
+        Node {
+          distance: shape_distance,
+          text_id: backend.create_text_centered(
+            &shown,
+            center_x as i32,
+            center_y as i32,
+            bounding_width as i32,
+            bounding_height as i32,
+            0.0,
+          ),
+          shape_id: backend.new_square(center_x, center_y, edge_length, thickness, rgb),
+          fill_id,
+          weight_id: None,
+          default_shape,
+          color,
+          style,
+          highlight: None,
+          label: text.to_string(),
         }
       }
       CS::Rectangle(width, height) => {
-        bounding_width = 3.0 * (width - DEFAULT_THICKNESS);
-        bounding_height = 3.0 * (height - DEFAULT_THICKNESS);
-        bounding_width *= CENTERING_RATIO;
-        bounding_height *= CENTERING_RATIO;
-
-        unsafe {
-          Node {
-            distance: shape_distance,
-            text_id: c_side::create_text_centered(
-              send_text.as_ptr(),
-              center_x as i32,
-              center_y as i32,
-              bounding_width as i32,
-              bounding_height as i32,
-              0.0,
-            ),
-            shape_id: c_side::new_rectangle(center_x, center_y, width, height, DEFAULT_THICKNESS, r, g, b),
-            weight_id: None,
-          }
+        Node {
+          distance: shape_distance,
+          text_id: backend.create_text_centered(
+            &shown,
+            center_x as i32,
+            center_y as i32,
+            bounding_width as i32,
+            bounding_height as i32,
+            0.0,
+          ),
+          shape_id: backend.new_rectangle(center_x, center_y, width, height, thickness, rgb),
+          fill_id,
+          weight_id: None,
+          default_shape,
+          color,
+          style,
+          highlight: None,
+          label: text.to_string(),
         }
       }
       CS::Removed => {
         panic!("Invalid");
       }
     };
-    unsafe { c_side::load_all_text_vbo();}
+    backend.flush_text();
     node
+    })
   }
   pub fn move_node(&self, x: c_float, y: c_float){
-    unsafe{
+    with_backend(|backend| {
       if let CS::Removed = self.distance {
         panic!("Invalid op");
       }
-      let text_obj = c_side::get_text(self.text_id);
-      let sprite_obj = c_side::get_sprite(self.shape_id);
-      c_side::move_text(self.text_id,
+      let sprite_obj = backend.sprite(self.shape_id);
+      backend.move_text(self.text_id,
         x as i32, y as i32);
-        // (*sprite_obj).x  as i32,
-        // (*sprite_obj).y as i32);
       if let Some(weight_id) = self.weight_id {
-        let weight_obj = c_side::get_text(weight_id);
-        c_side::move_text(weight_id, x as i32,
-          (y - (*sprite_obj).height / 2.0 - WEIGHT_SIZE as f32 - WBOTTOM_DISTANCE as f32) as i32);
+        backend.move_text(weight_id, x as i32,
+          (y - sprite_obj.height / 2.0 - WEIGHT_SIZE as f32 - WBOTTOM_DISTANCE as f32) as i32);
       }
-      c_side::move_sprite(self.shape_id, x, y); 
-      c_side::load_all_text_vbo();
-    }
+      backend.move_sprite(self.shape_id, x, y); 
+      if let Some(fill_id) = self.fill_id {
+        backend.move_sprite(fill_id, x, y);
+      }
+      backend.flush_text();
+    })
   }
 
   pub fn color_node(&mut self, color: NodeColor) {
-    let (r, g, b) = color.to_rgb();
-    unsafe {
+    self.color = color;
+    self.redraw_stroke();
+  }
+
+  pub fn highlight_node(&mut self, highlight: Option<NodeColor>) {
+    self.highlight = highlight;
+    self.redraw_stroke();
+  }
+
+  //the outline as dashes, a circle is walked as a polygon with one dash per side
+  pub fn ghost(&self, color: NodeColor) -> Ghost {
+    let mut ghost = Ghost::new(color);
+    if let CS::Removed = self.distance {
+      return ghost;
+    }
+    let shape = with_backend(|backend| backend.sprite(self.shape_id));
+    let thickness = shape.thickness * GHOST_THICKNESS_RATIO;
+    let center = Point::new(shape.x, shape.y);
+    match self.distance {
+      CS::Circle(radius) => {
+        let dashes = ((2.0 * PI * radius) / (DASH_LENGTH + DASH_GAP)).ceil().max(6.0) as usize;
+        let step = 2.0 * PI / dashes as f32;
+        let dash_angle = step * DASH_LENGTH / (DASH_LENGTH + DASH_GAP);
+        for i in 0..dashes {
+          let from_angle = i as f32 * step;
+          let to_angle = from_angle + dash_angle;
+          ghost.dashed(
+            Point::new(center.x + radius * from_angle.cos(), center.y - radius * from_angle.sin()),
+            Point::new(center.x + radius * to_angle.cos(), center.y - radius * to_angle.sin()),
+            thickness);
+        }
+      }
+      CS::Square(_) | CS::Rectangle(_, _) => {
+        let (half_width, half_height) = (shape.width, shape.height);
+        let corners = [
+          Point::new(center.x - half_width, center.y - half_height),
+          Point::new(center.x + half_width, center.y - half_height),
+          Point::new(center.x + half_width, center.y + half_height),
+          Point::new(center.x - half_width, center.y + half_height),
+        ];
+        for i in 0..corners.len() {
+          ghost.dashed(corners[i], corners[(i + 1) % corners.len()], thickness);
+        }
+      }
+      CS::Removed => unreachable!(),
+    }
+    ghost
+  }
+
+  fn redraw_stroke(&self) {
+    let rgb = match self.highlight {
+      Some(highlight) => highlight.to_rgb(),
+      None => self.style.stroke.unwrap_or(self.color.to_rgb()),
+    };
+    with_backend(|backend| {
         if let CS::Removed = self.distance {
             panic!("Invalid op");
         }
-        let sprite_obj = c_side::get_sprite(self.shape_id);
-        c_side::override_sprite(
-            self.shape_id,
-            (*sprite_obj).x,
-            (*sprite_obj).y,
-            (*sprite_obj).width,
-            (*sprite_obj).height,
-            (*sprite_obj).thickness,
-            r,
-            g,
-            b,
-        );
-    }
+        let frame = SpriteFrame {
+            color: rgb,
+            ..backend.sprite(self.shape_id).frame()
+        };
+        backend.override_sprite(self.shape_id, frame);
+    })
   }
   
+  //a label that needs another shape makes the node again
   pub fn label_node(&mut self, new_text: &str) {
-    unsafe {
-      if let CS::Removed = self.distance {
-        panic!("Invalid op");
-      }
-      let sprite_obj = c_side::get_sprite(self.shape_id);
-      c_side::remove_text(self.text_id);
-      self.text_id = c_side::create_text_centered(
-        CString::new(new_text).expect("Error cstr").as_ptr(),
-        (*sprite_obj).x as i32,
-        (*sprite_obj).y as i32,
-        (*sprite_obj).width as i32,
-        (*sprite_obj).height as i32,
+    if let CS::Removed = self.distance {
+      panic!("Invalid op");
+    }
+    let lines = wrap_label(new_text, LABEL_LINE_CHARS, LABEL_MAX_LINES);
+    if fitted_shape(self.default_shape, &self.style, &lines) != self.distance {
+      self.rebuild(new_text.to_string(), self.style);
+      return;
+    }
+    self.label = new_text.to_string();
+    let (bounding_width, bounding_height) = text_box(self.distance, self.thickness());
+    with_backend(|backend| {
+      let sprite_obj = backend.sprite(self.shape_id);
+      backend.remove_text(self.text_id);
+      self.text_id = backend.create_text_centered(
+        &lines.join("\n"),
+        sprite_obj.x as i32,
+        sprite_obj.y as i32,
+        bounding_width as i32,
+        bounding_height as i32,
         0.0,
       );
+    })
+  }
+
+  pub fn label(&self) -> &str {
+    &self.label
+  }
+
+  pub fn outline(&self) -> CS {
+    self.distance
+  }
+
+  fn thickness(&self) -> c_float {
+    self.style.thickness.unwrap_or(DEFAULT_THICKNESS)
+  }
+
+  //true when the node does not show all of its label
+  pub fn is_truncated(&self) -> bool {
+    wrap_label(&self.label, LABEL_LINE_CHARS, usize::MAX).len() > LABEL_MAX_LINES
+  }
+
+  pub fn contains(&self, x: c_float, y: c_float) -> bool {
+    if let CS::Removed = self.distance {
+      return false;
+    }
+    let shape = with_backend(|backend| backend.sprite(self.shape_id));
+    match self.distance {
+      CS::Circle(radius) => (x - shape.x).powi(2) + (y - shape.y).powi(2) <= radius * radius,
+      _ => (x - shape.x).abs() <= shape.width && (y - shape.y).abs() <= shape.height,
+    }
+  }
+
+  //top middle of the outline, where a tooltip hangs from
+  pub fn top(&self) -> Point {
+    let shape = with_backend(|backend| backend.sprite(self.shape_id));
+    match self.distance {
+      CS::Circle(radius) => Point::new(shape.x, shape.y - radius),
+      _ => Point::new(shape.x, shape.y - shape.height),
     }
   }
 
   pub fn scale_node(&mut self, width: c_float,  height: c_float){
-    unsafe{
-      let sprite_obj = c_side::get_sprite(self.shape_id);
-      let text_obj = c_side::get_text(self.text_id);
+    with_backend(|backend| {
+      let sprite_obj = backend.sprite(self.shape_id);
+      let text = backend.text(self.text_id);
       let old_text_id = self.text_id;
-      c_side::scale_sprite(self.shape_id, width, height, (*sprite_obj).thickness); 
-      match self.distance{
-        CS::Circle(_) =>{
-          let bounding_width = 2.0*width*f32::cos(PI / 4.0);
-          let bounding_height = bounding_width;
-
-          self.distance = CS::Circle(width);
-          self.text_id = c_side::create_text_centered((*text_obj).text, 
-            (*sprite_obj).x as i32, (*sprite_obj).y as i32,
-            bounding_width as i32, bounding_height as i32, 0.0);
-        }
-        CS::Square(_) =>{
-          self.distance = CS::Square(width);
-          self.text_id = c_side::create_text_centered((*text_obj).text,
-            (*sprite_obj).x as i32, (*sprite_obj).y as i32,
-            (width*2.0) as i32, (height*2.0) as i32, 0.0);
-        }
-        CS::Rectangle(_, _) =>{
-          self.distance = CS::Rectangle(width, height);
-          self.text_id = c_side::create_text_centered((*text_obj).text,
-            (*sprite_obj).x as i32, (*sprite_obj).y as i32,
-            (width*2.0) as i32, (height*2.0) as i32, 0.0);
-        }
+      backend.scale_sprite(self.shape_id, width, height, sprite_obj.thickness); 
+      if let Some(fill_id) = self.fill_id {
+        backend.scale_sprite(fill_id, width, height, width.min(height));
+      }
+      self.distance = match self.distance{
+        CS::Circle(_) => CS::Circle(width),
+        CS::Square(_) => CS::Square(width),
+        CS::Rectangle(_, _) => CS::Rectangle(width, height),
         CS::Removed =>{
           panic!("Removed");
         }
-      }
-      c_side::remove_text(old_text_id);
+      };
+      let (bounding_width, bounding_height) = text_box(self.distance, self.thickness());
+      self.text_id = backend.create_text_centered(&text,
+        sprite_obj.x as i32, sprite_obj.y as i32,
+        bounding_width as i32, bounding_height as i32, 0.0);
+      backend.remove_text(old_text_id);
       // Update or recreate weight text if present
       if let Some(wid) = self.weight_id {
-        let weight_obj = c_side::get_text(wid);
+        let weight_text = backend.text(wid);
         let old_weight_id = wid;
         // For simplicity, just recreate at the same position with new size
-        self.weight_id = Some(c_side::create_text_centered(
-          (*weight_obj).text,
-          (*sprite_obj).x as i32,
-          ((*sprite_obj).y - (*sprite_obj).height / 2.0 - WEIGHT_SIZE as f32 - WBOTTOM_DISTANCE as f32) as i32,
+        self.weight_id = Some(backend.create_text_centered(
+          &weight_text,
+          sprite_obj.x as i32,
+          (sprite_obj.y - sprite_obj.height / 2.0 - WEIGHT_SIZE as f32 - WBOTTOM_DISTANCE as f32) as i32,
           (width*2.0) as i32, (height*2.0) as i32, 0.0));
-        c_side::remove_text(old_weight_id);
+        backend.remove_text(old_weight_id);
       }
-      c_side::load_all_text_vbo();
-    }
+      backend.flush_text();
+    })
   }
 
   pub fn remove_node(&mut self){
-    unsafe{
+    with_backend(|backend| {
       if let CS::Removed = self.distance {
         panic!("Invalid op");
       }
-      c_side::remove_sprite(self.shape_id);
-      c_side::remove_text(self.text_id);
+      backend.remove_sprite(self.shape_id);
+      if let Some(fill_id) = self.fill_id.take() {
+        backend.remove_sprite(fill_id);
+      }
+      backend.remove_text(self.text_id);
       if let Some(wid) = self.weight_id {
-        c_side::remove_text(wid);
+        backend.remove_text(wid);
         self.weight_id = None;
       }
-      c_side::load_all_text_vbo();
+      backend.flush_text();
       self.distance = CS::Removed;
-    }
+    })
   }
   
+  //redraws with the current theme, style hints keep their colors
+  pub fn recolor(&mut self) {
+    if let CS::Removed = self.distance {
+      return;
+    }
+    self.color_node(self.color);
+  }
+
+  //the shape may change so the node is made again at the same place with the same texts
+  pub fn set_style(&mut self, style: Style) {
+    if style == self.style {
+      return;
+    }
+    if let CS::Removed = self.distance {
+      self.style = style;
+      return;
+    }
+    self.rebuild(self.label.clone(), style);
+  }
+
+  fn rebuild(&mut self, label: String, style: Style) {
+    let (position, weight) = with_backend(|backend| {
+      let sprite_obj = backend.sprite(self.shape_id);
+      ((sprite_obj.x, sprite_obj.y), self.weight_id.map(|id| backend.text(id)))
+    });
+    let highlight = self.highlight;
+    self.remove_node();
+    *self = Node::styled(self.default_shape, &label, position.0, position.1, self.color, style);
+    if let Some(weight) = weight {
+      self.weight_node(&weight);
+    }
+    if highlight.is_some() {
+      self.highlight_node(highlight);
+    }
+  }
+
   pub fn weight_node(&mut self, weight: &str) {
-    unsafe {
+    with_backend(|backend| {
         if let CS::Removed = self.distance {
             panic!("Invalid op");
         }
-        let sprite_obj = c_side::get_sprite(self.shape_id);
+        let sprite_obj = backend.sprite(self.shape_id);
         if let Some(old_weight_id) = self.weight_id {
-            c_side::remove_text(old_weight_id);
+            backend.remove_text(old_weight_id);
         }
-        self.weight_id = Some(c_side::create_text_centered(
-            CString::new(weight).expect("Error cstr").as_ptr(),
-            (*sprite_obj).x as i32,
-            ((*sprite_obj).y - (*sprite_obj).height / 2.0 - WEIGHT_SIZE as f32 - WBOTTOM_DISTANCE as f32) as i32,
-            (*sprite_obj).width as i32,
+        self.weight_id = Some(backend.create_text_centered(
+            weight,
+            sprite_obj.x as i32,
+            (sprite_obj.y - sprite_obj.height / 2.0 - WEIGHT_SIZE as f32 - WBOTTOM_DISTANCE as f32) as i32,
+            sprite_obj.width as i32,
             WEIGHT_SIZE,
             0.0,
         ));
-        c_side::load_all_text_vbo();
+        backend.flush_text();
+    })
+  }
+}
+
+fn styled_shape(default_shape: CS, style: &Style) -> CS {
+  let default_size = match default_shape {
+    CS::Circle(radius) => (radius, radius),
+    CS::Square(edge_length) => (edge_length, edge_length),
+    CS::Rectangle(width, height) => (width, height),
+    CS::Removed => panic!("Invalid"),
+  };
+  let (width, height) = style.size.unwrap_or(default_size);
+  match (style.shape, default_shape) {
+    (Some(ShapeHint::Circle), _) | (None, CS::Circle(_)) => CS::Circle(width),
+    (Some(ShapeHint::Square), _) | (None, CS::Square(_)) => CS::Square(width),
+    _ => CS::Rectangle(width, height),
+  }
+}
+
+//greedy wrap that breaks after spaces and punctuation, so struct dumps break between fields
+//a word longer than a line is cut, lines past max_lines are dropped and the last one ends in an ellipsis
+pub fn wrap_label(text: &str, line_chars: usize, max_lines: usize) -> Vec<String> {
+  let mut lines: Vec<String> = Vec::new();
+  for paragraph in text.lines() {
+    let mut tokens: Vec<String> = Vec::new();
+    let mut token = String::new();
+    for c in paragraph.chars() {
+      token.push(c);
+      if c.is_whitespace() || ",;{}()[]".contains(c) {
+        tokens.push(std::mem::take(&mut token));
+      }
+    }
+    if !token.is_empty() {
+      tokens.push(token);
+    }
+
+    let mut line = String::new();
+    for token in tokens {
+      if line.chars().count() + token.trim_end().chars().count() > line_chars && !line.is_empty() {
+        lines.push(line.trim_end().to_string());
+        line = String::new();
+      }
+      let mut rest: Vec<char> = token.chars().collect();
+      while rest.len() > line_chars {
+        lines.push(rest.drain(..line_chars).collect());
+      }
+      line.extend(rest);
+    }
+    if !line.trim_end().is_empty() || lines.is_empty() {
+      lines.push(line.trim_end().to_string());
+    }
+  }
+  if lines.is_empty() {
+    lines.push(String::new());
+  }
+
+  if lines.len() > max_lines {
+    lines.truncate(max_lines);
+    let last = lines.last_mut().unwrap();
+    let kept: String = last.chars().take(line_chars.saturating_sub(ELLIPSIS.len())).collect();
+    *last = kept.trim_end().to_string() + ELLIPSIS;
+  }
+  lines
+}
+
+//short labels keep the node's shape, longer ones get a rectangle that fits them unless the style picked one
+//the box a label is fitted into inside an outline, the same when a node is made, relabeled or scaled
+fn text_box(outline: CS, thickness: c_float) -> (c_float, c_float) {
+  let (width, height) = match outline {
+    CS::Circle(radius) => {
+      let side = 3.0 * radius * f32::cos(PI / 4.0) - 3.0 * thickness;
+      (side, side)
+    }
+    //half a side, the box is the side inside the outline
+    CS::Square(edge_length) => (2.0 * (edge_length - thickness), 2.0 * (edge_length - thickness)),
+    CS::Rectangle(width, height) => (2.0 * (width - thickness), 2.0 * (height - thickness)),
+    CS::Removed => panic!("Invalid"),
+  };
+  (width * CENTERING_RATIO, height * CENTERING_RATIO)
+}
+
+fn fitted_shape(default_shape: CS, style: &Style, lines: &[String]) -> CS {
+  let styled = styled_shape(default_shape, style);
+  let longest = lines.iter().map(|line| line.chars().count()).max().unwrap_or(0);
+  if style.shape.is_some() || style.size.is_some() || (lines.len() == 1 && longest <= CIRCLE_LABEL_CHARS) {
+    return styled;
+  }
+  let thickness = style.thickness.unwrap_or(DEFAULT_THICKNESS);
+  let text_width = longest as c_float * LABEL_TEXT_SIZE * GLYPH_ASPECT;
+  let text_height = lines.len() as c_float * LABEL_TEXT_SIZE * LABEL_LINE_SPACING;
+  //never smaller than the node it stands in for
+  let least = match styled {
+    CS::Circle(radius) => radius,
+    CS::Square(edge_length) => edge_length,
+    CS::Rectangle(width, height) => width.min(height),
+    CS::Removed => panic!("Invalid"),
+  };
+  CS::Rectangle(
+    (text_width / (2.0 * CENTERING_RATIO) + thickness).max(least),
+    (text_height / (2.0 * CENTERING_RATIO) + thickness).max(least),
+  )
+}
+
+//the full label of a node under the cursor, a filled box with an outline above the node
+#[derive(Debug)]
+pub struct Tooltip{
+  background_id: Handle,
+  border_id: Handle,
+  text_id: Handle,
+  half_height: c_float,
+}
+
+impl Tooltip {
+  pub fn new(text: &str, anchor: Point) -> Self {
+    let lines = wrap_label(text, TOOLTIP_LINE_CHARS, TOOLTIP_MAX_LINES);
+    let longest = lines.iter().map(|line| line.chars().count()).max().unwrap_or(0);
+    let text_width = longest as c_float * LABEL_TEXT_SIZE * GLYPH_ASPECT;
+    let text_height = lines.len() as c_float * LABEL_TEXT_SIZE * LABEL_LINE_SPACING;
+    let half_width = text_width / 2.0 + TOOLTIP_PADDING;
+    let half_height = text_height / 2.0 + TOOLTIP_PADDING;
+    let center = Point::new(anchor.x, anchor.y - TOOLTIP_GAP - half_height);
+    let theme = theme::current();
+    with_backend(|backend| {
+      let tooltip = Tooltip {
+        background_id: backend.new_rectangle(center.x, center.y, half_width, half_height,
+          half_width.min(half_height), theme.background),
+        border_id: backend.new_rectangle(center.x, center.y, half_width, half_height, LINE_HEIGHT, theme.edge),
+        text_id: backend.create_text_centered(&lines.join("\n"), center.x as i32, center.y as i32,
+          text_width as i32, text_height as i32, 0.0),
+        half_height,
+      };
+      backend.flush_text();
+      tooltip
+    })
+  }
+
+  pub fn move_to(&self, anchor: Point){
+    let center = Point::new(anchor.x, anchor.y - TOOLTIP_GAP - self.half_height);
+    with_backend(|backend| {
+      backend.move_sprite(self.background_id, center.x, center.y);
+      backend.move_sprite(self.border_id, center.x, center.y);
+      backend.move_text(self.text_id, center.x as i32, center.y as i32);
+      backend.flush_text();
+    })
+  }
+
+  pub fn remove(self){
+    with_backend(|backend| {
+      backend.remove_sprite(self.background_id);
+      backend.remove_sprite(self.border_id);
+      backend.remove_text(self.text_id);
+      backend.flush_text();
+    })
+  }
+}
+
+//fixed box of left aligned lines, the inspector draws one in a corner of its tile
+#[derive(Debug)]
+pub struct Panel{
+  background_id: Handle,
+  border_id: Handle,
+  text_id: Handle,
+  half_size: (c_float, c_float),
+}
+
+impl Panel {
+  pub fn new(lines: &[String], top_right: Point) -> Self {
+    let mut wrapped: Vec<String> = lines.iter()
+      .flat_map(|line| wrap_label(line, PANEL_LINE_CHARS, PANEL_MAX_LINES))
+      .take(PANEL_MAX_LINES)
+      .collect();
+    let longest = wrapped.iter().map(|line| line.chars().count()).max().unwrap_or(0);
+    //text is centered line by line, padding to one width lines it up on the left
+    for line in wrapped.iter_mut() {
+      let missing = longest - line.chars().count();
+      line.push_str(&" ".repeat(missing));
+    }
+    let text_width = longest as c_float * LABEL_TEXT_SIZE * GLYPH_ASPECT;
+    let text_height = wrapped.len() as c_float * LABEL_TEXT_SIZE * LABEL_LINE_SPACING;
+    let half_size = (text_width / 2.0 + TOOLTIP_PADDING, text_height / 2.0 + TOOLTIP_PADDING);
+    let center = Point::new(top_right.x - half_size.0, top_right.y + half_size.1);
+    let theme = theme::current();
+    with_backend(|backend| {
+      let panel = Panel {
+        background_id: backend.new_rectangle(center.x, center.y, half_size.0, half_size.1,
+          half_size.0.min(half_size.1), theme.background),
+        border_id: backend.new_rectangle(center.x, center.y, half_size.0, half_size.1, LINE_HEIGHT, theme.edge),
+        text_id: backend.create_text_centered(&wrapped.join("\n"), center.x as i32, center.y as i32,
+          text_width as i32, text_height as i32, 0.0),
+        half_size,
+      };
+      backend.flush_text();
+      panel
+    })
+  }
+
+  pub fn half_size(&self) -> (c_float, c_float){
+    self.half_size
+  }
+
+  pub fn move_to(&self, top_right: Point){
+    let center = Point::new(top_right.x - self.half_size.0, top_right.y + self.half_size.1);
+    with_backend(|backend| {
+      backend.move_sprite(self.background_id, center.x, center.y);
+      backend.move_sprite(self.border_id, center.x, center.y);
+      backend.move_text(self.text_id, center.x as i32, center.y as i32);
+      backend.flush_text();
+    })
+  }
+
+  pub fn remove(self){
+    with_backend(|backend| {
+      backend.remove_sprite(self.background_id);
+      backend.remove_sprite(self.border_id);
+      backend.remove_text(self.text_id);
+      backend.flush_text();
+    })
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::render::{self, RecordingBackend};
+
+  #[test]
+  fn a_square_label_fits_inside_the_outline() {
+    let backend = RecordingBackend::new();
+    let recording = backend.recording();
+    let previous = render::set_backend(Box::new(backend));
+    let style = Style { shape: Some(ShapeHint::Square), size: Some((30.0, 30.0)), thickness: Some(2.0), ..Style::default() };
+    let node = Node::styled(CS::Circle(20.0), "7", 100.0, 100.0, NodeColor::Red, style);
+    render::set_backend(previous);
+
+    assert_eq!(node.outline(), CS::Square(30.0));
+    let side = 2.0 * 30.0;
+    let inside = ((side - 2.0 * 2.0) * CENTERING_RATIO) as i32;
+    assert_eq!(recording.borrow().texts[&node.text_id].max_size, (inside, inside));
+  }
+
+  //a relabeled node fits its label in the box a node made with that label gets
+  #[test]
+  fn relabeled_nodes_keep_the_box_they_were_made_with() {
+    let backend = RecordingBackend::new();
+    let recording = backend.recording();
+    let previous = render::set_backend(Box::new(backend));
+    let text_box = |node: &Node| recording.borrow().texts[&node.text_id].max_size;
+    for shape in [ShapeHint::Circle, ShapeHint::Square, ShapeHint::Rectangle] {
+      let style = Style { shape: Some(shape), size: Some((40.0, 25.0)), thickness: Some(3.0), ..Style::default() };
+      let mut relabeled = Node::styled(CS::Circle(20.0), "a", 0.0, 0.0, NodeColor::Red, style);
+      relabeled.label_node("a longer label");
+      let made = Node::styled(CS::Circle(20.0), "a longer label", 0.0, 0.0, NodeColor::Red, style);
+      assert_eq!(text_box(&relabeled), text_box(&made), "{:?}", shape);
+
+      relabeled.scale_node(50.0, 35.0);
+      let scaled = Style { size: Some((50.0, 35.0)), ..style };
+      let made = Node::styled(CS::Circle(20.0), "a longer label", 0.0, 0.0, NodeColor::Red, scaled);
+      assert_eq!(text_box(&relabeled), text_box(&made), "{:?} scaled", shape);
     }
+    render::set_backend(previous);
   }
 }
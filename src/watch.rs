@@ -0,0 +1,65 @@
+use crate::events::read_trace;
+use crate::json_deserialize::Root;
+use std::path::Path;
+use std::time::{Duration, Instant, SystemTime};
+
+const SETTLE: Duration = Duration::from_millis(250); //a file that is still being written keeps changing
+
+type Stamp = (SystemTime, u64); //modified time and length
+
+//notices when the trace file was rewritten, by polling so it works the same everywhere
+pub struct TraceWatcher {
+  path: String,
+  loaded: Option<Stamp>,
+  changed: Option<(Stamp, Instant)>, //what the file looked like last time and since when
+}
+
+impl TraceWatcher {
+  pub fn new(path: &str) -> Self {
+    TraceWatcher { path: path.to_string(), loaded: stamp(path), changed: None }
+  }
+
+  pub fn path(&self) -> &str {
+    &self.path
+  }
+
+  //true once when the file changed and then stayed the same for a moment.
+  //a missing file is not a change, editors remove it before writing the new one
+  pub fn poll(&mut self) -> bool {
+    let Some(current) = stamp(&self.path) else {
+      return false;
+    };
+    if Some(current) == self.loaded {
+      self.changed = None;
+      return false;
+    }
+    match self.changed {
+      Some((seen, since)) if seen == current => {
+        if since.elapsed() < SETTLE {
+          return false;
+        }
+        self.loaded = Some(current);
+        self.changed = None;
+        true
+      }
+      _ => {
+        self.changed = Some((current, Instant::now()));
+        false
+      }
+    }
+  }
+}
+
+fn stamp(path: impl AsRef<Path>) -> Option<Stamp> {
+  let metadata = std::fs::metadata(path).ok()?;
+  Some((metadata.modified().ok()?, metadata.len()))
+}
+
+//a trace that does not parse is reported, the viewer keeps showing what it had
+pub fn load_trace(path: &str) -> Result<Root, String> {
+  match read_trace(path) {
+    Ok(root) if root.total_listeners == 0 => Err(String::from("the trace has no listeners")),
+    Ok(root) => Ok(root),
+    Err(e) => Err(e.to_string()),
+  }
+}
@@ -0,0 +1,137 @@
+//the change from one step of a graph to the next, what a diff of a trace holds. listeners write
+//their diffs with it and the viewer reads them back with it. a changed label or weight keeps its
+//old and new text apart, older traces join them into one "old===new" string which is still read
+use serde_json::{Value, json};
+use std::collections::BTreeMap;
+
+//a graph as one step shows it, in trace node ids
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Snapshot {
+  pub root: Option<u32>,
+  pub labels: BTreeMap<u32, String>,
+  pub edges: BTreeMap<(u32, u32), String>, //weights
+}
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct LabelChange {
+  pub id: u32,
+  pub old: String,
+  pub new: String,
+}
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct WeightChange {
+  pub from_id: u32,
+  pub to_id: u32,
+  pub old: String,
+  pub new: String,
+}
+
+//the six kinds of change, each sorted by id
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Diff {
+  pub added_nodes: Vec<(u32, String)>,
+  pub removed_nodes: Vec<(u32, String)>,
+  pub added_edges: Vec<((u32, u32), String)>,
+  pub removed_edges: Vec<((u32, u32), String)>,
+  pub labels_changed: Vec<LabelChange>,
+  pub weights_changed: Vec<WeightChange>,
+}
+
+pub fn diff(previous: &Snapshot, current: &Snapshot) -> Diff {
+  let only_in = |from: &BTreeMap<u32, String>, other: &BTreeMap<u32, String>| -> Vec<(u32, String)> {
+    from.iter().filter(|(id, _)| !other.contains_key(id)).map(|(id, label)| (*id, label.clone())).collect()
+  };
+  let edges_only_in = |from: &BTreeMap<(u32, u32), String>, other: &BTreeMap<(u32, u32), String>| -> Vec<((u32, u32), String)> {
+    from.iter().filter(|(ends, _)| !other.contains_key(ends)).map(|(ends, weight)| (*ends, weight.clone())).collect()
+  };
+  Diff {
+    added_nodes: only_in(&current.labels, &previous.labels),
+    removed_nodes: only_in(&previous.labels, &current.labels),
+    added_edges: edges_only_in(&current.edges, &previous.edges),
+    removed_edges: edges_only_in(&previous.edges, &current.edges),
+    labels_changed: current.labels.iter()
+      .filter_map(|(id, new)| {
+        let old = previous.labels.get(id).filter(|old| *old != new)?;
+        Some(LabelChange { id: *id, old: old.clone(), new: new.clone() })
+      })
+      .collect(),
+    weights_changed: current.edges.iter()
+      .filter_map(|(&(from_id, to_id), new)| {
+        let old = previous.edges.get(&(from_id, to_id)).filter(|old| *old != new)?;
+        Some(WeightChange { from_id, to_id, old: old.clone(), new: new.clone() })
+      })
+      .collect(),
+  }
+}
+
+impl Diff {
+  pub fn is_empty(&self) -> bool {
+    self.added_nodes.is_empty()
+      && self.removed_nodes.is_empty()
+      && self.added_edges.is_empty()
+      && self.removed_edges.is_empty()
+      && self.labels_changed.is_empty()
+      && self.weights_changed.is_empty()
+  }
+
+  //a diff entry of the trace format, the writer adds root_id and seq
+  pub fn to_value(&self, note: &str) -> Value {
+    let nodes = |nodes: &[(u32, String)]| -> Value {
+      nodes.iter().map(|(id, label)| json!({"id": id, "label": label})).collect()
+    };
+    let edges = |edges: &[((u32, u32), String)]| -> Value {
+      edges.iter().map(|((from_id, to_id), weight)| json!({"from_id": from_id, "to_id": to_id, "weight": weight})).collect()
+    };
+    json!({
+      "added_nodes": nodes(&self.added_nodes),
+      "removed_nodes": nodes(&self.removed_nodes),
+      "added_edges": edges(&self.added_edges),
+      "removed_edges": edges(&self.removed_edges),
+      "labels_changed": self.labels_changed.iter().map(LabelChange::to_value).collect::<Value>(),
+      "weights_changed": self.weights_changed.iter().map(WeightChange::to_value).collect::<Value>(),
+      "note": note,
+    })
+  }
+}
+
+//the text before the first === and after it, text without one is all new
+pub fn split_legacy(text: &str) -> (&str, &str) {
+  text.split_once("===").unwrap_or(("", text))
+}
+
+//old and new from their own fields, or from the "old===new" string under legacy_key
+fn old_and_new(value: &Value, legacy_key: &str) -> Option<(String, String)> {
+  if let (Some(old), Some(new)) = (value.get("old"), value.get("new")) {
+    return Some((old.as_str()?.to_string(), new.as_str()?.to_string()));
+  }
+  let (old, new) = split_legacy(value.get(legacy_key)?.as_str()?);
+  Some((old.to_string(), new.to_string()))
+}
+
+impl LabelChange {
+  pub fn to_value(&self) -> Value {
+    json!({"id": self.id, "old": self.old, "new": self.new})
+  }
+
+  //{"id": 3, "old": "a", "new": "b"} or {"id": 3, "label": "a===b"}
+  pub fn from_value(value: &Value) -> Option<Self> {
+    let id = value.get("id")?.as_u64()? as u32;
+    let (old, new) = old_and_new(value, "label")?;
+    Some(LabelChange { id, old, new })
+  }
+}
+
+impl WeightChange {
+  pub fn to_value(&self) -> Value {
+    json!({"from_id": self.from_id, "to_id": self.to_id, "old": self.old, "new": self.new})
+  }
+
+  //{"from_id": 1, "to_id": 2, "old": "a", "new": "b"} or the same with "label": "a===b"
+  pub fn from_value(value: &Value) -> Option<Self> {
+    let from_id = value.get("from_id")?.as_u64()? as u32;
+    let to_id = value.get("to_id")?.as_u64()? as u32;
+    let (old, new) = old_and_new(value, "label")?;
+    Some(WeightChange { from_id, to_id, old, new })
+  }
+}
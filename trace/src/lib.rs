@@ -0,0 +1,85 @@
+//records how a data structure changes, in the trace format the viewer reads. the rust side
+//of AbstractNode and Listener: a node type implements TraceNode and a Listener walks
+//everything reachable from a root on every graph_listen, recording what changed since the last one.
+//#[derive(TraceNode)] writes the impl from #[trace(...)] attributes on the fields, and diff
+//is what both the listeners and the viewer make of the change between two steps
+extern crate self as trace;
+
+pub mod diff;
+mod listener;
+
+pub use listener::{Listener, Trace};
+pub use trace_derive::TraceNode;
+
+use std::cell::RefCell;
+use std::fmt;
+use std::rc::Rc;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+
+//tells nodes apart for as long as they live, like node_id on the c++ side.
+//every node gets its own, a copied id makes two nodes one
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct TraceId(u64);
+
+impl TraceId {
+  pub fn new() -> Self {
+    TraceId(NEXT_ID.fetch_add(1, Ordering::Relaxed))
+  }
+}
+
+impl Default for TraceId {
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
+impl fmt::Display for TraceId {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "{}", self.0)
+  }
+}
+
+pub trait TraceNode {
+  fn trace_id(&self) -> TraceId;
+
+  //calls visit with every node this one points to and the label of the edge to it,
+  //the walk follows them in this order
+  fn nexts<'a>(&'a self, visit: &mut dyn FnMut(Next<'a>, String));
+
+  //the node's label
+  fn stringify(&self) -> String {
+    self.trace_id().to_string()
+  }
+}
+
+//a node nexts points to. most are borrowed for as long as the node pointing to them, one behind
+//a RefCell or a Weak can only be looked at for a moment, so the walk gets a handle to it instead
+pub enum Next<'a> {
+  Node(&'a dyn TraceNode),
+  Shared(Rc<dyn SharedNode>),
+}
+
+impl Next<'_> {
+  pub fn shared<T: SharedNode + 'static>(node: Rc<T>) -> Self {
+    Next::Shared(node)
+  }
+}
+
+//lends out a node for the length of one call, a RefCell borrows for it
+pub trait SharedNode {
+  fn with_node(&self, f: &mut dyn FnMut(&dyn TraceNode));
+}
+
+impl<T: TraceNode> SharedNode for RefCell<T> {
+  fn with_node(&self, f: &mut dyn FnMut(&dyn TraceNode)) {
+    f(&*self.borrow())
+  }
+}
+
+impl<T: TraceNode> SharedNode for T {
+  fn with_node(&self, f: &mut dyn FnMut(&dyn TraceNode)) {
+    f(self)
+  }
+}
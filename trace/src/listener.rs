@@ -0,0 +1,273 @@
+use crate::diff::{Snapshot, diff};
+use crate::{Next, SharedNode, TraceId, TraceNode};
+use serde_json::{Map, Value, json};
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use std::rc::Rc;
+use std::sync::{Arc, Mutex};
+
+//what a listener sees at one graph_listen
+fn take_snapshot(root: Option<&dyn TraceNode>, recording: &mut Recording) -> Snapshot {
+  let mut walk = Walk { snapshot: Snapshot::default(), seen: HashSet::new(), shared: Vec::new(), recording };
+  if let Some(root) = root {
+    walk.snapshot.root = Some(walk.reach(root).0);
+    walk.borrowed(root);
+    while let Some(shared) = walk.shared.pop() {
+      shared.with_node(&mut |node| walk.borrowed(node));
+    }
+  }
+  walk.snapshot
+}
+
+//keeps its own stacks, a long list would overflow the thread's. a node's id follows the order
+//it was first reached in
+struct Walk<'r> {
+  snapshot: Snapshot,
+  seen: HashSet<TraceId>,
+  shared: Vec<Rc<dyn SharedNode>>, //reached, what they point to is not walked yet
+  recording: &'r mut Recording,
+}
+
+impl Walk<'_> {
+  //the node's id, and whether this walk got to it for the first time
+  fn reach(&mut self, node: &dyn TraceNode) -> (u32, bool) {
+    let trace_id = node.trace_id();
+    if !self.seen.insert(trace_id) {
+      return (self.recording.ids[&trace_id], false);
+    }
+    let label = node.stringify();
+    let id = self.recording.node_id(trace_id, &label);
+    self.snapshot.labels.insert(id, label);
+    (id, true)
+  }
+
+  //everything start leads to through borrowed nodes. a shared node is only borrowed to reach it,
+  //what it points to is walked once the stack it is on gets to it
+  fn borrowed(&mut self, start: &dyn TraceNode) {
+    let mut stack = vec![start];
+    while let Some(node) = stack.pop() {
+      let id = self.recording.ids[&node.trace_id()];
+      node.nexts(&mut |next, weight| {
+        let next_id = match next {
+          Next::Node(next) => {
+            let (next_id, first) = self.reach(next);
+            if first {
+              stack.push(next);
+            }
+            next_id
+          }
+          Next::Shared(next) => {
+            let mut reached = (0, false);
+            next.with_node(&mut |node| reached = self.reach(node));
+            if reached.1 {
+              self.shared.push(next);
+            }
+            reached.0
+          }
+        };
+        self.snapshot.edges.insert((id, next_id), weight);
+      });
+    }
+  }
+}
+
+//shared by every listener of a trace
+struct Recording {
+  labels: Vec<String>, //the label every node was registered with, the id is the index
+  ids: HashMap<TraceId, u32>,
+  bases: Vec<Value>,
+  diffs: Vec<Vec<Value>>,
+  seq: u64,
+  out: Option<Box<dyn Write + Send>>,
+  error: Option<io::Error>, //the first failed write, nothing is written after it
+}
+
+impl Recording {
+  //nodes get trace ids from 0 in the order listeners first reach them
+  fn node_id(&mut self, trace_id: TraceId, label: &str) -> u32 {
+    if let Some(id) = self.ids.get(&trace_id) {
+      return *id;
+    }
+    let id = self.labels.len() as u32;
+    self.labels.push(label.to_string());
+    self.ids.insert(trace_id, id);
+    self.write(json!({"type": "node", "id": id, "label": label}));
+    id
+  }
+
+  fn write(&mut self, event: Value) {
+    if self.error.is_some() {
+      return;
+    }
+    if let Some(out) = self.out.as_mut()
+      && let Err(e) = writeln!(out, "{}", event)
+    {
+      self.error = Some(e);
+    }
+  }
+
+  fn flush(&mut self) {
+    if self.error.is_some() {
+      return;
+    }
+    if let Some(out) = self.out.as_mut()
+      && let Err(e) = out.flush()
+    {
+      self.error = Some(e);
+    }
+  }
+}
+
+//one trace file, every listener made from it writes into it
+#[derive(Clone)]
+pub struct Trace {
+  recording: Arc<Mutex<Recording>>,
+}
+
+impl Default for Trace {
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
+impl Trace {
+  //kept in memory until to_json or save
+  pub fn new() -> Self {
+    Trace {
+      recording: Arc::new(Mutex::new(Recording {
+        labels: Vec::new(),
+        ids: HashMap::new(),
+        bases: Vec::new(),
+        diffs: Vec::new(),
+        seq: 0,
+        out: None,
+        error: None,
+      })),
+    }
+  }
+
+  //also writes every event as a line the moment it happens, a program that crashes
+  //leaves a trace the viewer still reads up to the last step
+  pub fn streaming(out: impl Write + Send + 'static) -> Self {
+    let trace = Self::new();
+    trace.recording.lock().unwrap().out = Some(Box::new(out));
+    trace
+  }
+
+  pub fn create(path: impl AsRef<Path>) -> io::Result<Self> {
+    Ok(Self::streaming(BufWriter::new(File::create(path)?)))
+  }
+
+  //listener ids count up from 0 in the order they are made
+  pub fn listener(&self) -> Listener {
+    let mut recording = self.recording.lock().unwrap();
+    assert!(recording.bases.len() < u8::MAX as usize, "a trace has at most 254 listeners");
+    let id = recording.bases.len() as u8;
+    recording.bases.push(json!({"root": 0, "note": ""}));
+    recording.diffs.push(Vec::new());
+    Listener { id, previous: None, recording: Arc::clone(&self.recording) }
+  }
+
+  //the single object format
+  pub fn to_json(&self) -> String {
+    let recording = self.recording.lock().unwrap();
+    let nodes: Vec<Value> = recording.labels.iter()
+      .enumerate()
+      .map(|(id, label)| json!({"id": id, "label": label}))
+      .collect();
+    let mut bases = Map::new();
+    let mut diffs = Map::new();
+    for (listener_id, base) in recording.bases.iter().enumerate() {
+      bases.insert(format!("L{}", listener_id), base.clone());
+      diffs.insert(format!("L{}", listener_id), Value::from(recording.diffs[listener_id].clone()));
+    }
+    json!({
+      "specifiers": {"total_listeners": recording.bases.len()},
+      "nodes": nodes,
+      "bases": bases,
+      "diffs": diffs,
+    })
+    .to_string()
+  }
+
+  pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+    std::fs::write(path, self.to_json())
+  }
+
+  //the first write of a streaming trace that failed, if any
+  pub fn finish(&self) -> io::Result<()> {
+    let mut recording = self.recording.lock().unwrap();
+    recording.flush();
+    match recording.error.take() {
+      Some(e) => Err(e),
+      None => Ok(()),
+    }
+  }
+}
+
+pub struct Listener {
+  id: u8,
+  previous: Option<Snapshot>,
+  recording: Arc<Mutex<Recording>>,
+}
+
+impl Listener {
+  pub fn id(&self) -> u8 {
+    self.id
+  }
+
+  //the first call that reaches a node records the base, every later one a diff against the
+  //call before it. the viewer lays a base out from its root, so an empty structure has none
+  pub fn graph_listen<N: TraceNode>(&mut self, root: Option<&N>, note: &str) {
+    if root.is_none() && self.previous.is_none() {
+      return;
+    }
+    let mut recording = self.recording.lock().unwrap();
+    let snapshot = take_snapshot(root.map(|root| root as &dyn TraceNode), &mut recording);
+    let listener_id = self.id as usize;
+    match self.previous.as_ref() {
+      None => {
+        let base = base_value(&snapshot, note, &recording.labels);
+        recording.write(json!({"type": "base", "listener": self.id, "base": base}));
+        recording.bases[listener_id] = base;
+      }
+      Some(previous) => {
+        recording.seq += 1;
+        let mut diff = diff(previous, &snapshot).to_value(note);
+        diff["root_id"] = Value::from(snapshot.root.or(previous.root).unwrap_or(0));
+        diff["seq"] = Value::from(recording.seq);
+        recording.write(json!({"type": "diff", "listener": self.id, "diff": diff}));
+        recording.diffs[listener_id].push(diff);
+      }
+    }
+    recording.flush();
+    drop(recording);
+    self.previous = Some(snapshot);
+  }
+}
+
+//{"0": [{"1": "left"}], "1": [], "root": 0, "note": ""}, every reached node has a key. a node
+//whose label changed since another listener registered it has its current one under "labels"
+fn base_value(snapshot: &Snapshot, note: &str, registered: &[String]) -> Value {
+  let mut base = Map::new();
+  for id in snapshot.labels.keys() {
+    base.insert(id.to_string(), Value::Array(Vec::new()));
+  }
+  for ((from_id, to_id), weight) in &snapshot.edges {
+    if let Some(Value::Array(connections)) = base.get_mut(&from_id.to_string()) {
+      connections.push(json!({to_id.to_string(): weight}));
+    }
+  }
+  let labels: Map<String, Value> = snapshot.labels.iter()
+    .filter(|(id, label)| registered[**id as usize] != **label)
+    .map(|(id, label)| (id.to_string(), Value::from(label.as_str())))
+    .collect();
+  if !labels.is_empty() {
+    base.insert(String::from("labels"), Value::Object(labels));
+  }
+  base.insert(String::from("root"), Value::from(snapshot.root.unwrap_or(0)));
+  base.insert(String::from("note"), Value::from(note));
+  Value::Object(base)
+}
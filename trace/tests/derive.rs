@@ -0,0 +1,250 @@
+//what #[derive(TraceNode)] makes of every kind of child field, and that the walk gets through
+//structures too deep to recurse over
+use serde_json::Value;
+use std::cell::RefCell;
+use std::collections::{BTreeSet, VecDeque};
+use std::rc::{Rc, Weak};
+use std::sync::Arc;
+use trace::{Next, Trace, TraceId, TraceNode};
+
+#[derive(TraceNode)]
+struct Leaf {
+  id: TraceId,
+  #[trace(label)]
+  name: &'static str,
+}
+
+fn leaf(name: &'static str) -> Leaf {
+  Leaf { id: TraceId::new(), name }
+}
+
+//(child label, weight) for every node nexts hands out, shared ones borrowed to read them
+fn nexts_of(node: &dyn TraceNode) -> Vec<(String, String)> {
+  let mut found = Vec::new();
+  node.nexts(&mut |next, weight| {
+    let label = match next {
+      Next::Node(next) => next.stringify(),
+      Next::Shared(next) => {
+        let mut label = String::new();
+        next.with_node(&mut |next| label = next.stringify());
+        label
+      }
+    };
+    found.push((label, weight));
+  });
+  found
+}
+
+fn pairs(expected: &[(&str, &str)]) -> Vec<(String, String)> {
+  expected.iter().map(|(label, weight)| (label.to_string(), weight.to_string())).collect()
+}
+
+//(from label, weight, to label) of every edge of listener 0's base
+fn base_edges(trace: &Trace) -> BTreeSet<(String, String, String)> {
+  let json: Value = serde_json::from_str(&trace.to_json()).unwrap();
+  let label = |id: &str| json["nodes"][id.parse::<usize>().unwrap()]["label"].as_str().unwrap().to_string();
+  let mut edges = BTreeSet::new();
+  for (from_id, connections) in json["bases"]["L0"].as_object().unwrap() {
+    let Some(connections) = connections.as_array() else {
+      continue;
+    };
+    for connection in connections {
+      for (to_id, weight) in connection.as_object().unwrap() {
+        edges.insert((label(from_id), weight.as_str().unwrap().to_string(), label(to_id)));
+      }
+    }
+  }
+  edges
+}
+
+fn edge(from: &str, weight: &str, to: &str) -> (String, String, String) {
+  (from.to_string(), weight.to_string(), to.to_string())
+}
+
+#[derive(TraceNode)]
+struct Owned {
+  id: TraceId,
+  #[trace(label)]
+  name: &'static str,
+  #[trace(weight = "box")]
+  boxed: Box<Leaf>,
+  #[trace(weight = "rc")]
+  counted: Rc<Leaf>,
+  #[trace(weight = "arc")]
+  atomic: Arc<Leaf>,
+  #[trace(weight = "some")]
+  maybe: Option<Box<Leaf>>,
+  #[trace(weight = "none")]
+  missing: Option<Box<Leaf>>,
+  #[trace(weight = "{index}")]
+  list: Vec<Leaf>,
+  #[trace(weight = "deque {index}")]
+  deque: VecDeque<Box<Leaf>>,
+  #[trace(child)]
+  unweighted: Box<Leaf>,
+  ignored: Box<Leaf>,
+}
+
+#[test]
+fn owned_children_come_in_field_order() {
+  let node = Owned {
+    id: TraceId::new(),
+    name: "owned",
+    boxed: Box::new(leaf("b")),
+    counted: Rc::new(leaf("r")),
+    atomic: Arc::new(leaf("a")),
+    maybe: Some(Box::new(leaf("o"))),
+    missing: None,
+    list: vec![leaf("v0"), leaf("v1")],
+    deque: VecDeque::from([Box::new(leaf("d0"))]),
+    unweighted: Box::new(leaf("u")),
+    ignored: Box::new(leaf("i")),
+  };
+  assert_eq!(node.stringify(), "owned");
+  assert_eq!(
+    nexts_of(&node),
+    pairs(&[("b", "box"), ("r", "rc"), ("a", "arc"), ("o", "some"), ("v0", "0"), ("v1", "1"), ("d0", "deque 0"), ("u", "")])
+  );
+  assert_eq!(node.ignored.name, "i");
+}
+
+#[derive(TraceNode)]
+struct Shared {
+  id: TraceId,
+  #[trace(label)]
+  name: String,
+  #[trace(weight = "cell")]
+  cell: Option<Rc<RefCell<Shared>>>,
+  #[trace(weight = "weak cell {index}")]
+  weak_cells: Vec<Weak<RefCell<Shared>>>,
+  #[trace(weight = "weak")]
+  weak: Weak<Leaf>,
+}
+
+fn shared(name: &str) -> Rc<RefCell<Shared>> {
+  Rc::new(RefCell::new(Shared { id: TraceId::new(), name: name.to_string(), cell: None, weak_cells: Vec::new(), weak: Weak::new() }))
+}
+
+#[test]
+fn shared_children_are_lent_out_and_dropped_weak_ones_skipped() {
+  let kept = Rc::new(leaf("kept"));
+  let (a, b, gone) = (shared("a"), shared("b"), shared("gone"));
+  {
+    let mut a = a.borrow_mut();
+    a.cell = Some(Rc::clone(&b));
+    a.weak_cells = vec![Rc::downgrade(&gone), Rc::downgrade(&b)];
+    a.weak = Rc::downgrade(&kept);
+  }
+  drop(gone);
+  assert_eq!(nexts_of(&*a.borrow()), pairs(&[("b", "cell"), ("b", "weak cell 1"), ("kept", "weak")]));
+
+  drop(kept);
+  assert_eq!(nexts_of(&*a.borrow()), pairs(&[("b", "cell"), ("b", "weak cell 1")]));
+}
+
+#[test]
+fn nodes_borrowed_by_the_caller_can_still_be_walked() {
+  let (a, b) = (shared("a"), shared("b"));
+  a.borrow_mut().cell = Some(Rc::clone(&b));
+  b.borrow_mut().cell = Some(Rc::clone(&a));
+  b.borrow_mut().weak_cells.push(Rc::downgrade(&b));
+
+  let trace = Trace::new();
+  let mut listener = trace.listener();
+  let (a_held, b_held) = (a.borrow(), b.borrow());
+  listener.graph_listen(Some(&*a_held), "");
+  assert_eq!(a_held.name, "a");
+  assert_eq!(b_held.name, "b");
+  assert_eq!(
+    base_edges(&trace),
+    BTreeSet::from([edge("a", "cell", "b"), edge("b", "cell", "a"), edge("b", "weak cell 0", "b")])
+  );
+}
+
+#[test]
+#[should_panic(expected = "already mutably borrowed")]
+fn a_node_borrowed_mutably_cannot_be_walked() {
+  let (a, b) = (shared("a"), shared("b"));
+  a.borrow_mut().cell = Some(Rc::clone(&b));
+  let trace = Trace::new();
+  let mut listener = trace.listener();
+  let _writing = b.borrow_mut();
+  listener.graph_listen(Some(&*a.borrow()), "");
+}
+
+#[derive(TraceNode)]
+struct ListNode {
+  id: TraceId,
+  #[trace(label)]
+  value: usize,
+  #[trace(weight = "next")]
+  next: Option<Box<ListNode>>,
+}
+
+#[derive(TraceNode)]
+struct SharedListNode {
+  id: TraceId,
+  #[trace(label)]
+  value: usize,
+  #[trace(weight = "next")]
+  next: Option<Rc<RefCell<SharedListNode>>>,
+}
+
+const LONG: usize = 30_000;
+
+#[test]
+fn a_long_list_does_not_overflow_the_stack() {
+  let mut head: Option<Box<ListNode>> = None;
+  for value in (0..LONG).rev() {
+    head = Some(Box::new(ListNode { id: TraceId::new(), value, next: head }));
+  }
+  let trace = Trace::new();
+  let mut listener = trace.listener();
+  listener.graph_listen(head.as_deref(), "");
+  let edges = base_edges(&trace);
+  assert_eq!(edges.len(), LONG - 1);
+  assert!(edges.contains(&edge(&(LONG - 2).to_string(), "next", &(LONG - 1).to_string())));
+
+  //dropping it would recurse as deep
+  while let Some(mut node) = head {
+    head = node.next.take();
+  }
+}
+
+#[test]
+fn a_long_shared_list_does_not_overflow_the_stack() {
+  let mut head: Option<Rc<RefCell<SharedListNode>>> = None;
+  for value in (0..LONG).rev() {
+    head = Some(Rc::new(RefCell::new(SharedListNode { id: TraceId::new(), value, next: head })));
+  }
+  let trace = Trace::new();
+  let mut listener = trace.listener();
+  listener.graph_listen(Some(&*head.as_ref().unwrap().borrow()), "");
+  assert_eq!(base_edges(&trace).len(), LONG - 1);
+
+  while let Some(node) = head {
+    head = node.borrow_mut().next.take();
+  }
+}
+
+#[derive(TraceNode)]
+struct Wide {
+  id: TraceId,
+  #[trace(label)]
+  name: &'static str,
+  #[trace(weight = "{index}")]
+  children: Vec<Leaf>,
+}
+
+#[test]
+fn every_edge_of_a_wide_node_gets_its_own_weight() {
+  let wide = Wide { id: TraceId::new(), name: "wide", children: (0..LONG).map(|_| leaf("leaf")).collect() };
+  let found = nexts_of(&wide);
+  assert_eq!(found.len(), LONG);
+  assert!(found.iter().enumerate().all(|(index, (_, weight))| *weight == index.to_string()));
+
+  let trace = Trace::new();
+  let mut listener = trace.listener();
+  listener.graph_listen(Some(&wide), "");
+  assert_eq!(base_edges(&trace).len(), LONG);
+}
@@ -0,0 +1,103 @@
+//what diff makes of two steps, and that changes read back from both the structured and the
+//legacy "old===new" form
+use serde_json::{Value, json};
+use trace::diff::{Diff, LabelChange, Snapshot, WeightChange, diff, split_legacy};
+
+fn snapshot(root: u32, labels: &[(u32, &str)], edges: &[(u32, u32, &str)]) -> Snapshot {
+  Snapshot {
+    root: Some(root),
+    labels: labels.iter().map(|(id, label)| (*id, label.to_string())).collect(),
+    edges: edges.iter().map(|(from_id, to_id, weight)| ((*from_id, *to_id), weight.to_string())).collect(),
+  }
+}
+
+fn label_change(id: u32, old: &str, new: &str) -> LabelChange {
+  LabelChange { id, old: old.to_string(), new: new.to_string() }
+}
+
+fn weight_change(from_id: u32, to_id: u32, old: &str, new: &str) -> WeightChange {
+  WeightChange { from_id, to_id, old: old.to_string(), new: new.to_string() }
+}
+
+#[test]
+fn every_kind_of_change_is_found() {
+  let previous = snapshot(0, &[(0, "5"), (1, "3"), (2, "8"), (4, "1")], &[(0, 1, "left"), (0, 2, "right"), (1, 4, "left")]);
+  let current = snapshot(0, &[(0, "5"), (1, "4"), (2, "8"), (3, "9")], &[(0, 1, "left"), (0, 2, "middle"), (2, 3, "right")]);
+  let expected = Diff {
+    added_nodes: vec![(3, String::from("9"))],
+    removed_nodes: vec![(4, String::from("1"))],
+    added_edges: vec![((2, 3), String::from("right"))],
+    removed_edges: vec![((1, 4), String::from("left"))],
+    labels_changed: vec![label_change(1, "3", "4")],
+    weights_changed: vec![weight_change(0, 2, "right", "middle")],
+  };
+  assert_eq!(diff(&previous, &current), expected);
+
+  let back = diff(&current, &previous);
+  assert_eq!(back.added_nodes, expected.removed_nodes);
+  assert_eq!(back.removed_edges, expected.added_edges);
+  assert_eq!(back.labels_changed, vec![label_change(1, "4", "3")]);
+}
+
+#[test]
+fn the_same_step_twice_is_no_change() {
+  let step = snapshot(2, &[(0, "a"), (2, "b")], &[(2, 0, "")]);
+  assert!(diff(&step, &step).is_empty());
+  //the root is not part of a diff, the writer adds root_id itself
+  assert!(diff(&step, &Snapshot { root: Some(0), ..step.clone() }).is_empty());
+  assert!(!diff(&Snapshot::default(), &step).is_empty());
+}
+
+#[test]
+fn changes_read_back_from_their_own_fields() {
+  let label = json!({"id": 3, "old": "a", "new": "b"});
+  assert_eq!(LabelChange::from_value(&label), Some(label_change(3, "a", "b")));
+  let weight = json!({"from_id": 1, "to_id": 2, "old": "", "new": "left"});
+  assert_eq!(WeightChange::from_value(&weight), Some(weight_change(1, 2, "", "left")));
+
+  let changes = [label_change(7, "x", "y"), label_change(0, "", "")];
+  for change in changes {
+    assert_eq!(LabelChange::from_value(&change.to_value()), Some(change));
+  }
+  let change = weight_change(4, 9, "1", "2");
+  assert_eq!(WeightChange::from_value(&change.to_value()), Some(change));
+}
+
+#[test]
+fn legacy_changes_are_split_at_the_separator() {
+  let label = json!({"id": 3, "label": "a===b"});
+  assert_eq!(LabelChange::from_value(&label), Some(label_change(3, "a", "b")));
+  let weight = json!({"from_id": 1, "to_id": 2, "label": "left===right"});
+  assert_eq!(WeightChange::from_value(&weight), Some(weight_change(1, 2, "left", "right")));
+  //without a separator the whole text is new
+  assert_eq!(LabelChange::from_value(&json!({"id": 0, "label": "7"})), Some(label_change(0, "", "7")));
+}
+
+#[test]
+fn labels_with_the_separator_in_them() {
+  //the structured form keeps them whole
+  let change = label_change(5, "a===b", "===");
+  assert_eq!(LabelChange::from_value(&change.to_value()), Some(change));
+  let change = weight_change(1, 2, "===", "x===y===z");
+  assert_eq!(WeightChange::from_value(&change.to_value()), Some(change));
+
+  //the legacy form can only cut at the first one
+  assert_eq!(split_legacy("a===b===c"), ("a", "b===c"));
+  assert_eq!(split_legacy("===b"), ("", "b"));
+  assert_eq!(LabelChange::from_value(&json!({"id": 1, "label": "a===b===c"})), Some(label_change(1, "a", "b===c")));
+}
+
+#[test]
+fn broken_changes_are_not_read() {
+  let broken: [Value; 5] = [
+    json!({"old": "a", "new": "b"}),
+    json!({"id": "3", "old": "a", "new": "b"}),
+    json!({"id": 3, "old": 1, "new": "b"}),
+    json!({"id": 3}),
+    json!({"id": 3, "label": ["a", "b"]}),
+  ];
+  for value in &broken {
+    assert_eq!(LabelChange::from_value(value), None, "{} was read", value);
+  }
+  assert_eq!(WeightChange::from_value(&json!({"from_id": 1, "old": "a", "new": "b"})), None);
+}
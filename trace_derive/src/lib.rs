@@ -0,0 +1,203 @@
+//#[derive(TraceNode)] for structs. the id is the field of type TraceId, or the one marked
+//#[trace(id)]. #[trace(label)] makes a field's to_string the node's label, #[trace(child)]
+//makes the walk follow a field and #[trace(weight = "...")] names the edges it adds, {index}
+//standing for the position in a Vec. a child field can hold nodes through any nesting of
+//Box, Rc, Arc, Weak, Option, Vec and VecDeque, and a RefCell in an Rc or a Weak
+use proc_macro2::{Span, TokenStream};
+use quote::{format_ident, quote};
+use syn::{Data, DeriveInput, Field, Fields, GenericArgument, LitStr, PathArguments, Type, parse_macro_input};
+
+#[proc_macro_derive(TraceNode, attributes(trace))]
+pub fn derive_trace_node(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+  let input = parse_macro_input!(input as DeriveInput);
+  match expand(&input) {
+    Ok(tokens) => tokens.into(),
+    Err(e) => e.to_compile_error().into(),
+  }
+}
+
+#[derive(Default)]
+struct FieldAttrs {
+  id: bool,
+  label: bool,
+  child: bool,
+  weight: Option<LitStr>,
+}
+
+fn field_attrs(field: &Field) -> syn::Result<FieldAttrs> {
+  let mut attrs = FieldAttrs::default();
+  for attr in field.attrs.iter().filter(|attr| attr.path().is_ident("trace")) {
+    attr.parse_nested_meta(|meta| {
+      if meta.path.is_ident("id") {
+        attrs.id = true;
+      } else if meta.path.is_ident("label") {
+        attrs.label = true;
+      } else if meta.path.is_ident("child") {
+        attrs.child = true;
+      } else if meta.path.is_ident("weight") {
+        attrs.weight = Some(meta.value()?.parse()?);
+        attrs.child = true;
+      } else {
+        return Err(meta.error("expected id, label, child or weight = \"...\""));
+      }
+      Ok(())
+    })?;
+  }
+  Ok(attrs)
+}
+
+//the last path segment of a type, and its first type argument if it has one
+fn split_type(ty: &Type) -> Option<(String, Option<&Type>)> {
+  let Type::Path(path) = ty else {
+    return None;
+  };
+  let segment = path.path.segments.last()?;
+  let argument = match &segment.arguments {
+    PathArguments::AngleBracketed(arguments) => arguments.args.iter().find_map(|argument| match argument {
+      GenericArgument::Type(ty) => Some(ty),
+      _ => None,
+    }),
+    _ => None,
+  };
+  Some((segment.ident.to_string(), argument))
+}
+
+struct Walk {
+  depth: usize,
+  in_vec: bool,
+}
+
+impl Walk {
+  //code that passes every node the expression, a reference to ty, holds to visit along with
+  //weight. inside a Vec weight can use the position as `index`
+  fn nodes(&mut self, ty: &Type, expr: TokenStream, weight: &TokenStream) -> syn::Result<TokenStream> {
+    self.depth += 1;
+    let inner = format_ident!("inner_{}", self.depth);
+    let is_cell = |ty: &Type| matches!(split_type(ty), Some((name, Some(_))) if name == "RefCell");
+    let tokens = match split_type(ty) {
+      Some((wrapper, Some(argument))) if wrapper == "Rc" && is_cell(argument) => quote! {
+        visit(::trace::Next::shared(::std::rc::Rc::clone(#expr)), #weight);
+      },
+      Some((wrapper, Some(argument))) if wrapper == "Box" || wrapper == "Rc" || wrapper == "Arc" => {
+        if is_cell(argument) {
+          return Err(syn::Error::new_spanned(ty, "a RefCell child has to be in an Rc"));
+        }
+        self.nodes(argument, quote!(&**#expr), weight)?
+      }
+      Some((wrapper, Some(_))) if wrapper == "RefCell" => {
+        return Err(syn::Error::new_spanned(ty, "a RefCell child has to be in an Rc"));
+      }
+      Some((wrapper, Some(_))) if wrapper == "Weak" => quote! {
+        if let Some(#inner) = (#expr).upgrade() {
+          visit(::trace::Next::shared(#inner), #weight);
+        }
+      },
+      Some((wrapper, Some(argument))) if wrapper == "Option" => {
+        let nodes = self.nodes(argument, quote!(#inner), weight)?;
+        quote! {
+          if let Some(#inner) = #expr {
+            #nodes
+          }
+        }
+      }
+      Some((wrapper, Some(argument))) if wrapper == "Vec" || wrapper == "VecDeque" => {
+        self.in_vec = true;
+        let nodes = self.nodes(argument, quote!(#inner), weight)?;
+        quote! {
+          for (index, #inner) in (#expr).iter().enumerate() {
+            #nodes
+          }
+        }
+      }
+      _ => quote! {
+        visit(::trace::Next::Node(#expr), #weight);
+      },
+    };
+    self.depth -= 1;
+    Ok(tokens)
+  }
+}
+
+fn expand(input: &DeriveInput) -> syn::Result<TokenStream> {
+  let Data::Struct(data) = &input.data else {
+    return Err(syn::Error::new_spanned(&input.ident, "TraceNode can only be derived for structs"));
+  };
+  let accessors: Vec<TokenStream> = match &data.fields {
+    Fields::Named(fields) => fields.named.iter().map(|field| {
+      let name = field.ident.as_ref().unwrap();
+      quote!(#name)
+    }).collect(),
+    Fields::Unnamed(fields) => (0..fields.unnamed.len()).map(|i| {
+      let index = syn::Index::from(i);
+      quote!(#index)
+    }).collect(),
+    Fields::Unit => Vec::new(),
+  };
+
+  let mut id = None;
+  let mut typed_ids = Vec::new();
+  let mut label = None;
+  let mut visits = Vec::new();
+  for (field, accessor) in data.fields.iter().zip(&accessors) {
+    let attrs = field_attrs(field)?;
+    if attrs.id {
+      if id.is_some() {
+        return Err(syn::Error::new_spanned(field, "only one field can be #[trace(id)]"));
+      }
+      id = Some(accessor.clone());
+    }
+    if matches!(split_type(&field.ty), Some((name, None)) if name == "TraceId") {
+      typed_ids.push(accessor.clone());
+    }
+    if attrs.label {
+      if label.is_some() {
+        return Err(syn::Error::new_spanned(field, "only one field can be #[trace(label)]"));
+      }
+      label = Some(accessor.clone());
+    }
+    if !attrs.child {
+      continue;
+    }
+
+    let weight = match &attrs.weight {
+      Some(weight) if weight.value().contains("{index}") => quote!(#weight.replace("{index}", &index.to_string())),
+      Some(weight) => quote!(::std::string::String::from(#weight)),
+      None => quote!(::std::string::String::new()),
+    };
+    let mut walk = Walk { depth: 0, in_vec: false };
+    visits.push(walk.nodes(&field.ty, quote!(&self.#accessor), &weight)?);
+    if let Some(weight) = attrs.weight.as_ref().filter(|weight| weight.value().contains("{index}"))
+      && !walk.in_vec
+    {
+      return Err(syn::Error::new_spanned(weight, "{index} needs a Vec of children"));
+    }
+  }
+
+  let id = match (id, typed_ids.as_slice()) {
+    (Some(id), _) => id,
+    (None, [id]) => id.clone(),
+    (None, []) => return Err(syn::Error::new(Span::call_site(), "TraceNode needs a TraceId field")),
+    (None, _) => return Err(syn::Error::new(Span::call_site(), "more than one TraceId field, mark the node's own with #[trace(id)]")),
+  };
+  let stringify = label.map(|label| quote! {
+    fn stringify(&self) -> ::std::string::String {
+      ::std::string::ToString::to_string(&self.#label)
+    }
+  });
+  let name = &input.ident;
+  let (impl_generics, type_generics, where_clause) = input.generics.split_for_impl();
+  Ok(quote! {
+    impl #impl_generics ::trace::TraceNode for #name #type_generics #where_clause {
+      fn trace_id(&self) -> ::trace::TraceId {
+        self.#id
+      }
+
+      #[allow(unused_variables)]
+      fn nexts<'trace_node>(&'trace_node self, visit: &mut dyn FnMut(::trace::Next<'trace_node>, ::std::string::String)) {
+        #(#visits)*
+      }
+
+      #stringify
+    }
+  })
+}